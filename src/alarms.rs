@@ -0,0 +1,317 @@
+//! Threshold alarms of the live modes, configured as `[[alarms]]` in `config.toml`, e.g.
+//! over-level (short-term loudness above −10 LUFS for 3 s) or dead air (below −60 LUFS for 10 s).
+//! A rule triggers once when its condition has held for its duration and clears when it stops.
+use crate::recorder::UtcTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Entries kept in the alarm log popup
+const MAX_LOG_ENTRIES: usize = 200;
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct AlarmRule {
+    pub name: String,
+    pub metric: Metric,
+    /// Triggers when the metric is above this value
+    pub above: Option<f64>,
+    /// Triggers when the metric is below this value
+    pub below: Option<f64>,
+    /// Seconds the condition must hold
+    #[serde(default)]
+    pub duration: f64,
+    /// Run with `sh -c` when the alarm triggers, with `SOUNDSCOPE_ALARM` and `SOUNDSCOPE_VALUE` set
+    pub command: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Momentary loudness in LUFS
+    Momentary,
+    /// Short-term loudness in LUFS
+    Shortterm,
+    /// True peak in dBTP
+    TruePeak,
+    /// Sample peak in dBFS
+    SamplePeak,
+    /// Correlation of the left and right channels, from -1 to 1
+    Correlation,
+}
+
+impl Metric {
+    /// Panel flashed while an alarm on this metric is active.
+    pub fn panel(self) -> Panel {
+        match self {
+            Metric::Momentary | Metric::Shortterm => Panel::Loudness,
+            Metric::TruePeak => Panel::TruePeak,
+            Metric::SamplePeak => Panel::Waveform,
+            Metric::Correlation => Panel::Spectrum,
+        }
+    }
+
+    fn unit(self) -> &'static str {
+        match self {
+            Metric::Momentary | Metric::Shortterm => " LUFS",
+            Metric::TruePeak => " dBTP",
+            Metric::SamplePeak => " dBFS",
+            Metric::Correlation => "",
+        }
+    }
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Metric::Momentary => write!(f, "momentary"),
+            Metric::Shortterm => write!(f, "short-term"),
+            Metric::TruePeak => write!(f, "true peak"),
+            Metric::SamplePeak => write!(f, "sample peak"),
+            Metric::Correlation => write!(f, "correlation"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Panel {
+    Waveform,
+    Spectrum,
+    /// The LUFS chart and the short-term box
+    Loudness,
+    TruePeak,
+}
+
+/// Values the rules are checked against, levels are `-inf` when silent
+/// and the correlation is NaN, which never triggers, when silent.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Measurements {
+    pub momentary: f64,
+    pub shortterm: f64,
+    pub true_peak: f64,
+    pub sample_peak: f64,
+    pub correlation: f64,
+}
+
+impl Measurements {
+    fn get(&self, metric: Metric) -> f64 {
+        match metric {
+            Metric::Momentary => self.momentary,
+            Metric::Shortterm => self.shortterm,
+            Metric::TruePeak => self.true_peak,
+            Metric::SamplePeak => self.sample_peak,
+            Metric::Correlation => self.correlation,
+        }
+    }
+}
+
+/// Correlation of interleaved stereo samples, NaN if a channel is silent.
+pub fn correlation(samples: &[f32]) -> f64 {
+    let (mut lr, mut ll, mut rr) = (0f64, 0f64, 0f64);
+    for frame in samples.chunks_exact(2) {
+        let (l, r) = (frame[0] as f64, frame[1] as f64);
+        lr += l * r;
+        ll += l * l;
+        rr += r * r;
+    }
+    lr / (ll * rr).sqrt()
+}
+
+/// Line of the alarm log popup.
+pub struct AlarmLogEntry {
+    /// `13:05:09` in UTC
+    pub time: String,
+    pub message: String,
+}
+
+struct RuleState {
+    rule: AlarmRule,
+    /// When the condition started to hold
+    since: Option<Instant>,
+    active: bool,
+}
+
+#[derive(Default)]
+pub struct Alarms {
+    rules: Vec<RuleState>,
+    log: Vec<AlarmLogEntry>,
+}
+
+impl Alarms {
+    pub fn new(rules: Vec<AlarmRule>) -> Self {
+        Self {
+            rules: rules
+                .into_iter()
+                .map(|rule| RuleState {
+                    rule,
+                    since: None,
+                    active: false,
+                })
+                .collect(),
+            log: Vec::new(),
+        }
+    }
+
+    /// Checks every rule, returns `true` if an alarm triggered.
+    pub fn update(&mut self, measurements: &Measurements, now: Instant) -> bool {
+        let mut triggered = false;
+        let mut messages = Vec::new();
+        for state in &mut self.rules {
+            let rule = &state.rule;
+            let value = measurements.get(rule.metric);
+            let holds = rule.above.is_some_and(|above| value > above)
+                || rule.below.is_some_and(|below| value < below);
+            if !holds {
+                if state.active {
+                    messages.push(format!("{} cleared", rule.name));
+                }
+                state.since = None;
+                state.active = false;
+                continue;
+            }
+            let since = *state.since.get_or_insert(now);
+            if state.active || now - since < Duration::from_secs_f64(rule.duration.max(0.)) {
+                continue;
+            }
+            state.active = true;
+            triggered = true;
+            let value = if value.is_finite() {
+                format!("{value:.1}{}", rule.metric.unit())
+            } else {
+                String::from("silence")
+            };
+            messages.push(format!("{}: {} {value}", rule.name, rule.metric));
+            if let Some(command) = &rule.command {
+                run_command(command, &rule.name, &value);
+            }
+        }
+        for message in messages {
+            self.push_log(message);
+        }
+        triggered
+    }
+
+    /// Forgets the conditions, e.g. when the input changes.
+    pub fn reset(&mut self) {
+        for state in &mut self.rules {
+            state.since = None;
+            state.active = false;
+        }
+    }
+
+    pub fn is_active(&self, panel: Panel) -> bool {
+        self.rules
+            .iter()
+            .any(|state| state.active && state.rule.metric.panel() == panel)
+    }
+
+    pub fn log(&self) -> &[AlarmLogEntry] {
+        &self.log
+    }
+
+    fn push_log(&mut self, message: String) {
+        let time = UtcTime::new(SystemTime::now());
+        if self.log.len() == MAX_LOG_ENTRIES {
+            self.log.remove(0);
+        }
+        self.log.push(AlarmLogEntry {
+            time: format!("{:02}:{:02}:{:02}", time.hour, time.minute, time.second),
+            message,
+        });
+    }
+}
+
+/// Runs the command of a rule without waiting for it.
+fn run_command(command: &str, name: &str, value: &str) {
+    let mut command = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    let child = command
+        .env("SOUNDSCOPE_ALARM", name)
+        .env("SOUNDSCOPE_VALUE", value)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    if let Ok(mut child) = child {
+        // reap the process when it exits
+        thread::spawn(move || child.wait());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules() {
+        let rule = |name: &str, metric, above, below, duration| AlarmRule {
+            name: name.to_string(),
+            metric,
+            above,
+            below,
+            duration,
+            command: None,
+        };
+        let mut alarms = Alarms::new(vec![
+            rule("Over level", Metric::Shortterm, Some(-10.), None, 3.),
+            rule("Dead air", Metric::Shortterm, None, Some(-60.), 10.),
+            rule("Phase", Metric::Correlation, None, Some(0.), 0.),
+        ]);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let loud = Measurements {
+            shortterm: -8.,
+            correlation: 0.5,
+            ..Measurements::default()
+        };
+        assert!(!alarms.update(&loud, at(0)));
+        assert!(!alarms.update(&loud, at(2)));
+        assert!(alarms.update(&loud, at(3)));
+        assert!(alarms.is_active(Panel::Loudness));
+        // triggers once per episode
+        assert!(!alarms.update(&loud, at(4)));
+
+        let silent = Measurements {
+            shortterm: f64::NEG_INFINITY,
+            correlation: f64::NAN,
+            ..Measurements::default()
+        };
+        assert!(!alarms.update(&silent, at(5)));
+        assert!(!alarms.is_active(Panel::Loudness));
+        assert!(alarms.update(&silent, at(15)));
+
+        let messages: Vec<&str> = alarms.log().iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Over level: short-term -8.0 LUFS",
+                "Over level cleared",
+                "Dead air: short-term silence"
+            ]
+        );
+
+        let out_of_phase = Measurements {
+            correlation: -0.5,
+            ..loud
+        };
+        assert!(alarms.update(&out_of_phase, at(16)));
+        assert!(alarms.is_active(Panel::Spectrum));
+    }
+
+    #[test]
+    fn test_correlation() {
+        assert_eq!(correlation(&[0.5, 0.5, -0.25, -0.25]), 1.);
+        assert_eq!(correlation(&[0.5, -0.5, -0.25, 0.25]), -1.);
+        assert!(correlation(&[0.5, 0., 0.5, 0.]).is_nan());
+    }
+}
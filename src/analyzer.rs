@@ -6,6 +6,10 @@ use eyre::Result;
 use spectrum_analyzer::{
     FrequencyLimit, samples_fft_to_spectrum, scaling::SpectrumDataStats, windows::hann_window,
 };
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 // Approach from <https://dsp.stackexchange.com/questions/32076/fft-to-spectrum-in-decibel>:
 fn scale_to_dbfs(val: f32, stats: &SpectrumDataStats) -> f32 {
@@ -26,6 +30,70 @@ fn scale_to_dbfs(val: f32, stats: &SpectrumDataStats) -> f32 {
     }
 }
 
+/// Min-max waveform of a whole file with one pair of points per millisecond,
+/// the same layout as [`Analyzer::get_waveform`] returns.
+/// It is built incrementally while the file is decoded, so the samples are never traversed twice.
+pub struct WaveformOverview {
+    /// Interleaved samples in one millisecond, not an integer for e.g. 44.1 kHz
+    samples_per_point: f64,
+    points: Vec<(f64, f64)>,
+    /// Samples pushed so far
+    pushed: usize,
+    min: f32,
+    max: f32,
+}
+
+impl WaveformOverview {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        Self {
+            samples_per_point: (sample_rate as f64 * channels as f64 / 1000.).max(1.),
+            points: Vec::new(),
+            pushed: 0,
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+        }
+    }
+
+    /// Adds interleaved samples that follow the previously pushed ones.
+    pub fn push(&mut self, samples: &[f32]) {
+        let mut end = self.point_end();
+        for &sample in samples {
+            self.min = self.min.min(sample);
+            self.max = self.max.max(sample);
+            self.pushed += 1;
+            if self.pushed >= end {
+                self.flush();
+                end = self.point_end();
+            }
+        }
+    }
+
+    /// Adds the last incomplete millisecond once all samples are pushed.
+    pub fn finish(&mut self) {
+        if self.min <= self.max {
+            self.flush();
+        }
+    }
+
+    /// Points of the samples pushed so far, the last incomplete millisecond only after [`WaveformOverview::finish`].
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    /// Index of the sample after the last one of the current point
+    fn point_end(&self) -> usize {
+        ((self.points.len() / 2 + 1) as f64 * self.samples_per_point) as usize
+    }
+
+    fn flush(&mut self) {
+        let x = (self.points.len() / 2) as f64;
+        self.points.push((x, self.min as f64));
+        self.points.push((x, self.max as f64));
+        self.min = f32::INFINITY;
+        self.max = f32::NEG_INFINITY;
+    }
+}
+
 pub struct Analyzer {
     loudness_meter: EbuR128,
     sample_rate: u32,
@@ -144,6 +212,10 @@ impl Analyzer {
         self.loudness_meter.reset();
     }
 
+    pub fn get_momentary_lufs(&mut self) -> Result<f64, ebur128::Error> {
+        self.loudness_meter.loudness_momentary()
+    }
+
     pub fn get_shortterm_lufs(&mut self) -> Result<f64, ebur128::Error> {
         self.loudness_meter.loudness_shortterm()
     }
@@ -163,22 +235,90 @@ impl Analyzer {
         Ok((tp_left, tp_right))
     }
 
+    /// Highest true peak of all channels in the samples added last.
+    pub fn get_prev_true_peak(&mut self) -> Result<f64, ebur128::Error> {
+        (0..self.loudness_meter.channels()).try_fold(0f64, |peak, channel| {
+            Ok(peak.max(self.loudness_meter.prev_true_peak(channel)?))
+        })
+    }
+
     pub fn sample_rate(&self) -> u32 {
         self.sample_rate
     }
+}
 
-    pub fn calculate_integrated_lufs(&mut self, channels: u32, samples: &[f32]) -> Option<f64> {
-        let Ok(mut analyzer) = EbuR128::new(channels, self.sample_rate, Mode::all()) else {
-            return None;
-        };
+/// Programme loudness of the microphone, `None` until a device is selected.
+pub type SharedProgramMeter = Arc<Mutex<Option<ProgramMeter>>>;
 
-        for chunk in samples.chunks(self.sample_rate as usize * 2) {
-            if analyzer.add_frames_f32(chunk).is_err() {
-                return None;
-            }
+/// Integrated loudness, loudness range and true peak of the microphone since the last reset.
+/// Unlike [`Analyzer`], which gets the latest window on every frame, it is fed every captured
+/// sample once by the capture callback.
+pub struct ProgramMeter {
+    loudness_meter: EbuR128,
+    sample_rate: u32,
+    /// Frames measured since the last reset
+    frames: u64,
+    paused: bool,
+}
+
+impl ProgramMeter {
+    pub fn new(channels: u32, rate: u32) -> Result<Self> {
+        // the histogram keeps the memory constant however long the measurement runs
+        let mode = Mode::I | Mode::LRA | Mode::TRUE_PEAK | Mode::HISTOGRAM;
+        Ok(Self {
+            loudness_meter: EbuR128::new(channels, rate, mode)?,
+            sample_rate: rate,
+            frames: 0,
+            paused: false,
+        })
+    }
+
+    /// Measures the samples unless the measurement is paused.
+    pub fn add_samples(&mut self, samples: &[f32]) -> Result<(), ebur128::Error> {
+        if self.paused {
+            return Ok(());
         }
+        self.loudness_meter.add_frames_f32(samples)?;
+        self.frames += (samples.len() / self.loudness_meter.channels() as usize) as u64;
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.loudness_meter.reset();
+        self.frames = 0;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Duration of the measured audio, pauses excluded.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64(self.frames as f64 / self.sample_rate as f64)
+    }
+
+    pub fn get_integrated_lufs(&self) -> Result<f64, ebur128::Error> {
+        self.loudness_meter.loudness_global()
+    }
+
+    pub fn get_loudness_range(&self) -> Result<f64, ebur128::Error> {
+        self.loudness_meter.loudness_range()
+    }
+
+    /// Like [`Analyzer::get_true_peak`], mono is shown on both sides.
+    pub fn get_true_peak(&self) -> Result<(f64, f64), ebur128::Error> {
+        let tp_left = self.loudness_meter.true_peak(0)?;
+        let tp_right = if self.loudness_meter.channels() > 1 {
+            self.loudness_meter.true_peak(1)?
+        } else {
+            tp_left
+        };
 
-        analyzer.loudness_global().ok()
+        Ok((tp_left, tp_right))
     }
 }
 
@@ -321,6 +461,31 @@ mod tests {
         );
     }
 
+    #[test]
+    /// Tests that the overview does not depend on how the samples are split into chunks
+    fn test_waveform_overview() {
+        // 1 second of stereo at 44.1 kHz
+        let samples: Vec<f32> = (0..88200).map(|i| (i as f32 / 100.0).sin()).collect();
+
+        let mut whole = WaveformOverview::new(44100, 2);
+        whole.push(&samples);
+        whole.finish();
+        // one min/max pair per millisecond
+        assert_eq!(whole.points().len(), 1000 * 2);
+
+        let mut chunked = WaveformOverview::new(44100, 2);
+        for chunk in samples.chunks(1153) {
+            chunked.push(chunk);
+        }
+        chunked.finish();
+        assert_eq!(chunked.points(), whole.points());
+
+        for pair in whole.points().chunks(2) {
+            assert_eq!(pair[0].0, pair[1].0);
+            assert!(pair[0].1 <= pair[1].1);
+        }
+    }
+
     #[test]
     /// Tests the waveform generation
     fn test_get_waveform() {
@@ -396,4 +561,29 @@ mod tests {
         let result = analyzer.create_loudness_meter(6, 96000); // 5.1 surround, 96kHz
         assert!(result.is_ok());
     }
+
+    #[test]
+    /// Tests pausing and resetting the programme loudness
+    fn test_program_meter() {
+        let mut meter = ProgramMeter::new(1, 48000).unwrap();
+        let samples: Vec<f32> = (0..48000 * 2)
+            .map(|i| 0.5 * (1000.0 * 2.0 * std::f32::consts::PI * (i as f32 / 48000.0)).sin())
+            .collect();
+
+        meter.add_samples(&samples).unwrap();
+        assert_eq!(meter.elapsed(), Duration::from_secs(2));
+        let integrated = meter.get_integrated_lufs().unwrap();
+        assert!((integrated + 9.).abs() < 0.5, "{integrated}");
+        let (left, right) = meter.get_true_peak().unwrap();
+        assert_eq!(left, right);
+
+        meter.toggle_pause();
+        meter.add_samples(&samples).unwrap();
+        assert_eq!(meter.elapsed(), Duration::from_secs(2));
+
+        meter.toggle_pause();
+        meter.reset();
+        assert_eq!(meter.elapsed(), Duration::ZERO);
+        assert_eq!(meter.get_integrated_lufs().unwrap(), f64::NEG_INFINITY);
+    }
 }
@@ -1,10 +1,28 @@
 //! This module is responsible for capturing audio from the PC and microphone.
-use crate::tui::RBuffer;
+//! The microphone is captured with cpal. What the PC is playing is captured from PulseAudio/PipeWire
+//! monitor sources with `parec` and from ALSA loopback devices with `arecord`.
+use crate::{
+    analyzer::SharedProgramMeter,
+    loudness_log::SharedLog,
+    raw_pcm::{RawEncoding, RawFormat},
+    recorder::{SharedRecorder, SharedTrigger},
+    stdin_input::{self, InputThread},
+    tui::RBuffer,
+};
 use cpal::{
     Device, Stream, StreamConfig, default_host,
     traits::{DeviceTrait, HostTrait},
 };
 use eyre::Result;
+use std::process::Command;
+
+/// Format the system output is recorded in
+pub const SYSTEM_FORMAT: RawFormat = RawFormat {
+    encoding: RawEncoding::F32Le,
+    sample_rate: 48000,
+    channels: 2,
+    offset: 0,
+};
 
 pub struct AudioDevice {
     device: Device,
@@ -28,8 +46,14 @@ impl AudioDevice {
     }
 }
 
+/// Captures `audio_device` into `latest_captured_samples` and `program_meter`, and into
+/// `recorder` while it records, `trigger` while it is armed and `log` while it logs.
 pub fn build_input_stream(
     latest_captured_samples: RBuffer,
+    program_meter: SharedProgramMeter,
+    recorder: SharedRecorder,
+    trigger: SharedTrigger,
+    log: SharedLog,
     audio_device: &AudioDevice,
 ) -> Result<Stream> {
     let dev = audio_device.device();
@@ -38,14 +62,25 @@ pub fn build_input_stream(
     let stream = dev.build_input_stream(
         cfg,
         move |data: &[f32], _info| {
+            if let Some(meter) = program_meter.lock().unwrap().as_mut()
+                && let Err(_err) = meter.add_samples(data)
+            {
+                // only whole frames are captured
+            }
+            if let Some(recorder) = recorder.lock().unwrap().as_mut() {
+                recorder.write(data);
+            }
+            if let Some(log) = log.lock().unwrap().as_mut() {
+                log.write(data);
+            }
             let mut audio_buf = latest_captured_samples.lock().unwrap();
+            // the pre-roll is the audio before `data`
+            if let Some(trigger) = trigger.lock().unwrap().as_mut() {
+                trigger.write(data, &audio_buf);
+            }
             if is_mono {
-                let data: Vec<f32> = data
-                    .iter()
-                    .enumerate()
-                    .flat_map(|(i, &x)| if i == 0 { vec![x] } else { vec![0., x] })
-                    .collect();
-                audio_buf.extend(data);
+                // left channel, the right one is silent
+                audio_buf.extend(data.iter().flat_map(|&x| [x, 0.]));
             } else {
                 audio_buf.extend(data.iter().copied());
             }
@@ -73,3 +108,146 @@ pub fn list_input_devs() -> Vec<(String, Device)> {
     devs.sort_by(|(n1, _), (n2, _)| n1.cmp(n2));
     devs
 }
+
+/// A source of what the computer is playing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SystemSource {
+    /// PulseAudio/PipeWire monitor source of an output, e.g. `alsa_output.pci-0000_00_1f.3.analog-stereo.monitor`
+    Monitor(String),
+    /// Capture side of an ALSA loopback device (`snd-aloop`), e.g. `plughw:CARD=Loopback,DEV=1`
+    AlsaLoopback(String),
+}
+
+impl SystemSource {
+    /// Shown in the sources list and in the waveform title.
+    pub fn name(&self) -> &str {
+        match self {
+            SystemSource::Monitor(name) | SystemSource::AlsaLoopback(name) => name,
+        }
+    }
+
+    /// Command writing the captured audio to its standard output as [`SYSTEM_FORMAT`].
+    fn command(&self) -> Command {
+        match self {
+            SystemSource::Monitor(name) => {
+                let mut command = Command::new("parec");
+                command.args([
+                    &format!("--device={name}"),
+                    "--format=float32le",
+                    &format!("--rate={}", SYSTEM_FORMAT.sample_rate),
+                    &format!("--channels={}", SYSTEM_FORMAT.channels),
+                    "--raw",
+                ]);
+                command
+            }
+            SystemSource::AlsaLoopback(device) => {
+                let mut command = Command::new("arecord");
+                command.args([
+                    "-q",
+                    "-D",
+                    device,
+                    "-f",
+                    "FLOAT_LE",
+                    "-r",
+                    &SYSTEM_FORMAT.sample_rate.to_string(),
+                    "-c",
+                    &SYSTEM_FORMAT.channels.to_string(),
+                    "-t",
+                    "raw",
+                ]);
+                command
+            }
+        }
+    }
+}
+
+/// Starts capturing a system source, see [`stdin_input::spawn_command`].
+/// Captures `source`, the samples are also written to `log` while it logs.
+pub fn capture_system(source: &SystemSource, log: SharedLog) -> Result<InputThread> {
+    stdin_input::spawn_command(
+        source.command(),
+        SYSTEM_FORMAT,
+        source.name().to_string(),
+        Some(log),
+    )
+}
+
+/// Lists the monitor sources of PulseAudio/PipeWire, the monitor of the default output first,
+/// followed by ALSA loopback devices. Empty if neither is available.
+pub fn list_system_sources() -> Vec<SystemSource> {
+    let output = |program: &str, args: &[&str]| {
+        Command::new(program)
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+            .unwrap_or_default()
+    };
+    let mut monitors = parse_monitor_sources(&output("pactl", &["list", "short", "sources"]));
+    let default_monitor = format!("{}.monitor", output("pactl", &["get-default-sink"]).trim());
+    if let Some(index) = monitors.iter().position(|m| *m == default_monitor) {
+        monitors[..=index].rotate_right(1);
+    }
+    let cards = std::fs::read_to_string("/proc/asound/cards").unwrap_or_default();
+    monitors
+        .into_iter()
+        .map(SystemSource::Monitor)
+        .chain(
+            parse_loopback_cards(&cards)
+                .into_iter()
+                .map(SystemSource::AlsaLoopback),
+        )
+        .collect()
+}
+
+/// Names of the monitor sources in the output of `pactl list short sources`,
+/// tab-separated lines of index, name, driver, format and state.
+fn parse_monitor_sources(sources: &str) -> Vec<String> {
+    sources
+        .lines()
+        .filter_map(|line| line.split('\t').nth(1))
+        .filter(|name| name.ends_with(".monitor"))
+        .map(String::from)
+        .collect()
+}
+
+/// Capture devices of the loopback cards in `/proc/asound/cards`, where a card is listed as
+/// ` 2 [Loopback       ]: Loopback - Loopback`. Audio played to device 0 is captured from device 1.
+fn parse_loopback_cards(cards: &str) -> Vec<String> {
+    cards
+        .lines()
+        .filter_map(|line| {
+            let (id, driver) = line.split_once('[')?.1.split_once("]:")?;
+            driver
+                .trim_start()
+                .starts_with("Loopback")
+                .then(|| format!("plughw:CARD={},DEV=1", id.trim()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_system_sources() {
+        let sources = "\
+0\talsa_output.pci-0000_00_1f.3.analog-stereo.monitor\tPipeWire\ts32le 2ch 48000Hz\tSUSPENDED
+1\talsa_input.pci-0000_00_1f.3.analog-stereo\tPipeWire\ts32le 2ch 48000Hz\tRUNNING
+";
+        assert_eq!(
+            parse_monitor_sources(sources),
+            ["alsa_output.pci-0000_00_1f.3.analog-stereo.monitor"]
+        );
+
+        let cards = "\
+ 0 [PCH            ]: HDA-Intel - HDA Intel PCH
+                      HDA Intel PCH at 0xf7f10000 irq 130
+ 2 [Loopback       ]: Loopback - Loopback
+                      Loopback 1
+";
+        assert_eq!(parse_loopback_cards(cards), ["plughw:CARD=Loopback,DEV=1"]);
+    }
+}
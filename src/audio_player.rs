@@ -1,68 +1,238 @@
 //! This module contains the implementation of the audio player used to play audio files in user's terminal.
 //! under the hood it uses `rodio` for playback and `symphonia` for decoding.
-use crossbeam::channel::{Receiver, Sender};
+use crate::{
+    analyzer::WaveformOverview,
+    band_solo::{BandSolo, BandSoloFilter, SharedBandSolo},
+    chapters::{self, Chapter, ChapterMeter},
+    cue::{self, CueSheet},
+    file_info::{self, FileInfo},
+    monitor::{Monitor, MonitorMode, SharedMonitorMode},
+    probe::{self, ProbeHint},
+    raw_pcm::RawFormat,
+    sample_store::{SampleStore, SampleWriter},
+};
+use cpal::{
+    Device, default_host,
+    traits::{DeviceTrait, HostTrait},
+};
+use crossbeam::channel::{Receiver, Sender, unbounded};
+use ebur128::EbuR128;
 use eyre::{Result, eyre};
 use rodio::{ChannelCount, OutputStream, OutputStreamBuilder, Sink, Source, source};
-use std::{path::PathBuf, time::Duration};
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
 use symphonia::core::{
     audio::{Channels, SampleBuffer},
-    codecs::{CODEC_TYPE_NULL, DecoderOptions},
+    codecs::CODEC_TYPE_NULL,
     errors::Error,
-    formats::FormatOptions,
+    formats::{FormatOptions, Track},
     io::{MediaSourceStream, MediaSourceStreamOptions},
     meta::MetadataOptions,
-    probe::Hint,
 };
 
-// Samples of the whole file
-pub type Samples = Vec<f32>;
-// pub type Samples = Vec<f32>;
+// Samples of the whole file, shared between the decoder, the player, the sink and the TUI
+pub type Samples = Arc<SampleStore>;
 pub type SampleRate = u32;
 pub type PlaybackPosition = usize;
 
+/// The playback position is sent to the TUI every `POSITION_UPDATE_INTERVAL` samples,
+/// so this is also how far the analysis moves in one frame.
+pub const POSITION_UPDATE_INTERVAL: usize = 2048;
+/// Headerless PCM files are read in blocks of this many frames
+const RAW_BLOCK_FRAMES: usize = 4096;
+
 pub enum PlayerCommand {
-    SelectFile(PathBuf),
+    /// Load a file in the background, the current file keeps playing until the new one can be played
+    SelectFile(FileRequest),
     ChangeState,
     // Had to add Quit because on MacOS tui can't be on the main thread (smth does not implement Send), player must be there.
     // So when tui quits, player must know tui has quit and quits too.
     Quit,
-    /// Move the playhead right
-    MoveRight,
-    /// Move the playhead left
-    MoveLeft,
+    /// Move the playhead right by the given step
+    MoveRight(Duration),
+    /// Move the playhead left by the given step
+    MoveLeft(Duration),
+    /// Move the playhead to the given position
+    SeekTo(Duration),
+    /// Switch playback to the output device with the given name,
+    /// replies whether the device is in use
+    SelectOutputDevice(String, Sender<bool>),
+    /// Set the playback volume (1.0 is the original level)
+    SetVolume(f32),
+    SetMuted(bool),
+    SetMonitorMode(MonitorMode),
+    /// Filter the playback to the given frequency range, `None` removes the filter
+    SetBandSolo(Option<BandSolo>),
     /// Shows an error (only in debug mode)
     #[cfg(debug_assertions)]
     ShowTestError,
 }
 
+/// A file to load and how to follow the loading.
+pub struct FileRequest {
+    pub path: PathBuf,
+    /// Index of the audio track to decode, the first one if `None`
+    pub track: Option<usize>,
+    /// Format of a headerless PCM file, `None` for files with a container
+    pub raw: Option<RawFormat>,
+    /// Start playing as soon as the file is loaded
+    pub autoplay: bool,
+    /// Position to start from, used to keep the playhead when a file is reloaded
+    pub start: Duration,
+    pub progress: Arc<LoadProgress>,
+}
+
+/// Audio track of a container, shown in the track picker.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackInfo {
+    pub codec: String,
+    pub channels: Option<usize>,
+    pub sample_rate: Option<u32>,
+    pub language: Option<String>,
+}
+
+impl Display for TrackInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.codec)?;
+        if let Some(channels) = self.channels {
+            write!(f, ", {channels} ch")?;
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            write!(f, ", {sample_rate} Hz")?;
+        }
+        if let Some(language) = &self.language {
+            write!(f, ", {language}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Progress of a file being decoded, shared between the decoding thread and the TUI.
+#[derive(Default)]
+pub struct LoadProgress {
+    decoded_frames: AtomicU64,
+    /// 0 if the length of the track is unknown
+    total_frames: AtomicU64,
+    sample_rate: AtomicU32,
+    cancelled: AtomicBool,
+    failed: AtomicBool,
+}
+
+impl LoadProgress {
+    /// Stops decoding, the current file keeps playing.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the file could not be loaded, the error is sent separately.
+    pub fn has_failed(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// Decoded part of the file (0..1), `None` if the length is unknown.
+    pub fn ratio(&self) -> Option<f64> {
+        let total = self.total_frames.load(Ordering::Relaxed);
+        (total != 0)
+            .then(|| (self.decoded_frames.load(Ordering::Relaxed) as f64 / total as f64).min(1.))
+    }
+
+    pub fn decoded_duration(&self) -> Duration {
+        self.frames_to_duration(self.decoded_frames.load(Ordering::Relaxed))
+    }
+
+    pub fn total_duration(&self) -> Option<Duration> {
+        let total = self.total_frames.load(Ordering::Relaxed);
+        (total != 0).then(|| self.frames_to_duration(total))
+    }
+
+    fn frames_to_duration(&self, frames: u64) -> Duration {
+        let sample_rate = self.sample_rate.load(Ordering::Relaxed);
+        if sample_rate == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(frames as f64 / sample_rate as f64)
+    }
+}
+
 /// `AudioFile` represents a loaded audio file with its samples, sample rate, and channels.
 /// It implements [`Source`] and [`Iterator`] for playback.
+/// Cloning is cheap, the samples and the waveform overview are shared with the decoding thread,
+/// which keeps appending to them after the file has been handed to the player.
 #[derive(Clone)]
 pub struct AudioFile {
     title: String,
+    path: PathBuf,
+    /// File the samples are decoded from, the audio file of a CUE sheet
+    audio_path: PathBuf,
     samples: Samples,
-    mid_samples: Samples,
-    side_samples: Samples,
+    /// Chunk of `samples` being played and its index
+    chunk: Option<(usize, Arc<[f32]>)>,
+    /// Min-max waveform computed while decoding, see [`WaveformOverview`]
+    overview: Arc<Mutex<WaveformOverview>>,
+    /// Analysis of the whole file, set when the decoding has finished
+    summary: Arc<OnceLock<Summary>>,
+    /// Audio tracks of the container
+    tracks: Vec<TrackInfo>,
+    /// Index of the decoded track in `tracks`
+    track: usize,
+    /// Info and chapters known before decoding, the summary has the complete ones
+    info: Arc<FileInfo>,
+    chapters: Arc<Vec<Chapter>>,
     sample_rate: SampleRate,
-    duration: Duration,
+    /// Length announced by the container, `None` if it is only known once the file is decoded
+    total_frames: Option<u64>,
     // channels of the file (mono, stereo, etc.)
     channels: Channels,
     // Global state and the sender of it
     playback_position: usize, // Index of the Samples vec
+    /// Samples of silence left to finish a frame while playback waits for the decoder
+    silence: usize,
     playback_position_tx: Sender<usize>,
 }
 
+/// Results of the analysis done while decoding, known once the whole file is decoded.
+struct Summary {
+    integrated_lufs: Option<f64>,
+    info: FileInfo,
+    chapters: Vec<Chapter>,
+}
+
 impl Iterator for AudioFile {
     type Item = f32;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.silence > 0 {
+            self.silence -= 1;
+            return Some(0.);
+        }
         let pos = self.playback_position;
-        let res = if pos < self.samples.len() {
-            Some(self.samples[pos])
+        let len = self.samples.len();
+        if pos >= len && !self.samples.is_complete() {
+            // playback caught up with the decoder, whole frames of silence keep the channels in place
+            self.silence = self.channels.count().max(1) - 1;
+            return Some(0.);
+        }
+        let res = if pos < len {
+            Some(self.sample(pos))
         } else {
             None
         };
-        if pos.is_multiple_of(2048)
+        // the end of the file is sent as well so that the TUI knows that the track has ended
+        if (pos.is_multiple_of(POSITION_UPDATE_INTERVAL) || pos >= len)
             && let Err(_err) = self.playback_position_tx.send(pos)
         {
             // TODO: log sending error
@@ -86,18 +256,23 @@ impl Source for AudioFile {
     }
 
     fn total_duration(&self) -> Option<Duration> {
-        Some(self.duration)
+        (self.samples.is_complete() || self.total_frames.is_some()).then(|| self.duration())
     }
 
     fn try_seek(&mut self, pos: Duration) -> Result<(), source::SeekError> {
+        let channels = self.channels() as usize;
         // TODO: other channels, see https://docs.rs/rodio/latest/src/rodio/buffer.rs.html#88-105
-        let curr_channel = self.playback_position % self.channels() as usize;
-        let new_pos = pos.as_secs_f32() * self.sample_rate() as f32 * self.channels() as f32;
-        // saturate pos at the end of the source
+        let curr_channel = match self.silence {
+            0 => self.playback_position % channels,
+            silence => channels - silence,
+        };
+        self.silence = 0;
+        let new_pos = pos.as_secs_f32() * self.sample_rate() as f32 * channels as f32;
+        // saturate pos at the end of the source, a file being decoded can be played up to its announced length
         let new_pos = new_pos as usize;
-        let new_pos = new_pos.min(self.samples.len());
+        let new_pos = new_pos.min(self.seek_limit());
         // make sure the next sample is for the right channel
-        let new_pos = new_pos.next_multiple_of(self.channels() as usize);
+        let new_pos = new_pos.next_multiple_of(channels);
         let new_pos = new_pos - curr_channel;
 
         self.playback_position = new_pos;
@@ -114,115 +289,342 @@ impl AudioFile {
         &self.title
     }
 
-    pub fn samples(&self) -> &Samples {
-        &self.samples
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn audio_path(&self) -> &Path {
+        &self.audio_path
+    }
+
+    /// Copies the decoded samples in `range`.
+    pub fn samples(&self, range: Range<usize>) -> Vec<f32> {
+        self.samples.read(range)
+    }
+
+    /// Number of samples decoded so far
+    pub fn samples_len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns `true` once the whole file is decoded, or decoding stopped.
+    pub fn is_decoded(&self) -> bool {
+        self.samples.is_complete()
+    }
+
+    /// Decoded part of the file (0..1) while it is decoded, `None` if the length is unknown.
+    pub fn decoded_ratio(&self) -> Option<f64> {
+        let total = self.total_frames.filter(|&total| total != 0)?;
+        Some((self.frames() as f64 / total as f64).min(1.))
+    }
+
+    /// Number of frames (samples per channel) decoded so far
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.count().max(1)
+    }
+
+    /// Returns (L + R) / 2 of the given frames. Computed on demand to avoid keeping a copy of the file.
+    pub fn mid_samples(&self, frames: Range<usize>) -> Vec<f32> {
+        self.map_frames(frames, |l, r| (l + r) / 2.)
+    }
+
+    /// Returns (L - R) / 2 of the given frames. Computed on demand to avoid keeping a copy of the file.
+    pub fn side_samples(&self, frames: Range<usize>) -> Vec<f32> {
+        self.map_frames(frames, |l, r| (l - r) / 2.)
     }
 
-    pub fn mid_samples(&self) -> &Samples {
-        &self.mid_samples
+    /// Maps the first two channels of every frame in `frames`, mono files use the same channel twice.
+    fn map_frames(&self, frames: Range<usize>, f: impl Fn(f32, f32) -> f32) -> Vec<f32> {
+        let channels = self.channels.count().max(1);
+        let right = usize::from(channels > 1);
+        self.samples
+            .read(frames.start * channels..frames.end * channels)
+            .chunks_exact(channels)
+            .map(|frame| f(frame[0], frame[right]))
+            .collect()
     }
 
-    pub fn side_samples(&self) -> &Samples {
-        &self.side_samples
+    /// Copies the points of the waveform overview in `range`, the overview grows while the file is decoded.
+    pub fn overview(&self, range: Range<usize>) -> Vec<(f64, f64)> {
+        let overview = self.overview.lock().unwrap();
+        let points = overview.points();
+        let end = range.end.min(points.len());
+        points[range.start.min(end)..end].to_vec()
     }
 
-    pub fn duration(&self) -> &Duration {
-        &self.duration
+    pub fn overview_len(&self) -> usize {
+        self.overview.lock().unwrap().points().len()
+    }
+
+    /// Integrated loudness of the whole file, `None` until it is decoded.
+    pub fn integrated_lufs(&self) -> Option<f64> {
+        self.summary.get()?.integrated_lufs
+    }
+
+    pub fn tracks(&self) -> &[TrackInfo] {
+        &self.tracks
+    }
+
+    pub fn track(&self) -> usize {
+        self.track
+    }
+
+    pub fn info(&self) -> &FileInfo {
+        self.summary
+            .get()
+            .map_or(&self.info, |summary| &summary.info)
+    }
+
+    pub fn chapters(&self) -> &[Chapter] {
+        self.summary
+            .get()
+            .map_or(&self.chapters, |summary| &summary.chapters)
+    }
+
+    /// Length of the file, the announced length while it is decoded.
+    pub fn duration(&self) -> Duration {
+        let decoded = self.frames() as u64;
+        let frames = match self.total_frames {
+            Some(total) if !self.samples.is_complete() || decoded == 0 => total.max(decoded),
+            _ => decoded,
+        };
+        Duration::from_secs_f64(frames as f64 / self.sample_rate.max(1) as f64)
     }
 
     pub fn new(playback_position_tx: Sender<usize>) -> Self {
         AudioFile {
             title: String::new(),
-            samples: Vec::new(),
-            mid_samples: Vec::new(),
-            side_samples: Vec::new(),
+            path: PathBuf::new(),
+            audio_path: PathBuf::new(),
+            samples: Samples::default(),
+            chunk: None,
+            overview: Arc::new(Mutex::new(WaveformOverview::new(44100, 2))),
+            summary: Arc::default(),
+            tracks: Vec::new(),
+            track: 0,
+            info: Arc::default(),
+            chapters: Arc::default(),
             sample_rate: 44100,
-            duration: Duration::from_secs(15),
+            total_frames: Some(15 * 44100),
             channels: Channels::all(),
             playback_position: 0,
+            silence: 0,
             playback_position_tx,
         }
     }
 
-    /// creates a new `AudioFile` from file
-    pub fn from_file(path: &PathBuf, playback_position_tx: Sender<usize>) -> Result<Self> {
+    /// Decodes the `track`-th audio track of a file (the first one if `None`), reporting the
+    /// decoding progress to `progress`. Headerless PCM is read as `raw`.
+    /// The file is handed to `on_playable` as soon as its first samples are decoded,
+    /// the rest is appended to it until the end of the file, an error or cancellation.
+    pub fn from_file(
+        path: &Path,
+        track: Option<usize>,
+        raw: Option<RawFormat>,
+        playback_position_tx: Sender<usize>,
+        progress: &LoadProgress,
+        on_playable: impl FnOnce(AudioFile),
+    ) -> Result<()> {
         // get file name
         let title = path.file_name().unwrap().to_string_lossy().to_string();
-        let (samples, sample_rate, channels) = Self::decode_file(path)?;
-        // TODO: other channels, not only stereo sound.
-        let (mid_samples, side_samples) = get_mid_and_side_samples(&samples);
-        let duration = mid_samples.len() as f64 / sample_rate as f64 * 1000.;
-        Ok(AudioFile {
+        // a CUE sheet plays the album file it references, its tracks become chapters
+        let (audio_path, chapters) = if cue::is_cue(path) {
+            let cue = CueSheet::load(path)?;
+            if !cue.audio_path.exists() {
+                return Err(eyre!(
+                    "Audio file of the CUE sheet not found: {}",
+                    cue.audio_path.display()
+                ));
+            }
+            (cue.audio_path.clone(), Some(cue.chapters()))
+        } else {
+            (path.to_path_buf(), None)
+        };
+        let mut sink = FileSink {
             title,
-            samples,
-            mid_samples,
-            side_samples,
-            sample_rate,
-            duration: Duration::from_millis(duration as u64),
-            channels,
-            playback_position: 0,
+            path: path.to_path_buf(),
+            audio_path: audio_path.clone(),
             playback_position_tx,
-        })
+            on_playable: Some(Box::new(on_playable)),
+            decoding: None,
+        };
+        match raw {
+            Some(format) => Self::decode_raw_file(&audio_path, format, progress, &mut sink),
+            None => Self::decode_file(
+                &audio_path,
+                track.unwrap_or(0),
+                chapters,
+                progress,
+                &mut sink,
+            ),
+        }
+    }
+
+    /// The sample at `pos`, which must be decoded.
+    fn sample(&mut self, pos: usize) -> f32 {
+        let chunk_len = self.samples.chunk_len();
+        let index = pos / chunk_len;
+        if self.chunk.as_ref().is_none_or(|(i, _)| *i != index) {
+            self.chunk = self.samples.chunk(index).map(|chunk| (index, chunk));
+        }
+        self.chunk
+            .as_ref()
+            .map_or(0., |(_, chunk)| chunk[pos % chunk_len])
     }
 
-    /// Decodes file and returns its [`Samples`], [`SampleRate`] and [`Channels`]
-    fn decode_file(path: &PathBuf) -> Result<(Samples, SampleRate, Channels)> {
+    /// Last sample position playback can be moved to.
+    fn seek_limit(&self) -> usize {
+        let len = self.samples.len();
+        if self.samples.is_complete() {
+            return len;
+        }
+        let total = self.total_frames.unwrap_or(0) as usize * self.channels.count();
+        len.max(total)
+    }
+
+    /// Decodes a file into `sink`, together with the analysis done while decoding.
+    /// `chapters` replace the chapters found in the file.
+    fn decode_file(
+        path: &PathBuf,
+        track_index: usize,
+        chapters: Option<Vec<Chapter>>,
+        progress: &LoadProgress,
+        sink: &mut FileSink,
+    ) -> Result<()> {
         // open the media source and create a stream
         let src = std::fs::File::open(path)?;
         let mss = MediaSourceStream::new(Box::new(src), MediaSourceStreamOptions::default());
 
-        // Create a probe hint using the file's extension.
-        let mut hint = Hint::new();
-        hint.with_extension("mp3");
+        // Create a probe hint using the file's extension and content.
+        let probe_hint = ProbeHint::new(path);
 
         // Use the default options for metadata and format readers.
         let meta_opts: MetadataOptions = MetadataOptions::default();
         let fmt_opts: FormatOptions = FormatOptions::default();
 
         // Probe the media source.
-        let probed = symphonia::default::get_probe().format(&hint, mss, &fmt_opts, &meta_opts)?;
+        let mut probed = probe::formats()
+            .format(&probe_hint.hint(), mss, &fmt_opts, &meta_opts)
+            .map_err(|err| probe_hint.error(err))?;
+
+        // Tags found before the container (e.g. ID3v2), then tags of the container itself
+        let mut tags = probed
+            .metadata
+            .get()
+            .and_then(|m| m.current().map(file_info::read_tags))
+            .unwrap_or_default();
 
         // Get the instantiated format reader.
         let mut format = probed.format;
+        if let Some(revision) = format.metadata().current() {
+            tags.extend(file_info::read_tags(revision));
+        }
 
-        // Find the first audio track with a known (decodeable) codec.
-        let Some(track) = format
+        // Find audio tracks with a known (decodeable) codec.
+        let audio_tracks: Vec<&Track> = format
             .tracks()
             .iter()
-            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
-        else {
+            .filter(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .collect();
+        if audio_tracks.is_empty() {
             return Err(eyre!("No audio track found with a decodeable codec"));
+        }
+        let tracks: Vec<TrackInfo> = audio_tracks
+            .iter()
+            .map(|t| TrackInfo {
+                codec: probe::codec_name(t.codec_params.codec),
+                channels: t.codec_params.channels.map(|c| c.count()),
+                sample_rate: t.codec_params.sample_rate,
+                language: t.language.clone(),
+            })
+            .collect();
+        let Some(track) = audio_tracks.get(track_index) else {
+            return Err(eyre!(
+                "Audio track {} does not exist, the file has {} audio track(s)",
+                track_index + 1,
+                audio_tracks.len()
+            ));
         };
 
-        // Use the default options for the decoder.
-        let dec_opts: DecoderOptions = DecoderOptions::default();
-
-        // Create a decoder for the track.
-        let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &dec_opts)?;
+        // Create a decoder for the track with the default options.
+        let mut decoder = probe::make_decoder(&track.codec_params)?;
 
         // Store the track identifier, it will be used to filter packets.
         let track_id = track.id;
 
+        let mut chapters = match chapters {
+            Some(chapters) => chapters,
+            None => chapters::read_chapters(path, &tags).unwrap_or_default(),
+        };
+
+        let mut info = FileInfo {
+            container: probe_hint.container(),
+            codec: tracks[track_index].codec.clone(),
+            bits_per_sample: track
+                .codec_params
+                .bits_per_sample
+                .or(track.codec_params.bits_per_coded_sample),
+            file_size: std::fs::metadata(path)?.len(),
+            tags,
+            ..FileInfo::default()
+        };
+
+        if let Some(frames) = track.codec_params.n_frames {
+            progress.total_frames.store(frames, Ordering::Relaxed);
+        }
+        if let Some(rate) = track.codec_params.sample_rate {
+            progress.sample_rate.store(rate, Ordering::Relaxed);
+        }
+
         // Make a sample buffer to hold the decoded audio samples.
-        let mut all_samples = Vec::<f32>::new();
         let mut sample_buf = None;
+        // Frames handed to the sink
+        let mut frames = 0u64;
 
         // Defaults for sample rate and channels
         let mut sample_rate = 44100;
         let mut channels = Channels::empty();
+        let total_frames = track.codec_params.n_frames;
+        let header = |sample_rate, channels| Header {
+            sample_rate,
+            channels,
+            total_frames,
+            tracks: tracks.clone(),
+            track: track_index,
+            info: info.clone(),
+            chapters: chapters.clone(),
+        };
 
         // The decode loop.
         loop {
+            if progress.is_cancelled() {
+                return Err(eyre!("Loading cancelled"));
+            }
+
             // Get the next packet from the format reader.
             let packet = match format.next_packet() {
                 Ok(packet) => packet,
                 Err(Error::IoError(_)) => {
+                    // a file without a single packet is still loaded
+                    if !sink.is_started() {
+                        sink.start(header(sample_rate, channels));
+                    }
+                    info.sample_rate = sample_rate;
+                    info.channels = channels.count();
+                    info.channel_layout = format!("{channels:?}");
+                    info.frames = frames;
+                    // only WAV/BWF files have a `bext` chunk, reading other files fails
+                    if let Ok(bext) = file_info::read_bext(path, sample_rate) {
+                        info.tags.extend(bext);
+                    }
+                    chapters::set_ends(&mut chapters, info.frames as f64 / sample_rate as f64);
+
                     // End of stream - return Ok to indicate successful completion
-                    return Ok((all_samples, sample_rate, channels));
+                    sink.finish(info, chapters);
+                    return Ok(());
                 }
                 Err(err) => {
                     return Err(err.into());
-                    // return Err(err.into());
                 }
             };
 
@@ -234,6 +636,10 @@ impl AudioFile {
             // Decode the packet into audio samples, ignoring any decode errors.
             match decoder.decode(&packet) {
                 Ok(audio_buf) => {
+                    progress
+                        .decoded_frames
+                        .fetch_add(audio_buf.frames() as u64, Ordering::Relaxed);
+
                     // If this is the *first* decoded packet, create a sample buffer matching the
                     // decoded audio buffer format.
                     if sample_buf.is_none() {
@@ -247,15 +653,18 @@ impl AudioFile {
 
                         // Create the f32 sample buffer.
                         sample_buf = Some(SampleBuffer::<f32>::new(duration, spec));
+                        // the file can be played from now on
+                        sink.start(header(sample_rate, channels));
                     }
 
                     // Copy the decoded audio buffer into the sample buffer in an interleaved format.
                     if let Some(buf) = &mut sample_buf {
                         buf.copy_interleaved_ref(audio_buf);
 
-                        // Append the samples to our complete buffer
+                        // Append the samples to the file and analyze them while they are in the cache
                         let samples = buf.samples();
-                        all_samples.extend_from_slice(samples);
+                        frames += (samples.len() / channels.count().max(1)) as u64;
+                        sink.push(samples);
                     }
                 }
                 Err(symphonia::core::errors::Error::DecodeError(_)) => (),
@@ -265,26 +674,250 @@ impl AudioFile {
             }
         }
     }
+
+    /// Reads a headerless PCM file, the counterpart of [`AudioFile::decode_file`].
+    fn decode_raw_file(
+        path: &Path,
+        format: RawFormat,
+        progress: &LoadProgress,
+        sink: &mut FileSink,
+    ) -> Result<()> {
+        // symphonia has one flag per speaker, the first `n` are used like a WAV without a channel mask
+        let channels = u32::try_from(format.channels)
+            .ok()
+            .filter(|&n| n < u32::BITS)
+            .and_then(|n| Channels::from_bits((1 << n) - 1))
+            .ok_or_else(|| eyre!("Unsupported channel count: {}", format.channels))?;
+        let file_size = std::fs::metadata(path)?.len();
+        let frame_len = format.bytes_per_frame();
+        let frames = file_size.saturating_sub(format.offset) / frame_len as u64;
+        progress.total_frames.store(frames, Ordering::Relaxed);
+        progress
+            .sample_rate
+            .store(format.sample_rate, Ordering::Relaxed);
+
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(format.offset))?;
+        // trailing bytes of an incomplete frame are not read
+        let mut reader = file.take(frames * frame_len as u64);
+
+        let codec = format!("PCM {}", format.encoding.to_string().to_uppercase());
+        let mut tags = Vec::new();
+        if format.offset > 0 {
+            tags.push((String::from("Offset"), format!("{} bytes", format.offset)));
+        }
+        let info = FileInfo {
+            container: String::from("Raw PCM"),
+            codec: codec.clone(),
+            bits_per_sample: Some(format.encoding.bits_per_sample()),
+            sample_rate: format.sample_rate,
+            channels: format.channels,
+            channel_layout: format!("{channels:?}"),
+            frames,
+            file_size,
+            tags,
+        };
+        // the length is known from the file size, so the file can be played right away
+        sink.start(Header {
+            sample_rate: format.sample_rate,
+            channels,
+            total_frames: Some(frames),
+            tracks: vec![TrackInfo {
+                codec,
+                channels: Some(format.channels),
+                sample_rate: Some(format.sample_rate),
+                language: None,
+            }],
+            track: 0,
+            info: info.clone(),
+            chapters: Vec::new(),
+        });
+        let mut block = vec![0u8; RAW_BLOCK_FRAMES * frame_len];
+        loop {
+            if progress.is_cancelled() {
+                return Err(eyre!("Loading cancelled"));
+            }
+            let mut filled = 0;
+            while filled < block.len() {
+                match reader.read(&mut block[filled..])? {
+                    0 => break,
+                    read => filled += read,
+                }
+            }
+            if filled == 0 {
+                break;
+            }
+            let samples = format.encoding.decode(&block[..filled]);
+            progress
+                .decoded_frames
+                .fetch_add((filled / frame_len) as u64, Ordering::Relaxed);
+            sink.push(&samples);
+        }
+        sink.finish(info, Vec::new());
+        Ok(())
+    }
+}
+
+/// What is known about a file when its first samples are decoded.
+struct Header {
+    sample_rate: SampleRate,
+    channels: Channels,
+    /// Length announced by the container
+    total_frames: Option<u64>,
+    tracks: Vec<TrackInfo>,
+    track: usize,
+    info: FileInfo,
+    chapters: Vec<Chapter>,
+}
+
+/// Receives the samples of a file being decoded, see [`AudioFile::from_file`].
+struct FileSink<'a> {
+    title: String,
+    path: PathBuf,
+    audio_path: PathBuf,
+    playback_position_tx: Sender<usize>,
+    on_playable: Option<Box<dyn FnOnce(AudioFile) + 'a>>,
+    /// Storage and analysis shared with the file, from the first decoded samples on
+    decoding: Option<Decoding>,
+}
+
+struct Decoding {
+    writer: SampleWriter,
+    overview: Arc<Mutex<WaveformOverview>>,
+    summary: Arc<OnceLock<Summary>>,
+    loudness_meter: Option<EbuR128>,
+    chapter_meter: Option<ChapterMeter>,
+}
+
+impl FileSink<'_> {
+    fn is_started(&self) -> bool {
+        self.on_playable.is_none()
+    }
+
+    /// Creates the storage of the samples and hands the file to the player.
+    fn start(&mut self, header: Header) {
+        let count = header.channels.count();
+        let (samples, writer) = SampleStore::new(count);
+        let overview = Arc::new(Mutex::new(WaveformOverview::new(header.sample_rate, count)));
+        let summary = Arc::new(OnceLock::new());
+        let mut chapters = header.chapters;
+        if let Some(frames) = header.total_frames {
+            chapters::set_ends(&mut chapters, frames as f64 / header.sample_rate as f64);
+        }
+        self.decoding = Some(Decoding {
+            writer,
+            overview: overview.clone(),
+            summary: summary.clone(),
+            loudness_meter: EbuR128::new(count as u32, header.sample_rate, ebur128::Mode::I).ok(),
+            chapter_meter: (!chapters.is_empty())
+                .then(|| ChapterMeter::new(&chapters, header.sample_rate, count)),
+        });
+        let audio_file = AudioFile {
+            title: self.title.clone(),
+            path: self.path.clone(),
+            audio_path: self.audio_path.clone(),
+            samples,
+            chunk: None,
+            overview,
+            summary,
+            tracks: header.tracks,
+            track: header.track,
+            info: Arc::new(header.info),
+            chapters: Arc::new(chapters),
+            sample_rate: header.sample_rate,
+            total_frames: header.total_frames,
+            channels: header.channels,
+            playback_position: 0,
+            silence: 0,
+            playback_position_tx: self.playback_position_tx.clone(),
+        };
+        if let Some(on_playable) = self.on_playable.take() {
+            on_playable(audio_file);
+        }
+    }
+
+    /// Appends interleaved samples to the file and analyzes them.
+    fn push(&mut self, samples: &[f32]) {
+        let Some(decoding) = &mut self.decoding else {
+            return;
+        };
+        decoding.writer.push(samples);
+        decoding.overview.lock().unwrap().push(samples);
+        if let Some(meter) = &mut decoding.loudness_meter
+            && meter.add_frames_f32(samples).is_err()
+        {
+            decoding.loudness_meter = None;
+        }
+        if let Some(meter) = &mut decoding.chapter_meter {
+            meter.push(samples);
+        }
+    }
+
+    /// Publishes the analysis of the whole file, the chapters get their loudness.
+    fn finish(&mut self, info: FileInfo, mut chapters: Vec<Chapter>) {
+        let Some(decoding) = self.decoding.take() else {
+            return;
+        };
+        decoding.overview.lock().unwrap().finish();
+        if let Some(meter) = decoding.chapter_meter {
+            meter.finish(&mut chapters);
+        }
+        let _ = decoding.summary.set(Summary {
+            integrated_lufs: decoding
+                .loudness_meter
+                .and_then(|meter| meter.loudness_global().ok()),
+            info,
+            chapters,
+        });
+        // the file is complete once the summary is there
+        drop(decoding.writer);
+    }
 }
 
+/// A file decoded in the background, sent back to the player loop.
+type LoadedFile = (FileRequest, Result<AudioFile>);
+
 pub struct AudioPlayer {
     // sends playback position
     playback_position_tx: Sender<usize>,
+    /// Request of the file being decoded, if any
+    loading: Option<Arc<LoadProgress>>,
+    /// Progress of the loaded file, its decoding is cancelled when it is replaced
+    playing: Option<Arc<LoadProgress>>,
+    loaded_tx: Sender<LoadedFile>,
+    loaded_rx: Receiver<LoadedFile>,
     audio_file: AudioFile,
     _stream_handle: OutputStream,
     sink: Sink,
+    volume: f32,
+    muted: bool,
+    monitor_mode: SharedMonitorMode,
+    band_solo: SharedBandSolo,
 }
 
 impl AudioPlayer {
-    pub fn new(playback_position_tx: Sender<usize>) -> Result<Self> {
-        let stream_handle = OutputStreamBuilder::open_default_stream()?;
+    /// Creates a player that plays through `output_device` if it is connected,
+    /// or through the default output device otherwise.
+    pub fn new(playback_position_tx: Sender<usize>, output_device: Option<&str>) -> Result<Self> {
+        let stream_handle = output_device
+            .and_then(|name| open_output_stream(name).ok())
+            .map_or_else(open_default_output_stream, Ok)?;
         let sink = Sink::connect_new(stream_handle.mixer());
         let audio_file = AudioFile::new(playback_position_tx.clone());
+        let (loaded_tx, loaded_rx) = unbounded();
         Ok(Self {
             playback_position_tx,
+            loading: None,
+            playing: None,
+            loaded_tx,
+            loaded_rx,
             audio_file,
             _stream_handle: stream_handle,
             sink,
+            volume: 1.,
+            muted: false,
+            monitor_mode: SharedMonitorMode::default(),
+            band_solo: SharedBandSolo::default(),
         })
     }
 
@@ -296,35 +929,59 @@ impl AudioPlayer {
         error_tx: &Sender<String>,
     ) -> Result<()> {
         loop {
+            // swap in a file decoded in the background
+            if let Ok((request, result)) = self.loaded_rx.try_recv() {
+                self.receive_loaded_file(request, result, audio_file_tx, error_tx);
+            }
+
             // recieve a `PlayerCommand` from an UI
             if let Ok(cmd) = player_command_rx.try_recv() {
                 match cmd {
-                    PlayerCommand::SelectFile(path) => {
-                        match AudioFile::from_file(&path, self.playback_position_tx.clone()) {
-                            Err(err) => {
-                                if let Err(_err) =
-                                    error_tx.send(format!("Error loading file: {err}"))
-                                {
-                                    //TODO: log a sending error
+                    PlayerCommand::SelectFile(request) => {
+                        // only the latest request is loaded
+                        if let Some(progress) = self.loading.replace(request.progress.clone()) {
+                            progress.cancel();
+                        }
+                        let playback_position_tx = self.playback_position_tx.clone();
+                        let loaded_tx = self.loaded_tx.clone();
+                        let error_tx = error_tx.clone();
+                        std::thread::spawn(move || {
+                            let (path, progress) = (request.path.clone(), request.progress.clone());
+                            let mut request = Some(request);
+                            let result = AudioFile::from_file(
+                                &path,
+                                request.as_ref().and_then(|r| r.track),
+                                request.as_ref().and_then(|r| r.raw),
+                                playback_position_tx,
+                                &progress,
+                                |audio_file| {
+                                    if let Some(request) = request.take()
+                                        && let Err(_err) = loaded_tx.send((request, Ok(audio_file)))
+                                    {
+                                        //TODO: log a sending error
+                                    }
+                                },
+                            );
+                            match (request, result) {
+                                // the file could not be played at all
+                                (Some(request), Err(err)) => {
+                                    if let Err(_err) = loaded_tx.send((request, Err(err))) {
+                                        //TODO: log a sending error
+                                    }
                                 }
-                                continue;
-                            }
-                            Ok(af) => {
-                                self.audio_file = af.clone();
-                                if let Err(_err) = audio_file_tx.send(af) {
-                                    //TODO: log a sending error
+                                // the file is playing, it ends where decoding stopped
+                                (None, Err(err)) if !progress.is_cancelled() => {
+                                    let name = path.file_name().unwrap_or_default();
+                                    if let Err(_err) = error_tx.send(format!(
+                                        "Error decoding {}: {err}",
+                                        name.to_string_lossy()
+                                    )) {
+                                        //TODO: log a sending error
+                                    }
                                 }
+                                _ => (),
                             }
-                        }
-
-                        // clear the sink and append new file
-                        self.sink.stop();
-                        self.sink.clear();
-                        self.audio_file.playback_position = 0;
-                        self.sink.append(self.audio_file.clone());
-                        if let Err(_err) = self.playback_position_tx.send(0) {
-                            // TODO: log a sending error
-                        }
+                        });
                     }
 
                     PlayerCommand::ChangeState => {
@@ -336,7 +993,7 @@ impl AudioPlayer {
                         // if we hit the end of the track, then load it again
                         if self.sink.empty() {
                             self.audio_file.playback_position = 0;
-                            self.sink.append(self.audio_file.clone());
+                            self.sink.append(self.source());
                         }
                     }
                     PlayerCommand::Quit => {
@@ -350,12 +1007,12 @@ impl AudioPlayer {
                         return Ok(());
                     }
                     // move the playhead right
-                    PlayerCommand::MoveRight => {
+                    PlayerCommand::MoveRight(step) => {
                         let pos = self.sink.get_pos();
                         if self.sink.empty() {
                             continue;
                         }
-                        let seek = (pos + Duration::from_secs(5)).min(self.audio_file.duration);
+                        let seek = (pos + step).min(self.audio_file.duration());
 
                         if let Err(err) = self.sink.try_seek(seek) {
                             println!("Error seeking: {err:?}");
@@ -363,14 +1020,14 @@ impl AudioPlayer {
                         }
                     }
                     // move the playhead left
-                    PlayerCommand::MoveLeft => {
+                    PlayerCommand::MoveLeft(step) => {
                         if self.sink.empty() {
                             let pos = self
                                 .audio_file
-                                .duration
-                                .checked_sub(Duration::from_secs(5))
+                                .duration()
+                                .checked_sub(step)
                                 .unwrap_or_default();
-                            self.sink.append(self.audio_file.clone());
+                            self.sink.append(self.source());
                             if let Err(err) = self.sink.try_seek(pos) {
                                 println!("Error seeking: {err:?}");
                                 // TODO: error handling
@@ -378,13 +1035,42 @@ impl AudioPlayer {
                             continue;
                         }
                         let pos = self.sink.get_pos();
-                        if let Err(_err) = self
-                            .sink
-                            .try_seek(pos.saturating_sub(Duration::from_secs(5)))
-                        {
+                        if let Err(_err) = self.sink.try_seek(pos.saturating_sub(step)) {
+                            // TODO: error handling
+                        }
+                    }
+                    PlayerCommand::SeekTo(pos) => {
+                        // if we hit the end of the track, then load it again
+                        if self.sink.empty() {
+                            self.sink.append(self.source());
+                        }
+                        let pos = pos.min(self.audio_file.duration());
+                        if let Err(_err) = self.sink.try_seek(pos) {
                             // TODO: error handling
                         }
                     }
+                    PlayerCommand::SelectOutputDevice(name, reply_tx) => {
+                        let result = self.switch_output_device(&name);
+                        if let Err(err) = &result
+                            && let Err(_err) =
+                                error_tx.send(format!("Error opening output device: {err}"))
+                        {
+                            //TODO: log a sending error
+                        }
+                        if let Err(_err) = reply_tx.send(result.is_ok()) {
+                            // the TUI has quit
+                        }
+                    }
+                    PlayerCommand::SetVolume(volume) => {
+                        self.volume = volume;
+                        self.apply_volume(&self.sink);
+                    }
+                    PlayerCommand::SetMuted(muted) => {
+                        self.muted = muted;
+                        self.apply_volume(&self.sink);
+                    }
+                    PlayerCommand::SetMonitorMode(mode) => self.monitor_mode.set(mode),
+                    PlayerCommand::SetBandSolo(band) => self.band_solo.set(band),
                     #[cfg(debug_assertions)]
                     PlayerCommand::ShowTestError => {
                         error_tx.send("This is a test message".to_string()).unwrap();
@@ -397,6 +1083,145 @@ impl AudioPlayer {
     }
 }
 
+impl AudioPlayer {
+    /// Replaces the current file with a file decoded in the background.
+    /// Files of cancelled or outdated requests are dropped.
+    fn receive_loaded_file(
+        &mut self,
+        request: FileRequest,
+        result: Result<AudioFile>,
+        audio_file_tx: &Sender<AudioFile>,
+        error_tx: &Sender<String>,
+    ) {
+        let is_current = self
+            .loading
+            .as_ref()
+            .is_some_and(|progress| Arc::ptr_eq(progress, &request.progress));
+        if !is_current {
+            return;
+        }
+        self.loading = None;
+        if request.progress.is_cancelled() {
+            return;
+        }
+
+        let af = match result {
+            Ok(af) => af,
+            Err(err) => {
+                request.progress.failed.store(true, Ordering::Relaxed);
+                if let Err(_err) = error_tx.send(format!(
+                    "Error loading {}: {err}",
+                    request
+                        .path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                )) {
+                    //TODO: log a sending error
+                }
+                return;
+            }
+        };
+        // the replaced file is not needed anymore, even if it is still being decoded
+        if let Some(progress) = self.playing.replace(request.progress.clone()) {
+            progress.cancel();
+        }
+        self.audio_file = af.clone();
+        if let Err(_err) = audio_file_tx.send(af) {
+            //TODO: log a sending error
+        }
+
+        // clear the sink and append new file
+        self.sink.stop();
+        self.sink.clear();
+        self.audio_file.playback_position = 0;
+        self.sink.append(self.source());
+        if request.autoplay {
+            self.sink.play();
+        }
+        if request.start.is_zero() {
+            if let Err(_err) = self.playback_position_tx.send(0) {
+                // TODO: log a sending error
+            }
+        } else if let Err(_err) = self
+            .sink
+            .try_seek(request.start.min(self.audio_file.duration()))
+        {
+            // TODO: error handling
+        }
+    }
+
+    /// Moves playback to another output device keeping the position and the play/pause state.
+    fn switch_output_device(&mut self, name: &str) -> Result<()> {
+        let stream_handle = open_output_stream(name)?;
+        let sink = Sink::connect_new(stream_handle.mixer());
+        sink.pause();
+        self.apply_volume(&sink);
+
+        if !self.sink.empty() {
+            let pos = self.sink.get_pos();
+            sink.append(self.source());
+            if let Err(_err) = sink.try_seek(pos) {
+                // TODO: error handling
+            }
+        }
+        if !self.sink.is_paused() {
+            sink.play();
+        }
+
+        self.sink.stop();
+        self.sink = sink;
+        self._stream_handle = stream_handle;
+        Ok(())
+    }
+
+    /// Returns the loaded file wrapped into the band solo filter and the monitoring matrix.
+    fn source(&self) -> Monitor<BandSoloFilter<AudioFile>> {
+        Monitor::new(
+            BandSoloFilter::new(self.audio_file.clone(), self.band_solo.clone()),
+            self.monitor_mode.clone(),
+        )
+    }
+
+    fn apply_volume(&self, sink: &Sink) {
+        sink.set_volume(if self.muted { 0. } else { self.volume });
+    }
+}
+
+/// Lists output devices sorted by name.
+pub fn list_output_devs() -> Vec<(String, Device)> {
+    let host = default_host();
+    let mut devs: Vec<(String, Device)> = match host.output_devices() {
+        Ok(devs) => devs
+            .map(|dev| {
+                (
+                    dev.name().unwrap_or_else(|_| String::from("<unknown>")),
+                    dev,
+                )
+            })
+            .collect(),
+        Err(_err) => Vec::new(),
+    };
+    devs.sort_by(|(n1, _), (n2, _)| n1.cmp(n2));
+    devs
+}
+
+fn open_output_stream(name: &str) -> Result<OutputStream> {
+    let Some((_, device)) = list_output_devs().into_iter().find(|(n, _)| n == name) else {
+        return Err(eyre!("Output device {name} not found"));
+    };
+    let mut stream_handle = OutputStreamBuilder::from_device(device)?.open_stream_or_fallback()?;
+    // the message printed on drop would break the TUI when switching devices
+    stream_handle.log_on_drop(false);
+    Ok(stream_handle)
+}
+
+fn open_default_output_stream() -> Result<OutputStream> {
+    let mut stream_handle = OutputStreamBuilder::open_default_stream()?;
+    stream_handle.log_on_drop(false);
+    Ok(stream_handle)
+}
+
 pub fn get_mid_and_side_samples(samples: &[f32]) -> (Vec<f32>, Vec<f32>) {
     let left_samples = samples.iter().step_by(2).copied().collect::<Vec<f32>>();
     let right_samples = samples
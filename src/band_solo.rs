@@ -0,0 +1,274 @@
+//! Band solo: a filter applied to the playback to listen to a frequency range selected on the spectrum.
+//! Filters are RBJ cookbook biquads, see <https://www.w3.org/TR/audio-eq-cookbook/>.
+use rodio::{ChannelCount, Source, source};
+use std::{
+    f32::consts::PI,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+/// Gain of the bell filter in dB
+const BELL_GAIN_DB: f32 = 12.;
+/// Number of cascaded band-pass stages, more stages give steeper slopes
+const BAND_PASS_STAGES: usize = 2;
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum BandSoloKind {
+    /// Only the selected range is heard
+    #[default]
+    BandPass,
+    /// The selected range is boosted, sweeping it around makes resonances jump out
+    Bell,
+}
+
+/// Frequency range in Hz and the filter applied to it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BandSolo {
+    pub low: f32,
+    pub high: f32,
+    pub kind: BandSoloKind,
+}
+
+impl BandSolo {
+    /// Geometric center of the range in Hz.
+    pub fn center(&self) -> f32 {
+        (self.low * self.high).sqrt()
+    }
+
+    fn q(&self) -> f32 {
+        (self.center() / (self.high - self.low).max(f32::EPSILON)).clamp(0.3, 30.)
+    }
+
+    fn stages(&self, sample_rate: u32) -> Vec<Biquad> {
+        // keep the center frequency below Nyquist
+        let f0 = self.center().min(sample_rate as f32 * 0.49);
+        match self.kind {
+            BandSoloKind::BandPass => {
+                vec![Biquad::band_pass(f0, self.q(), sample_rate); BAND_PASS_STAGES]
+            }
+            BandSoloKind::Bell => vec![Biquad::bell(f0, self.q(), BELL_GAIN_DB, sample_rate)],
+        }
+    }
+}
+
+/// Normalized biquad coefficients (`a0` = 1).
+#[derive(Clone, Copy, Debug)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+/// Previous inputs and outputs of a [`Biquad`].
+#[derive(Clone, Copy, Default)]
+struct BiquadState {
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl Biquad {
+    /// Band-pass with 0 dB gain at `f0`.
+    fn band_pass(f0: f32, q: f32, sample_rate: u32) -> Self {
+        let w0 = 2. * PI * f0 / sample_rate as f32;
+        let alpha = w0.sin() / (2. * q);
+        let a0 = 1. + alpha;
+        Self {
+            b0: alpha / a0,
+            b1: 0.,
+            b2: -alpha / a0,
+            a1: -2. * w0.cos() / a0,
+            a2: (1. - alpha) / a0,
+        }
+    }
+
+    /// Peaking EQ boosting `f0` by `gain_db`.
+    fn bell(f0: f32, q: f32, gain_db: f32, sample_rate: u32) -> Self {
+        let a = 10f32.powf(gain_db / 40.);
+        let w0 = 2. * PI * f0 / sample_rate as f32;
+        let alpha = w0.sin() / (2. * q);
+        let a0 = 1. + alpha / a;
+        Self {
+            b0: (1. + alpha * a) / a0,
+            b1: -2. * w0.cos() / a0,
+            b2: (1. - alpha * a) / a0,
+            a1: -2. * w0.cos() / a0,
+            a2: (1. - alpha / a) / a0,
+        }
+    }
+
+    fn process(&self, state: &mut BiquadState, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * state.x1 + self.b2 * state.x2
+            - self.a1 * state.y1
+            - self.a2 * state.y2;
+        *state = BiquadState {
+            x1: x,
+            x2: state.x1,
+            y1: y,
+            y2: state.y1,
+        };
+        y
+    }
+}
+
+/// [`BandSolo`] shared between the player and the sources it plays.
+/// The version is bumped on every change so that the filter only locks the mutex when needed.
+#[derive(Clone, Default)]
+pub struct SharedBandSolo {
+    band: Arc<Mutex<Option<BandSolo>>>,
+    version: Arc<AtomicUsize>,
+}
+
+impl SharedBandSolo {
+    pub fn set(&self, band: Option<BandSolo>) {
+        *self.band.lock().unwrap() = band;
+        self.version.fetch_add(1, Ordering::Release);
+    }
+
+    fn get(&self) -> Option<BandSolo> {
+        *self.band.lock().unwrap()
+    }
+
+    fn version(&self) -> usize {
+        self.version.load(Ordering::Acquire)
+    }
+}
+
+/// [`Source`] filtering every channel with the current [`BandSolo`].
+pub struct BandSoloFilter<S> {
+    inner: S,
+    band: SharedBandSolo,
+    /// Version of `band` the stages were built for
+    version: usize,
+    stages: Vec<Biquad>,
+    /// `[channel][stage]`
+    states: Vec<Vec<BiquadState>>,
+    channel: usize,
+}
+
+impl<S: Source> BandSoloFilter<S> {
+    pub fn new(inner: S, band: SharedBandSolo) -> Self {
+        let mut filter = Self {
+            inner,
+            band,
+            version: 0,
+            stages: Vec::new(),
+            states: Vec::new(),
+            channel: 0,
+        };
+        filter.update_stages();
+        filter
+    }
+
+    fn update_stages(&mut self) {
+        self.version = self.band.version();
+        self.stages = self
+            .band
+            .get()
+            .map(|band| band.stages(self.inner.sample_rate()))
+            .unwrap_or_default();
+        self.states =
+            vec![vec![BiquadState::default(); self.stages.len()]; self.inner.channels() as usize];
+    }
+}
+
+impl<S: Source> Iterator for BandSoloFilter<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // only switch filters between frames so all channels use the same filter
+        if self.channel == 0 && self.version != self.band.version() {
+            self.update_stages();
+        }
+        let sample = self.inner.next()?;
+        let Some(states) = self.states.get_mut(self.channel) else {
+            return Some(sample);
+        };
+        let sample = self
+            .stages
+            .iter()
+            .zip(states.iter_mut())
+            .fold(sample, |x, (stage, state)| stage.process(state, x));
+        self.channel = (self.channel + 1) % self.states.len();
+        Some(sample)
+    }
+}
+
+impl<S: Source> Source for BandSoloFilter<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.inner.current_span_len()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), source::SeekError> {
+        // the filter history belongs to the old position
+        self.channel = 0;
+        self.update_stages();
+        self.inner.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Peak amplitude of a filtered sine after the filter has settled.
+    fn filtered_peak(stages: &[Biquad], freq: f32) -> f32 {
+        let sample_rate = 48000;
+        let mut states = vec![BiquadState::default(); stages.len()];
+        (0..sample_rate)
+            .map(|i| (2. * PI * freq * i as f32 / sample_rate as f32).sin())
+            .map(|x| {
+                stages
+                    .iter()
+                    .zip(states.iter_mut())
+                    .fold(x, |x, (stage, state)| stage.process(state, x))
+            })
+            .skip(sample_rate as usize / 2)
+            .fold(0., f32::max)
+    }
+
+    #[test]
+    fn test_band_pass() {
+        let band = BandSolo {
+            low: 500.,
+            high: 2000.,
+            kind: BandSoloKind::BandPass,
+        };
+        let stages = band.stages(48000);
+        assert!((filtered_peak(&stages, band.center()) - 1.).abs() < 0.01);
+        assert!(filtered_peak(&stages, 50.) < 0.05);
+        assert!(filtered_peak(&stages, 15000.) < 0.05);
+    }
+
+    #[test]
+    fn test_bell() {
+        let band = BandSolo {
+            low: 900.,
+            high: 1100.,
+            kind: BandSoloKind::Bell,
+        };
+        let stages = band.stages(48000);
+        let boost_db = 20. * filtered_peak(&stages, band.center()).log10();
+        assert!((boost_db - BELL_GAIN_DB).abs() < 0.1);
+        // far away frequencies are untouched
+        assert!((filtered_peak(&stages, 100.) - 1.).abs() < 0.01);
+    }
+}
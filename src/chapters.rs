@@ -0,0 +1,449 @@
+//! Chapters of audiobooks and podcasts and their loudness.
+//! Symphonia does not expose chapters, so they are read from ID3v2 `CHAP` frames (MP3),
+//! the Nero `chpl` atom (MP4/M4B) and `CHAPTERxxx` Vorbis comments (FLAC, Ogg).
+//! QuickTime chapter tracks are not supported.
+use ebur128::{EbuR128, Mode};
+use eyre::{Result, eyre};
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// A chapter of the loaded file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chapter {
+    pub title: String,
+    /// Start in seconds from the start of the file
+    pub start: f64,
+    /// End in seconds, the start of the next chapter or the end of the file
+    pub end: f64,
+    /// Measured while decoding, `None` if the chapter has no audio
+    pub loudness: Option<ChapterLoudness>,
+}
+
+/// Loudness statistics of one chapter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChapterLoudness {
+    /// Integrated loudness in LUFS
+    pub integrated: Option<f64>,
+    /// Loudness range in LU
+    pub range: Option<f64>,
+    /// Sample peak in dBFS
+    pub peak: f64,
+}
+
+/// Reads the chapters of a file from its tags or from the file itself.
+/// Returns chapters sorted by start time, their `end` is set by [`set_ends`].
+pub fn read_chapters(path: &Path, tags: &[(String, String)]) -> Result<Vec<Chapter>> {
+    let mut chapters = read_vorbis_chapters(tags);
+    if chapters.is_empty() {
+        let mut header = [0u8; 8];
+        File::open(path)?.read_exact(&mut header)?;
+        if header.starts_with(b"ID3") {
+            chapters = read_id3_chapters(path)?;
+        } else if &header[4..8] == b"ftyp" {
+            chapters = read_mp4_chapters(path)?;
+        }
+    }
+    chapters.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(chapters)
+}
+
+/// Sets the end of every chapter to the start of the next one, the last one ends at `duration`.
+pub fn set_ends(chapters: &mut [Chapter], duration: f64) {
+    let starts: Vec<f64> = chapters.iter().skip(1).map(|c| c.start).collect();
+    for (chapter, end) in chapters
+        .iter_mut()
+        .zip(starts.into_iter().chain([duration]))
+    {
+        chapter.end = end.max(chapter.start);
+    }
+}
+
+fn chapter(title: String, start: f64) -> Chapter {
+    Chapter {
+        title,
+        start,
+        end: start,
+        loudness: None,
+    }
+}
+
+/// `CHAPTER001=00:01:30.500` and `CHAPTER001NAME=Title`, see
+/// <https://wiki.xiph.org/Chapter_Extension>.
+fn read_vorbis_chapters(tags: &[(String, String)]) -> Vec<Chapter> {
+    let mut chapters: Vec<(String, Chapter)> = Vec::new();
+    for (key, value) in tags {
+        let key = key.to_uppercase();
+        let Some(id) = key.strip_prefix("CHAPTER") else {
+            continue;
+        };
+        if let Some(id) = id.strip_suffix("NAME") {
+            if let Some((_, chapter)) = chapters.iter_mut().find(|(i, _)| i == id) {
+                chapter.title = value.clone();
+            }
+        } else if id.chars().all(|c| c.is_ascii_digit())
+            && let Some(start) = parse_timestamp(value)
+        {
+            chapters.push((id.to_string(), chapter(format!("Chapter {id}"), start)));
+        }
+    }
+    chapters.into_iter().map(|(_, chapter)| chapter).collect()
+}
+
+/// Parses `hh:mm:ss.mmm`, hours are optional.
+fn parse_timestamp(value: &str) -> Option<f64> {
+    value.trim().split(':').try_fold(0., |total, part| {
+        Some(total * 60. + part.parse::<f64>().ok()?)
+    })
+}
+
+/// Reads `CHAP` frames of the ID3v2 tag at the start of the file, see
+/// <https://mutagen-specs.readthedocs.io/en/latest/id3/id3v2-chapters-1.0.html>.
+fn read_id3_chapters(path: &Path) -> Result<Vec<Chapter>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut header = [0u8; 10];
+    file.read_exact(&mut header)?;
+    let version = header[3];
+    let flags = header[5];
+    let mut tag = vec![0u8; synchsafe(&header[6..10]) as usize];
+    file.read_exact(&mut tag)?;
+
+    let mut frames = tag.as_slice();
+    // skip the extended header
+    if flags & 0x40 != 0 && frames.len() >= 4 {
+        let size = match version {
+            4 => synchsafe(&frames[..4]) as usize,
+            _ => u32::from_be_bytes(frames[..4].try_into()?) as usize + 4,
+        };
+        frames = frames.get(size..).unwrap_or_default();
+    }
+
+    let mut chapters = Vec::new();
+    for (id, data) in id3_frames(frames, version) {
+        if &id != b"CHAP" {
+            continue;
+        }
+        // element id, then start and end times in ms and byte offsets
+        let Some(id_end) = data.iter().position(|&b| b == 0) else {
+            continue;
+        };
+        let Some(times) = data.get(id_end + 1..id_end + 17) else {
+            continue;
+        };
+        let start_ms = u32::from_be_bytes(times[..4].try_into()?);
+        let title = id3_frames(&data[id_end + 17..], version)
+            .find(|(id, _)| id == b"TIT2")
+            .map(|(_, text)| id3_text(text))
+            .unwrap_or_else(|| String::from_utf8_lossy(&data[..id_end]).to_string());
+        chapters.push(chapter(title, start_ms as f64 / 1000.));
+    }
+    Ok(chapters)
+}
+
+fn synchsafe(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |size, b| size << 7 | (b & 0x7F) as u32)
+}
+
+/// Iterates over `(id, data)` of ID3v2.3/2.4 frames, stops at the padding.
+fn id3_frames(mut data: &[u8], version: u8) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let header = data.get(..10)?;
+        if header[0] == 0 {
+            return None;
+        }
+        let id: [u8; 4] = header[..4].try_into().ok()?;
+        let size = match version {
+            4 => synchsafe(&header[4..8]),
+            _ => u32::from_be_bytes(header[4..8].try_into().ok()?),
+        } as usize;
+        let frame = data.get(10..10 + size)?;
+        data = &data[10 + size..];
+        Some((id, frame))
+    })
+}
+
+/// Decodes an ID3v2 text frame: an encoding byte followed by the text.
+fn id3_text(data: &[u8]) -> String {
+    let Some((&encoding, text)) = data.split_first() else {
+        return String::new();
+    };
+    let utf16 = |text: &[u8], big_endian: bool| {
+        let units: Vec<u16> = text
+            .chunks_exact(2)
+            .map(|c| match big_endian {
+                true => u16::from_be_bytes([c[0], c[1]]),
+                false => u16::from_le_bytes([c[0], c[1]]),
+            })
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+    let text = match encoding {
+        // ISO-8859-1
+        0 => text.iter().map(|&b| b as char).collect(),
+        1 => match text {
+            [0xFE, 0xFF, rest @ ..] => utf16(rest, true),
+            [0xFF, 0xFE, rest @ ..] => utf16(rest, false),
+            _ => utf16(text, false),
+        },
+        2 => utf16(text, true),
+        _ => String::from_utf8_lossy(text).to_string(),
+    };
+    text.trim_end_matches('\0').to_string()
+}
+
+/// Reads the Nero `moov/udta/chpl` atom.
+fn read_mp4_chapters(path: &Path) -> Result<Vec<Chapter>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let len = file.get_ref().metadata()?.len();
+    // `moov` is small, `mdat` is skipped without reading it
+    let mut moov = None;
+    let mut pos = 0;
+    while pos + 8 <= len {
+        file.seek(SeekFrom::Start(pos))?;
+        let mut header = [0u8; 16];
+        file.read_exact(&mut header[..8])?;
+        let mut size = u32::from_be_bytes(header[..4].try_into()?) as u64;
+        let mut header_len = 8;
+        if size == 1 {
+            file.read_exact(&mut header[8..])?;
+            size = u64::from_be_bytes(header[8..].try_into()?);
+            header_len = 16;
+        } else if size == 0 {
+            size = len - pos;
+        }
+        if size < header_len {
+            return Err(eyre!("Invalid MP4 box size"));
+        }
+        if &header[4..8] == b"moov" {
+            let mut data = vec![0u8; (size - header_len) as usize];
+            file.read_exact(&mut data)?;
+            moov = Some(data);
+            break;
+        }
+        pos += size;
+    }
+    let Some(moov) = moov else {
+        return Ok(Vec::new());
+    };
+    let Some(chpl) = mp4_child(&moov, b"udta").and_then(|udta| mp4_child(udta, b"chpl")) else {
+        return Ok(Vec::new());
+    };
+
+    // version, flags, 4 reserved bytes in version 1, then the number of chapters
+    let version = chpl.first().copied().unwrap_or_default();
+    let mut data = chpl
+        .get(if version == 1 { 8 } else { 4 }..)
+        .unwrap_or_default();
+    let Some((&count, rest)) = data.split_first() else {
+        return Ok(Vec::new());
+    };
+    data = rest;
+    let mut chapters = Vec::new();
+    for _ in 0..count {
+        // start in 100 ns units, then a title prefixed with its length
+        let (Some(start), Some(&title_len)) = (data.get(..8), data.get(8)) else {
+            break;
+        };
+        let start = u64::from_be_bytes(start.try_into()?) as f64 / 10_000_000.;
+        let Some(title) = data.get(9..9 + title_len as usize) else {
+            break;
+        };
+        chapters.push(chapter(String::from_utf8_lossy(title).to_string(), start));
+        data = &data[9 + title_len as usize..];
+    }
+    Ok(chapters)
+}
+
+/// Returns the content of the first child box of type `kind`.
+fn mp4_child<'a>(mut data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    while data.len() >= 8 {
+        let size = u32::from_be_bytes(data[..4].try_into().ok()?) as usize;
+        let content = data.get(8..size)?;
+        if &data[4..8] == kind {
+            return Some(content);
+        }
+        data = &data[size..];
+    }
+    None
+}
+
+/// Measures the loudness of every chapter while the file is decoded.
+pub struct ChapterMeter {
+    /// First frame of every chapter
+    starts: Vec<u64>,
+    channels: usize,
+    sample_rate: u32,
+    /// Frame index of the next pushed sample
+    frame: u64,
+    /// Chapter being measured
+    current: Option<(usize, EbuR128)>,
+    results: Vec<Option<ChapterLoudness>>,
+}
+
+impl ChapterMeter {
+    pub fn new(chapters: &[Chapter], sample_rate: u32, channels: usize) -> Self {
+        Self {
+            starts: chapters
+                .iter()
+                .map(|c| (c.start * sample_rate as f64) as u64)
+                .collect(),
+            channels: channels.max(1),
+            sample_rate,
+            frame: 0,
+            current: None,
+            results: vec![None; chapters.len()],
+        }
+    }
+
+    /// Measures interleaved samples, splitting them at chapter boundaries.
+    pub fn push(&mut self, mut samples: &[f32]) {
+        while samples.len() >= self.channels {
+            // audio before the first chapter is not measured
+            let index = self
+                .starts
+                .partition_point(|&start| start <= self.frame)
+                .checked_sub(1);
+            let next_start = self
+                .starts
+                .get(index.map_or(0, |i| i + 1))
+                .copied()
+                .unwrap_or(u64::MAX);
+            let frames = ((samples.len() / self.channels) as u64).min(next_start - self.frame);
+            let (chunk, rest) = samples.split_at(frames as usize * self.channels);
+            if let Some(index) = index {
+                if self.current.as_ref().is_none_or(|(i, _)| *i != index) {
+                    self.finish_chapter();
+                    self.current = EbuR128::new(
+                        self.channels as u32,
+                        self.sample_rate,
+                        Mode::I | Mode::LRA | Mode::SAMPLE_PEAK,
+                    )
+                    .ok()
+                    .map(|meter| (index, meter));
+                }
+                if let Some((_, meter)) = &mut self.current
+                    && meter.add_frames_f32(chunk).is_err()
+                {
+                    self.current = None;
+                }
+            }
+            self.frame += frames;
+            samples = rest;
+        }
+    }
+
+    fn finish_chapter(&mut self) {
+        let Some((index, meter)) = self.current.take() else {
+            return;
+        };
+        let peak = (0..self.channels as u32)
+            .filter_map(|channel| meter.sample_peak(channel).ok())
+            .fold(0., f64::max);
+        self.results[index] = Some(ChapterLoudness {
+            integrated: meter.loudness_global().ok().filter(|l| l.is_finite()),
+            range: meter.loudness_range().ok(),
+            peak: 20. * peak.log10(),
+        });
+    }
+
+    /// Stores the loudness of every chapter in `chapters`.
+    pub fn finish(mut self, chapters: &mut [Chapter]) {
+        self.finish_chapter();
+        for (chapter, loudness) in chapters.iter_mut().zip(self.results) {
+            chapter.loudness = loudness;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vorbis_chapters() {
+        let tags = [
+            ("CHAPTER001", "00:00:00.000"),
+            ("CHAPTER001NAME", "Intro"),
+            ("ARTIST", "Someone"),
+            ("chapter002", "00:01:30.500"),
+            ("CHAPTER003", "not a time"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        let mut chapters = read_vorbis_chapters(&tags);
+        set_ends(&mut chapters, 100.);
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].title, "Intro");
+        assert_eq!(chapters[0].end, 90.5);
+        assert_eq!(chapters[1].title, "Chapter 002");
+        assert_eq!(chapters[1].start, 90.5);
+        assert_eq!(chapters[1].end, 100.);
+    }
+
+    #[test]
+    fn test_id3_chapters() {
+        let frame = |id: &[u8; 4], data: &[u8]| {
+            let mut frame = id.to_vec();
+            frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            frame.extend_from_slice(&[0, 0]);
+            frame.extend_from_slice(data);
+            frame
+        };
+        let chap = |id: &[u8], start_ms: u32, title: &str| {
+            let mut data = id.to_vec();
+            data.push(0);
+            data.extend_from_slice(&start_ms.to_be_bytes());
+            data.extend_from_slice(&[0; 12]);
+            data.extend(frame(b"TIT2", &[&[3], title.as_bytes()].concat()));
+            frame(b"CHAP", &data)
+        };
+        // ID3v2.3, so frame sizes are plain integers
+        let frames = [chap(b"ch1", 61_000, "Second"), chap(b"ch0", 0, "First")].concat();
+        let mut file = b"ID3\x03\x00\x00".to_vec();
+        let size = frames.len() as u32;
+        file.extend((0..4).rev().map(|i| (size >> (7 * i) & 0x7F) as u8));
+        file.extend(frames);
+        file.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+
+        let dir =
+            std::env::temp_dir().join(format!("soundscope_chapters_id3_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("book.mp3");
+        std::fs::write(&path, file).unwrap();
+        let chapters = read_chapters(&path, &[]).unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].title, "First");
+        assert_eq!(chapters[1].title, "Second");
+        assert_eq!(chapters[1].start, 61.);
+    }
+
+    #[test]
+    fn test_chapter_meter() {
+        let sample_rate = 48000;
+        let mut chapters = vec![chapter(String::new(), 0.), chapter(String::new(), 5.)];
+        let mut meter = ChapterMeter::new(&chapters, sample_rate, 1);
+        // 5 s of a loud sine, then 5 s of a quieter one, pushed in odd sized packets
+        let samples: Vec<f32> = (0..sample_rate * 10)
+            .map(|i| {
+                let amplitude = if i < sample_rate * 5 { 0.5 } else { 0.05 };
+                amplitude * (i as f32 * 1000. * std::f32::consts::TAU / sample_rate as f32).sin()
+            })
+            .collect();
+        for packet in samples.chunks(1153) {
+            meter.push(packet);
+        }
+        meter.finish(&mut chapters);
+
+        let loud = chapters[0].loudness.unwrap();
+        let quiet = chapters[1].loudness.unwrap();
+        assert!((loud.peak - 20. * 0.5f64.log10()).abs() < 0.1);
+        // 20 dB quieter
+        let difference = loud.integrated.unwrap() - quiet.integrated.unwrap();
+        assert!((difference - 20.).abs() < 0.1);
+    }
+}
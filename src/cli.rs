@@ -0,0 +1,301 @@
+//! Command line arguments parsing.
+use crate::{
+    config::LogFormat,
+    raw_pcm::{RawEncoding, RawFormat},
+    stream_input,
+};
+use eyre::{Result, eyre};
+use std::{path::PathBuf, str::FromStr};
+
+/// What `soundscope` was asked to do.
+pub enum Command {
+    Help,
+    Version,
+    Run(Args),
+    /// Headless loudness log of an input device (`soundscope log`)
+    Log(LogArgs),
+}
+
+/// Arguments of `soundscope log`, `None` falls back to the config.
+#[derive(Default, Debug, PartialEq)]
+pub struct LogArgs {
+    /// Index of the input device in the device list (`--device` is 1-based), the default one if not set
+    pub device: Option<usize>,
+    pub interval: Option<f64>,
+    pub format: Option<LogFormat>,
+    pub directory: Option<PathBuf>,
+}
+
+/// Arguments of the TUI.
+#[derive(Default, Debug, PartialEq)]
+pub struct Args {
+    /// Audio files (or playlists) to open on startup
+    pub files: Vec<PathBuf>,
+    /// Index of the audio track to decode in multi-track files (`--track` is 1-based)
+    pub track: Option<usize>,
+    /// Reload the open file when it changes on disk
+    pub watch: bool,
+    /// Analyze audio piped to stdin (`-`)
+    pub stdin: bool,
+    /// HTTP/Icecast stream to analyze, given instead of a file
+    pub stream: Option<String>,
+    /// Format of headerless PCM on stdin or in the files (`--raw`, `--rate`, `--channels`, `--offset`)
+    pub raw: Option<RawFormat>,
+}
+
+/// Parses command line arguments (without the program name).
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command> {
+    let mut args = args.into_iter().peekable();
+    if args.next_if(|arg| arg == "log").is_some() {
+        return parse_log(args);
+    }
+    let mut parsed = Args::default();
+    let mut encoding = None;
+    let mut sample_rate = None;
+    let mut channels = None;
+    let mut offset = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-" => parsed.stdin = true,
+            "--raw" => encoding = Some(value::<RawEncoding>(&arg, args.next())?),
+            "--rate" => sample_rate = Some(value::<u32>(&arg, args.next())?),
+            "--channels" => channels = Some(value::<usize>(&arg, args.next())?),
+            "--offset" => offset = Some(value::<u64>(&arg, args.next())?),
+            "-h" | "--help" => return Ok(Command::Help),
+            "-v" | "--version" => return Ok(Command::Version),
+            "-t" | "--track" => {
+                let track = args
+                    .next()
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| eyre!("{arg} expects a track number starting from 1"))?;
+                parsed.track = Some(track - 1);
+            }
+            "-w" | "--watch" => parsed.watch = true,
+            flag if flag.starts_with('-') => return Err(eyre!("Unknown option: {flag}")),
+            url if stream_input::is_url(url) => {
+                if parsed.stream.replace(url.to_string()).is_some() {
+                    return Err(eyre!("Only one stream URL can be analyzed at a time"));
+                }
+            }
+            file => parsed.files.push(PathBuf::from(file)),
+        }
+    }
+    parsed.raw = match encoding {
+        Some(encoding) => Some(RawFormat {
+            encoding,
+            sample_rate: sample_rate.unwrap_or(RawFormat::DEFAULT_SAMPLE_RATE),
+            channels: channels.unwrap_or(RawFormat::DEFAULT_CHANNELS),
+            offset: offset.unwrap_or(0),
+        }),
+        None if sample_rate.is_some() || channels.is_some() || offset.is_some() => {
+            return Err(eyre!("--rate, --channels and --offset need --raw <FORMAT>"));
+        }
+        None => None,
+    };
+    if let Some(raw) = parsed.raw {
+        raw.validate()?;
+    }
+    Ok(Command::Run(parsed))
+}
+
+fn parse_log(mut args: impl Iterator<Item = String>) -> Result<Command> {
+    let mut parsed = LogArgs::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-d" | "--device" => {
+                let device = value::<usize>(&arg, args.next())
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| eyre!("{arg} expects a device number starting from 1"))?;
+                parsed.device = Some(device - 1);
+            }
+            "--interval" => {
+                let interval = value::<f64>(&arg, args.next())?;
+                if interval.is_nan() || interval <= 0. {
+                    return Err(eyre!("--interval must be greater than 0"));
+                }
+                parsed.interval = Some(interval);
+            }
+            "--format" => parsed.format = Some(value::<LogFormat>(&arg, args.next())?),
+            "-o" | "--output" => parsed.directory = Some(value::<PathBuf>(&arg, args.next())?),
+            "-h" | "--help" => return Ok(Command::Help),
+            other => return Err(eyre!("Unknown option for log: {other}")),
+        }
+    }
+    Ok(Command::Log(parsed))
+}
+
+/// Parses the value of an option, e.g. the `48000` of `--rate 48000`.
+fn value<T: FromStr>(option: &str, value: Option<String>) -> Result<T> {
+    value
+        .as_deref()
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| eyre!("Invalid or missing value for {option}"))
+}
+
+pub fn print_help() {
+    println!("Usage: soundscope [OPTIONS] [FILE]...");
+    println!("       soundscope log [LOG OPTIONS]");
+    println!();
+    println!("Arguments:");
+    println!("  [FILE]...  Audio files or M3U/M3U8 playlists to open on startup,");
+    println!("             - analyzes audio piped to stdin,");
+    println!("             an http:// URL analyzes an internet radio or Icecast stream,");
+    println!("             https:// streams are not supported");
+    println!();
+    println!("Options:");
+    println!("  -t, --track <N>       Audio track to play in files with several tracks");
+    println!("  -w, --watch           Reload the open file when it is rewritten");
+    println!(
+        "      --raw <FMT>       Stdin and the files are headerless PCM: u8, s8, s16le, s16be,"
+    );
+    println!("                        s24le, s24be, s32le, s32be, f32le, f32be, f64le, f64be");
+    println!("      --rate <HZ>       Sample rate of headerless PCM (default: 48000)");
+    println!("      --channels <N>    Channels of headerless PCM (default: 2)");
+    println!("      --offset <BYTES>  Bytes to skip before the samples (default: 0)");
+    println!("  -h, --help            Print help");
+    println!("  -v, --version         Print version");
+    println!();
+    println!("Log options (loudness log of an input device, without the TUI):");
+    println!("  -d, --device <N>       Input device, numbered as in the device list");
+    println!("                         (default: the default input device)");
+    println!("      --interval <SECS>  Seconds between two lines (default: 1)");
+    println!("      --format <FMT>     csv or jsonl (default: csv)");
+    println!("  -o, --output <DIR>     Directory of the daily log files");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Command> {
+        parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn test_parse_files() {
+        let Ok(Command::Run(args)) = parse_args(&["a.wav", "b.flac"]) else {
+            panic!("expected Command::Run");
+        };
+        assert_eq!(
+            args.files,
+            [PathBuf::from("a.wav"), PathBuf::from("b.flac")]
+        );
+    }
+
+    #[test]
+    fn test_parse_flags() {
+        assert!(matches!(parse_args(&["-h"]), Ok(Command::Help)));
+        assert!(matches!(
+            parse_args(&["a.wav", "--version"]),
+            Ok(Command::Version)
+        ));
+        assert!(parse_args(&["--unknown"]).is_err());
+
+        let Ok(Command::Run(args)) = parse_args(&["-w", "a.wav"]) else {
+            panic!("expected Command::Run");
+        };
+        assert!(args.watch);
+    }
+
+    #[test]
+    fn test_parse_track() {
+        let Ok(Command::Run(args)) = parse_args(&["--track", "2", "a.mkv"]) else {
+            panic!("expected Command::Run");
+        };
+        assert_eq!(args.track, Some(1));
+        assert_eq!(args.files, [PathBuf::from("a.mkv")]);
+
+        assert!(parse_args(&["--track", "0"]).is_err());
+        assert!(parse_args(&["a.mkv", "-t"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_log() {
+        let Ok(Command::Log(args)) = parse_args(&["log", "--device", "2", "--format", "jsonl"])
+        else {
+            panic!("expected Command::Log");
+        };
+        assert_eq!(
+            args,
+            LogArgs {
+                device: Some(1),
+                format: Some(LogFormat::Jsonl),
+                ..LogArgs::default()
+            }
+        );
+        assert!(parse_args(&["log", "--interval", "0"]).is_err());
+        assert!(parse_args(&["log", "a.wav"]).is_err());
+
+        let Ok(Command::Run(args)) = parse_args(&["a.wav", "log"]) else {
+            panic!("expected Command::Run");
+        };
+        assert_eq!(args.files, [PathBuf::from("a.wav"), PathBuf::from("log")]);
+    }
+
+    #[test]
+    fn test_parse_stdin() {
+        let Ok(Command::Run(args)) = parse_args(&["--raw", "s16le", "--rate", "44100", "-"]) else {
+            panic!("expected Command::Run");
+        };
+        assert!(args.stdin);
+        assert!(args.files.is_empty());
+        assert_eq!(
+            args.raw,
+            Some(RawFormat {
+                encoding: RawEncoding::S16Le,
+                sample_rate: 44100,
+                channels: 2,
+                offset: 0,
+            })
+        );
+
+        assert!(parse_args(&["--rate", "44100", "-"]).is_err());
+        assert!(parse_args(&["--raw", "pcm", "-"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_stream() {
+        let Ok(Command::Run(args)) = parse_args(&["a.wav", "http://radio.example:8000/live"])
+        else {
+            panic!("expected Command::Run");
+        };
+        assert_eq!(args.files, [PathBuf::from("a.wav")]);
+        assert_eq!(
+            args.stream.as_deref(),
+            Some("http://radio.example:8000/live")
+        );
+
+        assert!(parse_args(&["http://a.example", "http://b.example"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_raw_file() {
+        let Ok(Command::Run(args)) = parse_args(&[
+            "--raw",
+            "s24be",
+            "--channels",
+            "1",
+            "--offset",
+            "64",
+            "a.raw",
+        ]) else {
+            panic!("expected Command::Run");
+        };
+        assert!(!args.stdin);
+        assert_eq!(args.files, [PathBuf::from("a.raw")]);
+        assert_eq!(
+            args.raw,
+            Some(RawFormat {
+                encoding: RawEncoding::S24Be,
+                sample_rate: 48000,
+                channels: 1,
+                offset: 64,
+            })
+        );
+
+        assert!(parse_args(&["--offset", "64", "a.raw"]).is_err());
+        assert!(parse_args(&["--raw", "s16le", "--channels", "0", "a.raw"]).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+//! User settings stored in `{YOUR_CONFIG_DIRECTORY}/soundscope/config.toml`.
+//! Every field is optional, missing values fall back to defaults.
+use crate::alarms::AlarmRule;
+use eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf, str::FromStr};
+use toml_edit::{DocumentMut, Item};
+
+#[derive(Deserialize, Serialize, Default, Debug, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub seek: SeekConfig,
+    /// Name of the output device used for playback, the default one if not set
+    pub output_device: Option<String>,
+    pub recording: RecordingConfig,
+    pub log: LogConfig,
+    /// Threshold alarms of the live modes (`[[alarms]]`)
+    pub alarms: Vec<AlarmRule>,
+}
+
+/// Loudness log of the live input, written with `l` or `soundscope log`.
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Where the daily log files are written, `<data directory>/soundscope/logs` if not set
+    pub directory: Option<PathBuf>,
+    /// Seconds between two lines
+    pub interval: f64,
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            interval: 1.,
+            format: LogFormat::Csv,
+        }
+    }
+}
+
+impl LogConfig {
+    /// The configured directory, the data directory or the current directory.
+    pub fn directory(&self) -> PathBuf {
+        self.directory
+            .clone()
+            .or_else(|| dirs::data_dir().map(|dir| dir.join("soundscope").join("logs")))
+            .unwrap_or_default()
+    }
+
+    /// Rejects what `--interval` rejects on the command line.
+    pub fn validate(&self) -> Result<()> {
+        if self.interval.is_nan() || self.interval <= 0. {
+            return Err(eyre!("log.interval must be greater than 0"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Csv,
+    /// JSON Lines, one object per line
+    Jsonl,
+}
+
+impl LogFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            LogFormat::Csv => "csv",
+            LogFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(LogFormat::Csv),
+            "jsonl" => Ok(LogFormat::Jsonl),
+            _ => Err(eyre!("Unknown log format: {s}, expected csv or jsonl")),
+        }
+    }
+}
+
+/// Recordings of the microphone input.
+#[derive(Deserialize, Serialize, Default, Debug, PartialEq)]
+#[serde(default)]
+pub struct RecordingConfig {
+    /// Where recordings are saved, the music directory if not set
+    pub directory: Option<PathBuf>,
+    pub trigger: TriggerConfig,
+}
+
+/// Triggered recording, which records every time the input gets loud.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct TriggerConfig {
+    /// Level that starts a recording, in `unit`
+    pub threshold: f64,
+    pub unit: TriggerUnit,
+    /// Seconds before the trigger included in the recording
+    pub pre_roll: f64,
+    /// Seconds below the threshold that end the recording
+    pub hold: f64,
+}
+
+impl Default for TriggerConfig {
+    fn default() -> Self {
+        Self {
+            threshold: -30.,
+            unit: TriggerUnit::Dbfs,
+            pre_roll: 2.,
+            hold: 5.,
+        }
+    }
+}
+
+/// What the trigger threshold is compared to.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerUnit {
+    /// Sample peak
+    Dbfs,
+    /// Momentary loudness (400 ms)
+    Lufs,
+}
+
+impl RecordingConfig {
+    /// The configured directory, the music directory or the current directory.
+    pub fn directory(&self) -> PathBuf {
+        self.directory
+            .clone()
+            .or_else(dirs::audio_dir)
+            .unwrap_or_default()
+    }
+}
+
+/// Seek steps in seconds.
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct SeekConfig {
+    /// Left/Right arrows
+    pub step: f64,
+    /// Shift + Left/Right arrows
+    pub large_step: f64,
+    /// Ctrl + Left/Right arrows
+    pub small_step: f64,
+}
+
+impl Default for SeekConfig {
+    fn default() -> Self {
+        Self {
+            step: 5.,
+            large_step: 30.,
+            small_step: 1.,
+        }
+    }
+}
+
+impl Config {
+    /// Path of the config file, `None` if the config directory does not exist.
+    pub fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("soundscope").join("config.toml"))
+    }
+
+    /// Loads the config file. Returns the default config if it does not exist.
+    pub fn load() -> Result<Self> {
+        match Self::path() {
+            Some(path) if path.exists() => Self::parse(&fs::read_to_string(path)?),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text)?;
+        config.log.validate()?;
+        Ok(config)
+    }
+
+    /// Sets `output_device` in the config file, creating the config directory if needed.
+    /// The other settings are kept, a file that is not valid TOML is not overwritten.
+    pub fn save_output_device(name: &str) -> Result<()> {
+        let Some(path) = Self::path() else {
+            return Err(eyre!("Config directory does not exist"));
+        };
+        let text = if path.exists() {
+            fs::read_to_string(&path)?
+        } else {
+            String::new()
+        };
+        let text = with_output_device(&text, name)
+            .map_err(|err| eyre!("{} is not valid TOML: {err}", path.display()))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+/// Returns the config text with `output_device` set to `name`,
+/// the comments and the order of the other settings are kept.
+fn with_output_device(text: &str, name: &str) -> Result<String> {
+    let mut document: DocumentMut = text.parse()?;
+    match document
+        .get_mut("output_device")
+        .and_then(Item::as_value_mut)
+    {
+        // keep the comment after the old value
+        Some(value) => {
+            let decor = value.decor().clone();
+            *value = name.into();
+            *value.decor_mut() = decor;
+        }
+        None => document["output_device"] = toml_edit::value(name),
+    }
+    Ok(document.to_string())
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "macos") {
+        // On macOS, use ~/.config instead of ~/Library/Application Support
+        let home = std::env::var("HOME").ok()?;
+        Some(PathBuf::from(home).join(".config"))
+    } else {
+        dirs::config_local_dir()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alarms::Metric;
+
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let config: Config = toml::from_str("[seek]\nlarge_step = 60.0\n").unwrap();
+        assert_eq!(config.seek.large_step, 60.);
+        assert_eq!(config.seek.step, SeekConfig::default().step);
+        assert_eq!(config.seek.small_step, SeekConfig::default().small_step);
+
+        let config: Config = toml::from_str("[recording.trigger]\nunit = \"lufs\"\n").unwrap();
+        assert_eq!(config.recording.trigger.unit, TriggerUnit::Lufs);
+        assert_eq!(config.recording.trigger.hold, TriggerConfig::default().hold);
+
+        let config: Config =
+            toml::from_str("[[alarms]]\nname = \"Over\"\nmetric = \"true_peak\"\nabove = -1.0\n")
+                .unwrap();
+        assert_eq!(config.alarms[0].metric, Metric::TruePeak);
+        assert_eq!(config.alarms[0].duration, 0.);
+
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_log_interval() {
+        assert_eq!(
+            Config::parse("[log]\ninterval = 0.5\n")
+                .unwrap()
+                .log
+                .interval,
+            0.5
+        );
+        for interval in ["0.0", "-1.0", "nan"] {
+            assert!(Config::parse(&format!("[log]\ninterval = {interval}\n")).is_err());
+        }
+    }
+
+    #[test]
+    fn test_with_output_device() {
+        let text = "# my settings\noutput_device = \"Speakers\" # the loud ones\n\n\
+                    [seek]\nstep = 3.0\nunknown = 1\n";
+        let saved = with_output_device(text, "Headphones").unwrap();
+        assert_eq!(
+            saved,
+            "# my settings\noutput_device = \"Headphones\" # the loud ones\n\n\
+             [seek]\nstep = 3.0\nunknown = 1\n"
+        );
+        let config: Config = toml::from_str(&saved).unwrap();
+        assert_eq!(config.output_device.as_deref(), Some("Headphones"));
+        assert_eq!(config.seek.step, 3.);
+        // a new setting goes before the first table
+        assert_eq!(
+            with_output_device("[seek]\nstep = 3.0\n", "Headphones").unwrap(),
+            "output_device = \"Headphones\"\n[seek]\nstep = 3.0\n"
+        );
+        assert!(
+            with_output_device("", "Headphones")
+                .unwrap()
+                .contains("Headphones")
+        );
+        // a broken file is not replaced
+        assert!(with_output_device("[seek\n", "Headphones").is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! CUE sheets describing the tracks of a single-file album (image + cue).
+//! The referenced audio file is decoded and the tracks are shown as chapters.
+use crate::chapters::Chapter;
+use eyre::{Result, eyre};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// CUE time frames per second
+const FRAMES_PER_SECOND: f64 = 75.;
+/// Tried when the file named in the sheet does not exist, rips are often re-encoded
+/// without updating the sheet
+const FALLBACK_EXTENSIONS: [&str; 2] = ["flac", "wav"];
+
+#[derive(Debug, PartialEq)]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// `INDEX 01` in seconds
+    pub start: f64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CueSheet {
+    /// Audio file of the album
+    pub audio_path: PathBuf,
+    pub tracks: Vec<CueTrack>,
+}
+
+pub fn is_cue(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("cue"))
+}
+
+impl CueSheet {
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path)?;
+        let text = String::from_utf8_lossy(&bytes);
+        let dir = path.parent().unwrap_or(Path::new(""));
+        Self::parse(text.trim_start_matches('\u{feff}'), dir)
+    }
+
+    /// Parses a sheet, the audio file is resolved relative to `dir`.
+    /// Only the tracks of the first `FILE` are read.
+    fn parse(text: &str, dir: &Path) -> Result<Self> {
+        let mut file = None;
+        let mut tracks: Vec<CueTrack> = Vec::new();
+        for line in text.lines() {
+            let line = line.trim();
+            let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match command.to_uppercase().as_str() {
+                "FILE" if file.is_none() => file = Some(unquote(rest_without_type(rest))),
+                // a sheet for several files, stop at the second one
+                "FILE" => break,
+                "TRACK" => {
+                    let number = rest
+                        .split_whitespace()
+                        .next()
+                        .and_then(|n| n.parse().ok())
+                        .ok_or_else(|| eyre!("Invalid track number in CUE sheet: {line}"))?;
+                    tracks.push(CueTrack {
+                        number,
+                        title: None,
+                        performer: None,
+                        start: 0.,
+                    });
+                }
+                // album title and performer come before the first track
+                "TITLE" => {
+                    if let Some(track) = tracks.last_mut() {
+                        track.title = Some(unquote(rest));
+                    }
+                }
+                "PERFORMER" => {
+                    if let Some(track) = tracks.last_mut() {
+                        track.performer = Some(unquote(rest));
+                    }
+                }
+                "INDEX" => {
+                    let mut parts = rest.split_whitespace();
+                    if parts.next() == Some("01")
+                        && let Some(track) = tracks.last_mut()
+                    {
+                        track.start = parts
+                            .next()
+                            .and_then(parse_time)
+                            .ok_or_else(|| eyre!("Invalid index in CUE sheet: {line}"))?;
+                    }
+                }
+                _ => (),
+            }
+        }
+        let file = file.ok_or_else(|| eyre!("CUE sheet does not reference an audio file"))?;
+        Ok(Self {
+            audio_path: resolve(dir, &file),
+            tracks,
+        })
+    }
+
+    /// Tracks as chapters, e.g. `03. Title`.
+    pub fn chapters(&self) -> Vec<Chapter> {
+        self.tracks
+            .iter()
+            .map(|track| {
+                let name = match (&track.performer, &track.title) {
+                    (Some(performer), Some(title)) => format!("{performer} - {title}"),
+                    (None, Some(title)) => title.clone(),
+                    (_, None) => String::from("Track"),
+                };
+                Chapter {
+                    title: format!("{:02}. {name}", track.number),
+                    start: track.start,
+                    end: track.start,
+                    loudness: None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// `"album.flac" WAVE` -> `"album.flac"`
+fn rest_without_type(rest: &str) -> &str {
+    match rest.rsplit_once(' ') {
+        Some((file, _)) if !rest.ends_with('"') => file,
+        _ => rest,
+    }
+}
+
+fn unquote(value: &str) -> String {
+    value.trim().trim_matches('"').to_string()
+}
+
+/// Parses `mm:ss:ff`, where `ff` are frames of 1/75 s.
+fn parse_time(value: &str) -> Option<f64> {
+    let mut parts = value.split(':').map(|p| p.parse::<u32>().ok());
+    let (Some(Some(minutes)), Some(Some(seconds)), Some(Some(frames)), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    Some(minutes as f64 * 60. + seconds as f64 + frames as f64 / FRAMES_PER_SECOND)
+}
+
+fn resolve(dir: &Path, file: &str) -> PathBuf {
+    let path = dir.join(file);
+    if path.exists() {
+        return path;
+    }
+    FALLBACK_EXTENSIONS
+        .iter()
+        .map(|extension| path.with_extension(extension))
+        .find(|path| path.exists())
+        .unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let sheet = r#"REM GENRE Jazz
+PERFORMER "The Band"
+TITLE "The Album"
+FILE "The Band - The Album.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "Opening"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Second Song"
+    PERFORMER "Guest"
+    INDEX 00 03:59:50
+    INDEX 01 04:02:15
+"#;
+        let cue = CueSheet::parse(sheet, Path::new("rips")).unwrap();
+        assert_eq!(
+            cue.audio_path,
+            Path::new("rips").join("The Band - The Album.flac")
+        );
+        assert_eq!(cue.tracks.len(), 2);
+        assert_eq!(cue.tracks[1].start, 242.2);
+
+        let chapters = cue.chapters();
+        assert_eq!(chapters[0].title, "01. Opening");
+        assert_eq!(chapters[1].title, "02. Guest - Second Song");
+
+        assert!(CueSheet::parse("TRACK 01 AUDIO\n", Path::new("")).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! Technical information and tags of an audio file shown in the info popup.
+use crate::riff;
+use eyre::Result;
+use std::path::Path;
+use symphonia::core::meta::MetadataRevision;
+
+/// Collected while the file is decoded.
+#[derive(Clone, Default, Debug)]
+pub struct FileInfo {
+    pub container: String,
+    pub codec: String,
+    pub bits_per_sample: Option<u32>,
+    pub sample_rate: u32,
+    pub channels: usize,
+    /// Speaker positions, e.g. `FRONT_LEFT | FRONT_RIGHT`
+    pub channel_layout: String,
+    /// Duration in samples per channel
+    pub frames: u64,
+    pub file_size: u64,
+    /// Tags of the container followed by the BWF `bext` fields
+    pub tags: Vec<(String, String)>,
+}
+
+impl FileInfo {
+    pub fn duration_secs(&self) -> f64 {
+        if self.sample_rate == 0 {
+            return 0.;
+        }
+        self.frames as f64 / self.sample_rate as f64
+    }
+
+    /// Average bitrate in kbit/s, including the container overhead.
+    pub fn bitrate(&self) -> Option<f64> {
+        let duration = self.duration_secs();
+        (duration > 0.).then(|| self.file_size as f64 * 8. / duration / 1000.)
+    }
+
+    /// Rows of the info popup: technical information first, then tags.
+    pub fn rows(&self) -> Vec<(String, String)> {
+        let mut rows = vec![
+            ("Container".to_string(), self.container.clone()),
+            ("Codec".to_string(), self.codec.clone()),
+            (
+                "Bit depth".to_string(),
+                self.bits_per_sample
+                    .map_or_else(|| "-".to_string(), |b| format!("{b} bit")),
+            ),
+            (
+                "Bitrate".to_string(),
+                self.bitrate()
+                    .map_or_else(|| "-".to_string(), |b| format!("{b:.0} kbit/s")),
+            ),
+            (
+                "Sample rate".to_string(),
+                format!("{} Hz", self.sample_rate),
+            ),
+            (
+                "Channels".to_string(),
+                format!("{} ({})", self.channels, self.channel_layout),
+            ),
+            (
+                "Duration".to_string(),
+                format!("{} samples ({:.3} s)", self.frames, self.duration_secs()),
+            ),
+            (
+                "File size".to_string(),
+                format!(
+                    "{} bytes ({:.1} MiB)",
+                    self.file_size,
+                    self.file_size as f64 / 1024. / 1024.
+                ),
+            ),
+        ];
+        rows.extend(self.tags.iter().cloned());
+        rows
+    }
+}
+
+/// Returns tags of a metadata revision. Known tags use symphonia's standard names
+/// (e.g. `TrackTitle`, `IdentIsrc`, `ReplayGainTrackGain`), others keep the key of the file.
+pub fn read_tags(revision: &MetadataRevision) -> Vec<(String, String)> {
+    revision
+        .tags()
+        .iter()
+        .map(|tag| {
+            let key = tag
+                .std_key
+                .map_or_else(|| tag.key.clone(), |key| format!("{key:?}"));
+            (key, tag.value.to_string())
+        })
+        .collect()
+}
+
+/// Reads the description, originator and time reference from the BWF `bext` chunk.
+/// Returns no rows if the file has no `bext` chunk.
+pub fn read_bext(path: &Path, sample_rate: u32) -> Result<Vec<(String, String)>> {
+    let chunks = riff::read_chunks(path, &[b"bext"])?;
+    let Some(bext) = chunks.first() else {
+        return Ok(Vec::new());
+    };
+    let text = |start: usize, len: usize| {
+        bext.data
+            .get(start..(start + len).min(bext.data.len()))
+            .map(riff::read_text)
+            .unwrap_or_default()
+    };
+
+    let mut rows = vec![
+        ("BWF Description".to_string(), text(0, 256)),
+        ("BWF Originator".to_string(), text(256, 32)),
+        ("BWF Originator reference".to_string(), text(288, 32)),
+        (
+            "BWF Origination".to_string(),
+            format!("{} {}", text(320, 10), text(330, 8)),
+        ),
+    ];
+    if let (Some(low), Some(high)) = (
+        riff::read_u32(&bext.data, 338),
+        riff::read_u32(&bext.data, 342),
+    ) {
+        let samples = (high as u64) << 32 | low as u64;
+        let seconds = samples as f64 / sample_rate.max(1) as f64;
+        rows.push((
+            "BWF Time reference".to_string(),
+            format!(
+                "{samples} samples ({:02}:{:02}:{:06.3})",
+                (seconds / 3600.) as u64,
+                (seconds / 60.) as u64 % 60,
+                seconds % 60.
+            ),
+        ));
+    }
+    rows.retain(|(_, value)| !value.trim().is_empty());
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_read_bext() {
+        let mut bext = vec![0u8; 602];
+        bext[..11].copy_from_slice(b"Interview 3");
+        bext[256..263].copy_from_slice(b"Zoom F6");
+        bext[320..330].copy_from_slice(b"2026-05-01");
+        bext[330..338].copy_from_slice(b"10:30:00");
+        // one hour at 48 kHz
+        bext[338..342].copy_from_slice(&(48000u32 * 3600).to_le_bytes());
+
+        let mut body = b"WAVE".to_vec();
+        body.extend_from_slice(b"bext");
+        body.extend_from_slice(&(bext.len() as u32).to_le_bytes());
+        body.extend(bext);
+        let mut wav = b"RIFF".to_vec();
+        wav.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        wav.extend(body);
+
+        let dir =
+            std::env::temp_dir().join(format!("soundscope_file_info_bext_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bext.wav");
+        fs::write(&path, wav).unwrap();
+
+        let rows = read_bext(&path, 48000).unwrap();
+        let get = |key: &str| rows.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        assert_eq!(get("BWF Description"), Some("Interview 3"));
+        assert_eq!(get("BWF Originator"), Some("Zoom F6"));
+        // empty fields are skipped
+        assert_eq!(get("BWF Originator reference"), None);
+        assert_eq!(get("BWF Origination"), Some("2026-05-01 10:30:00"));
+        assert_eq!(
+            get("BWF Time reference"),
+            Some("172800000 samples (01:00:00.000)")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+//! Watches the loaded file so it can be reloaded when it is rewritten, e.g. by a DAW bounce.
+//! The file is polled, a change is only reported once the file stopped changing.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often the file is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long the file must stay unchanged before it is considered written
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// Modification time and size of a file, `None` while the file does not exist.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Stamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl Stamp {
+    fn read(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+pub struct FileWatch {
+    path: PathBuf,
+    last_poll: Instant,
+    /// State of the loaded version of the file
+    loaded: Option<Stamp>,
+    /// New state of the file and when it was first seen
+    changed: Option<(Option<Stamp>, Instant)>,
+}
+
+impl FileWatch {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            last_poll: Instant::now(),
+            loaded: Stamp::read(path),
+            changed: None,
+        }
+    }
+
+    /// Returns `true` once after the file has been rewritten and then left unchanged for [`SETTLE_TIME`].
+    pub fn poll(&mut self) -> bool {
+        let now = Instant::now();
+        if now.duration_since(self.last_poll) < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = now;
+        self.update(Stamp::read(&self.path), now)
+    }
+
+    fn update(&mut self, stamp: Option<Stamp>, now: Instant) -> bool {
+        if stamp == self.loaded {
+            self.changed = None;
+            return false;
+        }
+        match self.changed {
+            // a missing file is being replaced, wait for the new one
+            Some((changed, since)) if changed == stamp && stamp.is_some() => {
+                if now.duration_since(since) < SETTLE_TIME {
+                    return false;
+                }
+                self.loaded = stamp;
+                self.changed = None;
+                true
+            }
+            Some((changed, _)) if changed == stamp => false,
+            // still being written
+            _ => {
+                self.changed = Some((stamp, now));
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reload_after_writing_settles() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let stamp = |len: u64| {
+            Some(Stamp {
+                modified: SystemTime::UNIX_EPOCH + Duration::from_secs(len),
+                len,
+            })
+        };
+        let mut watch = FileWatch {
+            path: PathBuf::new(),
+            last_poll: start,
+            loaded: stamp(10),
+            changed: None,
+        };
+
+        assert!(!watch.update(stamp(10), at(500)));
+        // the file is deleted and written again in several steps
+        assert!(!watch.update(None, at(1000)));
+        assert!(!watch.update(None, at(3000)));
+        assert!(!watch.update(stamp(5), at(3500)));
+        assert!(!watch.update(stamp(20), at(4000)));
+        assert!(!watch.update(stamp(20), at(4500)));
+        assert!(watch.update(stamp(20), at(5000)));
+        // reported only once
+        assert!(!watch.update(stamp(20), at(5500)));
+    }
+}
@@ -0,0 +1,386 @@
+//! Long-term loudness log of the live input, e.g. compliance records of a broadcast feed.
+//! Every interval a line with the momentary, short-term and integrated loudness and the
+//! true and sample peaks of the interval is appended to a CSV or JSON Lines file per day.
+//! The integrated loudness starts again with every file, it is the loudness of the day so far.
+//! The input is metered where it is captured, the files are written on the log's own thread.
+use crate::{
+    audio_capture::{self, AudioDevice, list_input_devs},
+    cli::LogArgs,
+    config::{Config, LogConfig, LogFormat},
+    recorder::UtcTime,
+};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use crossbeam::channel::{Receiver, Sender, unbounded};
+use ebur128::{EbuR128, Mode};
+use eyre::{Result, eyre};
+use ringbuffer::AllocRingBuffer;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+const CSV_HEADER: &str =
+    "time,momentary_lufs,shortterm_lufs,integrated_lufs,true_peak_dbtp,sample_peak_dbfs";
+
+/// The log being written, `None` while not logging.
+pub type SharedLog = Arc<Mutex<Option<LoudnessLog>>>;
+
+pub struct LoudnessLog {
+    meter: EbuR128,
+    channels: u32,
+    /// Frames between two lines
+    interval_frames: u64,
+    /// Frames since the last line
+    frames: u64,
+    /// Highest peaks since the last line, linear
+    true_peak: f64,
+    sample_peak: f64,
+    /// UTC year, month and day the meter measures, it is reset when the day changes
+    day: Option<(u64, u64, u64)>,
+    /// Lines with the date they belong to, written by the writer thread
+    tx: Sender<(String, LogLine)>,
+    writer: JoinHandle<()>,
+    /// Lines written, counted by the writer thread
+    lines: Arc<AtomicUsize>,
+    /// First error, nothing is logged after it
+    error: Arc<OnceLock<String>>,
+}
+
+/// Daily files of a log, owned by its writer thread.
+struct LogFiles {
+    format: LogFormat,
+    dir: PathBuf,
+    /// Date and file of the current day
+    file: Option<(String, File)>,
+}
+
+/// One line of the log, levels are `-inf` when silent.
+struct LogLine {
+    /// ISO 8601, e.g. `2024-05-31T13:05:09Z`
+    time: String,
+    momentary: f64,
+    shortterm: f64,
+    integrated: f64,
+    true_peak: f64,
+    sample_peak: f64,
+}
+
+impl LoudnessLog {
+    pub fn new(config: &LogConfig, sample_rate: u32, channels: u32) -> Result<Self> {
+        // the histogram keeps the memory constant however long the log runs
+        let mode =
+            Mode::M | Mode::S | Mode::I | Mode::TRUE_PEAK | Mode::SAMPLE_PEAK | Mode::HISTOGRAM;
+        let dir = config.directory();
+        fs::create_dir_all(&dir)?;
+        let meter = EbuR128::new(channels, sample_rate, mode)?;
+        let mut files = LogFiles {
+            format: config.format,
+            dir,
+            file: None,
+        };
+        let (tx, rx) = unbounded();
+        let lines = Arc::new(AtomicUsize::new(0));
+        let error = Arc::new(OnceLock::new());
+        let writer = {
+            let (lines, error) = (lines.clone(), error.clone());
+            thread::spawn(move || {
+                if let Err(err) = files.write_lines(&rx, &lines) {
+                    let _ = error.set(err.to_string());
+                }
+            })
+        };
+        Ok(Self {
+            meter,
+            channels,
+            interval_frames: ((config.interval * sample_rate as f64) as u64).max(1),
+            frames: 0,
+            true_peak: 0.,
+            sample_peak: 0.,
+            day: None,
+            tx,
+            writer,
+            lines,
+            error,
+        })
+    }
+
+    /// Measures interleaved samples and hands a line to the writer thread every interval.
+    /// Called from the capture callback, so an error is kept for [`LoudnessLog::error`].
+    pub fn write(&mut self, samples: &[f32]) {
+        if self.error.get().is_some() {
+            return;
+        }
+        if let Err(err) = self.add(samples, SystemTime::now()) {
+            let _ = self.error.set(err.to_string());
+        }
+    }
+
+    fn add(&mut self, samples: &[f32], now: SystemTime) -> Result<()> {
+        let time = UtcTime::new(now);
+        let day = Some((time.year, time.month, time.day));
+        if self.day != day {
+            // the integrated loudness of a file covers only its day
+            if self.day.is_some() {
+                self.meter.reset();
+            }
+            self.day = day;
+        }
+        self.meter.add_frames_f32(samples)?;
+        for channel in 0..self.channels {
+            self.true_peak = self.true_peak.max(self.meter.prev_true_peak(channel)?);
+            self.sample_peak = self.sample_peak.max(self.meter.prev_sample_peak(channel)?);
+        }
+        self.frames += (samples.len() / self.channels.max(1) as usize) as u64;
+        if self.frames < self.interval_frames {
+            return Ok(());
+        }
+        self.frames -= self.interval_frames;
+        let line = LogLine {
+            time: format!(
+                "{}T{:02}:{:02}:{:02}Z",
+                time.date(),
+                time.hour,
+                time.minute,
+                time.second
+            ),
+            momentary: self.meter.loudness_momentary()?,
+            shortterm: self.meter.loudness_shortterm()?,
+            integrated: self.meter.loudness_global()?,
+            true_peak: 20. * self.true_peak.log10(),
+            sample_peak: 20. * self.sample_peak.log10(),
+        };
+        self.true_peak = 0.;
+        self.sample_peak = 0.;
+        // the channel is closed once the writer thread stopped at an error
+        let _ = self.tx.send((time.date(), line));
+        Ok(())
+    }
+
+    /// Waits for the writer thread to write the remaining lines.
+    pub fn stop(self) {
+        drop(self.tx);
+        let _ = self.writer.join();
+    }
+
+    /// Takes the log out of `log` and stops it. The lock is released first, the capture
+    /// must not wait for the disk.
+    pub fn stop_shared(log: &SharedLog) {
+        let old = log.lock().unwrap().take();
+        if let Some(old) = old {
+            old.stop();
+        }
+    }
+
+    pub fn lines(&self) -> usize {
+        self.lines.load(Ordering::Relaxed)
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.get().map(String::as_str)
+    }
+}
+
+impl LogFiles {
+    /// Appends the lines to the file of their day until the log is stopped.
+    fn write_lines(&mut self, rx: &Receiver<(String, LogLine)>, lines: &AtomicUsize) -> Result<()> {
+        for (date, line) in rx {
+            let format = self.format;
+            writeln!(self.file_for(date)?, "{}", line.format(format))?;
+            lines.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Opens the file of `date` when the day changes, a file of the same day is appended to.
+    fn file_for(&mut self, date: String) -> Result<&mut File> {
+        if self.file.as_ref().is_none_or(|(day, _)| *day != date) {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.path_of(&date))?;
+            if self.format == LogFormat::Csv && file.metadata()?.len() == 0 {
+                writeln!(file, "{CSV_HEADER}")?;
+            }
+            self.file = Some((date, file));
+        }
+        Ok(&mut self.file.as_mut().unwrap().1)
+    }
+
+    fn path_of(&self, date: &str) -> PathBuf {
+        self.dir
+            .join(format!("loudness-{date}.{}", self.format.extension()))
+    }
+}
+
+/// Logs an input device without the TUI (`soundscope log`) until the process is stopped.
+pub fn run(args: LogArgs) -> Result<()> {
+    let mut config = Config::load()?.log;
+    config.interval = args.interval.unwrap_or(config.interval);
+    config.format = args.format.unwrap_or(config.format);
+    config.directory = args.directory.or(config.directory);
+
+    let (name, device) = match args.device {
+        Some(index) => {
+            let mut devices = list_input_devs();
+            if devices.is_empty() {
+                return Err(eyre!("No input device found"));
+            }
+            if index >= devices.len() {
+                let names: Vec<String> = devices
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (name, _))| format!("  {}. {name}", i + 1))
+                    .collect();
+                return Err(eyre!(
+                    "Invalid device number: {}, the input devices are:\n{}",
+                    index + 1,
+                    names.join("\n")
+                ));
+            }
+            let (name, device) = devices.swap_remove(index);
+            (name, AudioDevice::new(Some(device)))
+        }
+        None => {
+            let device = AudioDevice::new(None);
+            let name = device.device().name().unwrap_or_default();
+            (name, device)
+        }
+    };
+    let sample_rate = device.config().sample_rate.0;
+    let channels = device.config().channels as u32;
+    let log = LoudnessLog::new(&config, sample_rate, channels)?;
+    eprintln!(
+        "Logging the loudness of {name} to {}, press Ctrl+C to stop",
+        config.directory().display()
+    );
+    let log: SharedLog = Arc::new(Mutex::new(Some(log)));
+    // only the log is needed, the analysis buffer is kept small
+    let buffer = Arc::new(Mutex::new(AllocRingBuffer::new(1024)));
+    let stream = audio_capture::build_input_stream(
+        buffer,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        log.clone(),
+        &device,
+    )?;
+    stream.play()?;
+    loop {
+        thread::sleep(Duration::from_secs(1));
+        if let Some(log) = log.lock().unwrap().as_ref()
+            && let Some(error) = log.error()
+        {
+            return Err(eyre!("Error writing the loudness log: {error}"));
+        }
+    }
+}
+
+impl LogLine {
+    fn format(&self, format: LogFormat) -> String {
+        let values = [
+            self.momentary,
+            self.shortterm,
+            self.integrated,
+            self.true_peak,
+            self.sample_peak,
+        ];
+        // silence is an empty field in CSV and null in JSON
+        let number = |value: f64, empty: &str| {
+            if value.is_finite() {
+                format!("{value:.2}")
+            } else {
+                empty.to_string()
+            }
+        };
+        match format {
+            LogFormat::Csv => {
+                let values: Vec<String> = values.iter().map(|&v| number(v, "")).collect();
+                format!("{},{}", self.time, values.join(","))
+            }
+            LogFormat::Jsonl => {
+                let names = CSV_HEADER.split(',').skip(1);
+                let fields: Vec<String> = names
+                    .zip(values)
+                    .map(|(name, value)| format!("\"{name}\":{}", number(value, "null")))
+                    .collect();
+                format!("{{\"time\":\"{}\",{}}}", self.time, fields.join(","))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_line() {
+        let line = LogLine {
+            time: String::from("2024-05-31T13:05:09Z"),
+            momentary: -23.456,
+            shortterm: -24.,
+            integrated: f64::NEG_INFINITY,
+            true_peak: -1.,
+            sample_peak: -1.5,
+        };
+        assert_eq!(
+            line.format(LogFormat::Csv),
+            "2024-05-31T13:05:09Z,-23.46,-24.00,,-1.00,-1.50"
+        );
+        assert_eq!(
+            line.format(LogFormat::Jsonl),
+            "{\"time\":\"2024-05-31T13:05:09Z\",\"momentary_lufs\":-23.46,\"shortterm_lufs\":-24.00,\
+             \"integrated_lufs\":null,\"true_peak_dbtp\":-1.00,\"sample_peak_dbfs\":-1.50}"
+        );
+    }
+
+    #[test]
+    fn test_daily_files() {
+        let dir = std::env::temp_dir().join(format!(
+            "soundscope-test-loudness-log-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let config = LogConfig {
+            directory: Some(dir.clone()),
+            interval: 0.5,
+            format: LogFormat::Csv,
+        };
+        let mut log = LoudnessLog::new(&config, 8000, 1).unwrap();
+        let day = SystemTime::UNIX_EPOCH + Duration::from_secs(1_717_160_709);
+        let next_day = day + Duration::from_secs(86400);
+        // 1 kHz, DC would be filtered out by the meter
+        let tone = |amplitude: f32| -> Vec<f32> {
+            (0..4000)
+                .map(|i| amplitude * (i as f32 * std::f32::consts::FRAC_PI_4).sin())
+                .collect()
+        };
+        log.add(&tone(0.5), day).unwrap();
+        log.add(&tone(0.5), day).unwrap();
+        // 20 dB quieter, the loud audio of the day before is not part of its loudness
+        log.add(&tone(0.05), next_day).unwrap();
+        log.stop();
+
+        let integrated = |line: &str| line.split(',').nth(3).unwrap().parse::<f64>().unwrap();
+        let text = fs::read_to_string(dir.join("loudness-2024-06-01.csv")).unwrap();
+        let next_day_line = text.lines().nth(1).unwrap();
+        assert!(integrated(next_day_line) < -20., "{next_day_line}");
+
+        let text = fs::read_to_string(dir.join("loudness-2024-05-31.csv")).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert!(lines[1].starts_with("2024-05-31T13:05:09Z,"));
+        assert!(lines[2].ends_with(",-6.02"));
+        assert!(integrated(lines[2]) > -10., "{}", lines[2]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
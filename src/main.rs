@@ -1,9 +1,35 @@
+mod alarms;
 mod analyzer;
 mod audio_capture;
 mod audio_player;
+mod band_solo;
 mod builtin_themes;
+mod chapters;
+mod cli;
+mod config;
+mod cue;
+mod file_info;
+mod file_watch;
+mod loudness_log;
+mod markers;
+mod monitor;
+#[cfg(feature = "opus")]
+mod opus;
+mod playlist;
+mod probe;
+mod raw_pcm;
+mod recorder;
+mod riff;
+mod sample_store;
+mod stdin_input;
+mod stream_input;
 mod tui;
-use crate::audio_player::{AudioFile, AudioPlayer, PlaybackPosition, PlayerCommand};
+mod wavpack;
+use crate::{
+    audio_player::{AudioFile, AudioPlayer, PlaybackPosition, PlayerCommand},
+    cli::Command,
+    config::Config,
+};
 use crossbeam::channel::{bounded, unbounded};
 use eyre::Result;
 use ringbuffer::{AllocRingBuffer, RingBuffer};
@@ -15,19 +41,22 @@ use std::{
 };
 
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
-
-    // Handle help flag
-    if args.len() > 1 && (args[1] == "-h" || args[1] == "--help") {
-        print_help();
-        return Ok(());
-    }
-
-    // Handle version flag
-    if args.len() > 1 && (args[1] == "-v" || args[1] == "--version") {
-        println!("soundscope {}", env!("CARGO_PKG_VERSION"));
-        return Ok(());
-    }
+    let mut args = match cli::parse(env::args().skip(1))? {
+        Command::Help => {
+            cli::print_help();
+            return Ok(());
+        }
+        Command::Version => {
+            println!("soundscope {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Command::Run(args) => args,
+        Command::Log(args) => {
+            #[cfg(target_os = "linux")]
+            suppress_alsa_messages();
+            return loudness_log::run(args);
+        }
+    };
 
     #[cfg(target_os = "linux")]
     suppress_alsa_messages();
@@ -44,21 +73,30 @@ fn main() -> Result<()> {
     let (error_tx, error_rx) = bounded::<String>(1);
 
     // create an audio player
-    let mut player = AudioPlayer::new(playback_position_tx.clone())?;
+    // a broken config is reported by the TUI, the player just falls back to the default device
+    let config = Config::load().unwrap_or_default();
+    let mut player = AudioPlayer::new(
+        playback_position_tx.clone(),
+        config.output_device.as_deref(),
+    )?;
 
     // just a place holder audio_file to initialize app
     let audio_file = AudioFile::new(playback_position_tx);
 
-    let mut startup_file = args.get(1).map(PathBuf::from);
-    if let Some(f) = startup_file {
+    let files = args
+        .files
+        .iter()
+        .map(|f| f.canonicalize())
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    if let Some(f) = args.files.first() {
         let current_working_dir = env::current_dir()?;
-        startup_file = Some(f.canonicalize()?);
         env::set_current_dir(
             f.parent()
                 .filter(|&s| s.to_str().unwrap() != "")
                 .unwrap_or(&current_working_dir),
         )?;
     }
+    args.files = files;
 
     let mut buf = AllocRingBuffer::new(44100usize * 30);
     buf.fill(0.0);
@@ -72,23 +110,12 @@ fn main() -> Result<()> {
             playback_position_rx,
             error_rx,
             latest_captured_samples,
-            startup_file,
+            args,
         )
     });
     player.run(&player_command_rx, &audio_file_tx, &error_tx)
 }
 
-fn print_help() {
-    println!("Usage: soundscope [OPTIONS] [FILE]");
-    println!();
-    println!("Arguments:");
-    println!("  [FILE]  Audio file to open on startup");
-    println!();
-    println!("Options:");
-    println!("  -h, --help     Print help");
-    println!("  -v, --version  Print version");
-}
-
 // The code below suppresses ALSA error messages
 #[cfg(target_os = "linux")]
 #[link(name = "asound")]
@@ -0,0 +1,288 @@
+//! Named markers dropped at the playhead.
+//! Markers are stored in a sidecar file next to the audio file (`track.wav.soundscope.toml`)
+//! and can be imported from WAV/BWF `cue ` and `LIST`/`adtl` chunks.
+use crate::riff;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Extension appended to the audio file name to get the sidecar file name.
+const SIDECAR_EXTENSION: &str = "soundscope.toml";
+
+/// A named position in the audio file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Marker {
+    pub name: String,
+    /// Position in seconds from the start of the file.
+    pub time: f64,
+}
+
+/// Markers of the currently loaded file, sorted by time.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct Markers {
+    #[serde(default)]
+    markers: Vec<Marker>,
+}
+
+impl Markers {
+    /// Returns the sidecar path for an audio file, e.g. `track.wav` -> `track.wav.soundscope.toml`.
+    pub fn sidecar_path(audio_path: &Path) -> PathBuf {
+        let mut name = audio_path.file_name().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(SIDECAR_EXTENSION);
+        audio_path.with_file_name(name)
+    }
+
+    /// Loads markers for an audio file. The sidecar file takes precedence,
+    /// otherwise cue points embedded in WAV/BWF files are imported.
+    pub fn load_for(audio_path: &Path) -> Result<Self> {
+        let sidecar = Self::sidecar_path(audio_path);
+        if sidecar.exists() {
+            let contents = fs::read_to_string(sidecar)?;
+            let mut markers: Markers = toml::from_str(&contents)?;
+            markers.sort();
+            return Ok(markers);
+        }
+
+        let is_riff = audio_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| matches!(e.to_lowercase().as_str(), "wav" | "wave" | "bwf"));
+        if is_riff {
+            return Self::import_riff_cues(audio_path);
+        }
+
+        Ok(Self::default())
+    }
+
+    /// Writes markers to the sidecar file of `audio_path`.
+    pub fn save_for(&self, audio_path: &Path) -> Result<()> {
+        let sidecar = Self::sidecar_path(audio_path);
+        // do not litter directories with empty sidecar files
+        if self.markers.is_empty() && !sidecar.exists() {
+            return Ok(());
+        }
+        fs::write(sidecar, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Reads cue points and their labels from `cue ` and `LIST`/`adtl` chunks.
+    pub fn import_riff_cues(audio_path: &Path) -> Result<Self> {
+        let chunks = riff::read_chunks(audio_path, &[b"fmt ", b"cue ", b"LIST"])?;
+
+        let sample_rate = chunks
+            .iter()
+            .find(|c| &c.id == b"fmt ")
+            .and_then(|c| riff::read_u32(&c.data, 4))
+            .filter(|&sr| sr != 0)
+            .unwrap_or(44100);
+
+        // cue id -> label
+        let mut labels = HashMap::new();
+        for list in chunks.iter().filter(|c| &c.id == b"LIST") {
+            if list.data.get(0..4) != Some(b"adtl") {
+                continue;
+            }
+            let mut offset = 4;
+            while offset + 8 <= list.data.len() {
+                let id = &list.data[offset..offset + 4];
+                let size = riff::read_u32(&list.data, offset + 4).unwrap_or(0) as usize;
+                let start = offset + 8;
+                let end = (start + size).min(list.data.len());
+                if (id == b"labl" || id == b"note") && end >= start + 4 {
+                    let cue_id = riff::read_u32(&list.data, start).unwrap();
+                    let text = riff::read_text(&list.data[start + 4..end]);
+                    // labels take precedence over notes
+                    if id == b"labl" || !labels.contains_key(&cue_id) {
+                        labels.insert(cue_id, text);
+                    }
+                }
+                offset = start + size + size % 2;
+            }
+        }
+
+        let mut markers = Vec::new();
+        if let Some(cue) = chunks.iter().find(|c| &c.id == b"cue ") {
+            let count = riff::read_u32(&cue.data, 0).unwrap_or(0) as usize;
+            for i in 0..count {
+                let point = 4 + i * 24;
+                let (Some(cue_id), Some(sample_offset)) = (
+                    riff::read_u32(&cue.data, point),
+                    riff::read_u32(&cue.data, point + 20),
+                ) else {
+                    break;
+                };
+                let name = labels
+                    .get(&cue_id)
+                    .filter(|l| !l.is_empty())
+                    .cloned()
+                    .unwrap_or_else(|| format!("Cue {cue_id}"));
+                markers.push(Marker {
+                    name,
+                    time: sample_offset as f64 / sample_rate as f64,
+                });
+            }
+        }
+
+        let mut markers = Markers { markers };
+        markers.sort();
+        Ok(markers)
+    }
+
+    /// Adds a marker keeping the list sorted. Returns its index.
+    pub fn add(&mut self, name: String, time: f64) -> usize {
+        let index = self.markers.partition_point(|m| m.time <= time);
+        self.markers.insert(index, Marker { name, time });
+        index
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.markers.len() {
+            self.markers.remove(index);
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Marker> {
+        self.markers.get(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Marker> {
+        self.markers.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.markers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.markers.is_empty()
+    }
+
+    /// Returns the first marker strictly after `time`.
+    pub fn next_after(&self, time: f64) -> Option<&Marker> {
+        self.markers.iter().find(|m| m.time > time)
+    }
+
+    /// Returns the last marker strictly before `time`.
+    pub fn prev_before(&self, time: f64) -> Option<&Marker> {
+        self.markers.iter().rev().find(|m| m.time < time)
+    }
+
+    fn sort(&mut self) {
+        self.markers.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_sidecar_path() {
+        let path = Markers::sidecar_path(Path::new("/tmp/mix/track.wav"));
+        assert_eq!(path, PathBuf::from("/tmp/mix/track.wav.soundscope.toml"));
+    }
+
+    #[test]
+    fn test_add_keeps_markers_sorted() {
+        let mut markers = Markers::default();
+        markers.add("b".to_string(), 10.);
+        markers.add("a".to_string(), 5.);
+        markers.add("c".to_string(), 20.);
+        let names: Vec<&str> = markers.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+
+        assert_eq!(markers.next_after(5.).unwrap().name, "b");
+        assert_eq!(markers.prev_before(10.).unwrap().name, "a");
+        assert!(markers.next_after(20.).is_none());
+        assert!(markers.prev_before(5.).is_none());
+    }
+
+    #[test]
+    fn test_sidecar_roundtrip() {
+        let dir = std::env::temp_dir().join(format!(
+            "soundscope_markers_roundtrip_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let audio = dir.join("song.flac");
+
+        let mut markers = Markers::default();
+        markers.add("Vocal too loud".to_string(), 83.5);
+        markers.add("Click".to_string(), 12.25);
+        markers.save_for(&audio).unwrap();
+
+        let loaded = Markers::load_for(&audio).unwrap();
+        assert_eq!(loaded, markers);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_riff_cues() {
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        fmt.extend_from_slice(&2u16.to_le_bytes()); // channels
+        fmt.extend_from_slice(&48000u32.to_le_bytes()); // sample rate
+        fmt.extend_from_slice(&(48000u32 * 4).to_le_bytes()); // byte rate
+        fmt.extend_from_slice(&4u16.to_le_bytes()); // block align
+        fmt.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+
+        let mut cue = 2u32.to_le_bytes().to_vec();
+        for (id, offset) in [(1u32, 96000u32), (2, 24000)] {
+            cue.extend_from_slice(&id.to_le_bytes());
+            cue.extend_from_slice(&offset.to_le_bytes());
+            cue.extend_from_slice(b"data");
+            cue.extend_from_slice(&0u32.to_le_bytes());
+            cue.extend_from_slice(&0u32.to_le_bytes());
+            cue.extend_from_slice(&offset.to_le_bytes());
+        }
+
+        let mut adtl = b"adtl".to_vec();
+        let mut labl = 1u32.to_le_bytes().to_vec();
+        labl.extend_from_slice(b"Chorus\0");
+        adtl.extend(chunk(b"labl", &labl));
+
+        let mut body = b"WAVE".to_vec();
+        body.extend(chunk(b"fmt ", &fmt));
+        body.extend(chunk(b"data", &[0u8; 16]));
+        body.extend(chunk(b"cue ", &cue));
+        body.extend(chunk(b"LIST", &adtl));
+        let wav = chunk(b"RIFF", &body);
+
+        let dir =
+            std::env::temp_dir().join(format!("soundscope_markers_cues_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cues.wav");
+        fs::write(&path, &wav).unwrap();
+
+        let markers = Markers::load_for(&path).unwrap();
+        assert_eq!(markers.len(), 2);
+        assert_eq!(markers.get(0).unwrap().name, "Cue 2");
+        assert_eq!(markers.get(0).unwrap().time, 0.5);
+        assert_eq!(markers.get(1).unwrap().name, "Chorus");
+        assert_eq!(markers.get(1).unwrap().time, 2.);
+
+        // a truncated chunk claiming almost 4 GiB is not allocated, the cues before it are read
+        let mut truncated = wav;
+        truncated.extend_from_slice(b"LIST");
+        truncated.extend_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+        truncated.extend_from_slice(b"adtl");
+        fs::write(&path, truncated).unwrap();
+        assert_eq!(Markers::load_for(&path).unwrap().len(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
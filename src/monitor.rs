@@ -0,0 +1,190 @@
+//! Monitoring matrix applied to the playback only, the analysis always uses the original samples.
+use rodio::{ChannelCount, Source, source};
+use std::{
+    fmt::Display,
+    sync::{
+        Arc,
+        atomic::{AtomicU8, Ordering},
+    },
+    time::Duration,
+};
+
+/// What is sent to the left and right outputs.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum MonitorMode {
+    #[default]
+    Stereo,
+    /// Left channel on both outputs
+    Left,
+    /// Right channel on both outputs
+    Right,
+    /// Equal-power sum of both channels, so a mono-compatible mix keeps its loudness
+    Mono,
+    /// (L + R) / 2, the same mid channel the analysis uses
+    Mid,
+    /// (L - R) / 2, makes phase problems easy to hear
+    Side,
+    /// Right channel with inverted polarity
+    InvertRight,
+}
+
+impl MonitorMode {
+    const ALL: [MonitorMode; 7] = [
+        MonitorMode::Stereo,
+        MonitorMode::Left,
+        MonitorMode::Right,
+        MonitorMode::Mono,
+        MonitorMode::Mid,
+        MonitorMode::Side,
+        MonitorMode::InvertRight,
+    ];
+
+    /// Returns the next mode, wrapping around to [`MonitorMode::Stereo`].
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    fn from_u8(value: u8) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or_default()
+    }
+
+    /// Maps a stereo frame to the output frame.
+    pub fn apply(self, l: f32, r: f32) -> (f32, f32) {
+        match self {
+            MonitorMode::Stereo => (l, r),
+            MonitorMode::Left => (l, l),
+            MonitorMode::Right => (r, r),
+            MonitorMode::Mono => {
+                let m = (l + r) * std::f32::consts::FRAC_1_SQRT_2;
+                (m, m)
+            }
+            MonitorMode::Mid => {
+                let m = (l + r) / 2.;
+                (m, m)
+            }
+            MonitorMode::Side => {
+                let s = (l - r) / 2.;
+                (s, s)
+            }
+            MonitorMode::InvertRight => (l, -r),
+        }
+    }
+}
+
+impl Display for MonitorMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MonitorMode::Stereo => write!(f, "Stereo"),
+            MonitorMode::Left => write!(f, "Left"),
+            MonitorMode::Right => write!(f, "Right"),
+            MonitorMode::Mono => write!(f, "Mono"),
+            MonitorMode::Mid => write!(f, "Mid"),
+            MonitorMode::Side => write!(f, "Side"),
+            MonitorMode::InvertRight => write!(f, "R inverted"),
+        }
+    }
+}
+
+/// [`MonitorMode`] shared between the player and the sources it plays,
+/// so the mode can change without rebuilding the sink.
+#[derive(Clone, Default)]
+pub struct SharedMonitorMode(Arc<AtomicU8>);
+
+impl SharedMonitorMode {
+    pub fn get(&self) -> MonitorMode {
+        MonitorMode::from_u8(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, mode: MonitorMode) {
+        self.0.store(mode as u8, Ordering::Relaxed);
+    }
+}
+
+/// [`Source`] applying the [`MonitorMode`] to stereo sources. Other sources are passed through.
+pub struct Monitor<S> {
+    inner: S,
+    mode: SharedMonitorMode,
+    /// The current output frame, the right sample is returned after the left one
+    right: Option<f32>,
+}
+
+impl<S: Source> Monitor<S> {
+    pub fn new(inner: S, mode: SharedMonitorMode) -> Self {
+        Self {
+            inner,
+            mode,
+            right: None,
+        }
+    }
+}
+
+impl<S: Source> Iterator for Monitor<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.inner.channels() != 2 {
+            return self.inner.next();
+        }
+        if let Some(r) = self.right.take() {
+            return Some(r);
+        }
+        let l = self.inner.next()?;
+        let Some(r) = self.inner.next() else {
+            return Some(l);
+        };
+        let (l, r) = self.mode.get().apply(l, r);
+        self.right = Some(r);
+        Some(l)
+    }
+}
+
+impl<S: Source> Source for Monitor<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.inner.current_span_len()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), source::SeekError> {
+        // the inner source is always at the start of a frame, drop the pending right sample
+        // so that the channels stay aligned after the seek
+        self.right = None;
+        self.inner.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(MonitorMode::Stereo.apply(0.5, -0.25), (0.5, -0.25));
+        assert_eq!(MonitorMode::Left.apply(0.5, -0.25), (0.5, 0.5));
+        assert_eq!(MonitorMode::Right.apply(0.5, -0.25), (-0.25, -0.25));
+        assert_eq!(MonitorMode::Mid.apply(0.5, -0.25), (0.125, 0.125));
+        assert_eq!(MonitorMode::Side.apply(0.5, -0.25), (0.375, 0.375));
+        assert_eq!(MonitorMode::InvertRight.apply(0.5, -0.25), (0.5, 0.25));
+        // a centered mono signal cancels out in the side channel
+        assert_eq!(MonitorMode::Side.apply(0.5, 0.5), (0., 0.));
+    }
+
+    #[test]
+    fn test_next_wraps_around() {
+        let mut mode = MonitorMode::Stereo;
+        for _ in 0..MonitorMode::ALL.len() {
+            mode = mode.next();
+        }
+        assert_eq!(mode, MonitorMode::Stereo);
+    }
+}
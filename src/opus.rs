@@ -0,0 +1,373 @@
+//! Opus decoding with libopus, built with the `opus` feature. symphonia reads Opus tracks
+//! from Ogg and WebM files but has no decoder for them.
+//! The pre-skip and the output gain of the `OpusHead` header are applied here, files are read
+//! without gapless trimming so the first samples are the encoder's priming samples.
+use audiopus_sys as ffi;
+use std::ptr::NonNull;
+use symphonia::core::{
+    audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec},
+    codecs::{
+        CODEC_TYPE_OPUS, CodecDescriptor, CodecParameters, Decoder, DecoderOptions, FinalizeResult,
+    },
+    errors::{Result, decode_error, unsupported_error},
+    formats::Packet,
+    support_codec,
+};
+
+/// libopus decodes at 48 kHz whatever the rate of the original audio
+const SAMPLE_RATE: u32 = 48000;
+/// Frames of the longest packet, 120 ms
+const MAX_PACKET_FRAMES: usize = 5760;
+
+/// Planes of the channels in Vorbis order, which is used by mapping family 1, by channel count
+const VORBIS_ORDER: [&[usize]; 8] = [
+    &[0],
+    &[0, 1],
+    &[0, 2, 1],
+    &[0, 1, 2, 3],
+    &[0, 2, 1, 3, 4],
+    &[0, 2, 1, 5, 3, 4],
+    &[0, 2, 1, 6, 5, 3, 4],
+    &[0, 2, 1, 7, 5, 6, 3, 4],
+];
+
+/// The identification header, the first Ogg packet or the codec private data in WebM.
+#[derive(Debug, PartialEq)]
+struct OpusHead {
+    channels: usize,
+    /// Frames to drop at the start
+    pre_skip: usize,
+    /// Output gain as a linear factor
+    gain: f32,
+    family: u8,
+    streams: u8,
+    coupled_streams: u8,
+    /// Decoded channel of every output channel
+    mapping: Vec<u8>,
+}
+
+impl OpusHead {
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 19 || &data[..8] != b"OpusHead" {
+            return decode_error("opus: missing identification header");
+        }
+        let channels = data[9] as usize;
+        // the gain is in dB with 8 fractional bits
+        let gain = i16::from_le_bytes([data[16], data[17]]);
+        let family = data[18];
+        let (streams, coupled_streams, mapping) = match family {
+            0 if (1..=2).contains(&channels) => (1, channels as u8 - 1, vec![0, 1]),
+            0 => return decode_error("opus: invalid channel count"),
+            _ if data.len() >= 21 + channels && channels > 0 => {
+                (data[19], data[20], data[21..21 + channels].to_vec())
+            }
+            _ => return decode_error("opus: invalid channel mapping"),
+        };
+        Ok(Self {
+            channels,
+            pre_skip: u16::from_le_bytes([data[10], data[11]]) as usize,
+            gain: 10f32.powf(gain as f32 / (20. * 256.)),
+            family,
+            streams,
+            coupled_streams,
+            mapping: mapping[..channels].to_vec(),
+        })
+    }
+
+    /// Speakers of the channels, the first `n` if the mapping family doesn't define them.
+    fn speakers(&self) -> Option<Channels> {
+        let speakers = match (self.family, self.channels) {
+            (0 | 1, 1) => Channels::FRONT_LEFT,
+            (0 | 1, 2) => Channels::FRONT_LEFT | Channels::FRONT_RIGHT,
+            (1, 3) => Channels::FRONT_LEFT | Channels::FRONT_CENTRE | Channels::FRONT_RIGHT,
+            (1, 4) => {
+                Channels::FRONT_LEFT
+                    | Channels::FRONT_RIGHT
+                    | Channels::REAR_LEFT
+                    | Channels::REAR_RIGHT
+            }
+            (1, 5) => {
+                Channels::FRONT_LEFT
+                    | Channels::FRONT_CENTRE
+                    | Channels::FRONT_RIGHT
+                    | Channels::REAR_LEFT
+                    | Channels::REAR_RIGHT
+            }
+            (1, 6) => {
+                Channels::FRONT_LEFT
+                    | Channels::FRONT_CENTRE
+                    | Channels::FRONT_RIGHT
+                    | Channels::REAR_LEFT
+                    | Channels::REAR_RIGHT
+                    | Channels::LFE1
+            }
+            (1, 7) => {
+                Channels::FRONT_LEFT
+                    | Channels::FRONT_CENTRE
+                    | Channels::FRONT_RIGHT
+                    | Channels::SIDE_LEFT
+                    | Channels::SIDE_RIGHT
+                    | Channels::REAR_CENTRE
+                    | Channels::LFE1
+            }
+            (1, 8) => {
+                Channels::FRONT_LEFT
+                    | Channels::FRONT_CENTRE
+                    | Channels::FRONT_RIGHT
+                    | Channels::SIDE_LEFT
+                    | Channels::SIDE_RIGHT
+                    | Channels::REAR_LEFT
+                    | Channels::REAR_RIGHT
+                    | Channels::LFE1
+            }
+            (_, n) if n < u32::BITS as usize => Channels::from_bits((1 << n) - 1)?,
+            _ => return None,
+        };
+        Some(speakers)
+    }
+
+    /// Channel of the decoded samples that goes to every plane of the audio buffer.
+    fn planes(&self) -> Vec<usize> {
+        match self.family {
+            1 if self.channels <= 8 => VORBIS_ORDER[self.channels - 1].to_vec(),
+            _ => (0..self.channels).collect(),
+        }
+    }
+}
+
+/// Decodes Opus packets with libopus' multistream decoder, which decodes every mapping family.
+pub struct OpusDecoder {
+    params: CodecParameters,
+    decoder: NonNull<ffi::OpusMSDecoder>,
+    head: OpusHead,
+    planes: Vec<usize>,
+    /// Interleaved samples decoded by libopus
+    pcm: Vec<f32>,
+    buf: AudioBuffer<f32>,
+    /// Frames of the pre-skip still to drop
+    skip: usize,
+}
+
+// SAFETY: `OpusDecoder` owns the libopus decoder, the pointer is never copied out of it
+// and libopus keeps no global or thread-local state, so moving it to another thread is
+// fine. Only `reset`, `decode` and `drop` pass the pointer to libopus and all of them
+// take `&mut self`, so the borrow checker rules out two threads using it at once.
+// The `&self` methods never touch the pointer, sharing `&OpusDecoder` is fine as well.
+unsafe impl Send for OpusDecoder {}
+// SAFETY: see `Send` above, no `&self` method dereferences the pointer
+unsafe impl Sync for OpusDecoder {}
+
+impl Decoder for OpusDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        let Some(extra_data) = params.extra_data.as_deref() else {
+            return decode_error("opus: missing identification header");
+        };
+        let head = OpusHead::parse(extra_data)?;
+        let Some(speakers) = head.speakers() else {
+            return unsupported_error("opus: unsupported channel count");
+        };
+        let mut error = 0;
+        // SAFETY: the mapping has an entry per channel, libopus copies it
+        let decoder = unsafe {
+            ffi::opus_multistream_decoder_create(
+                SAMPLE_RATE as i32,
+                head.channels as i32,
+                head.streams as i32,
+                head.coupled_streams as i32,
+                head.mapping.as_ptr(),
+                &mut error,
+            )
+        };
+        let Some(decoder) = NonNull::new(decoder).filter(|_| error == ffi::OPUS_OK) else {
+            return unsupported_error("opus: unsupported channel mapping");
+        };
+        Ok(Self {
+            params: params.clone(),
+            decoder,
+            planes: head.planes(),
+            pcm: vec![0.; MAX_PACKET_FRAMES * head.channels],
+            buf: AudioBuffer::new(
+                MAX_PACKET_FRAMES as u64,
+                SignalSpec::new(SAMPLE_RATE, speakers),
+            ),
+            skip: head.pre_skip,
+            head,
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(CODEC_TYPE_OPUS, "opus", "Opus")]
+    }
+
+    fn reset(&mut self) {
+        // SAFETY: the decoder is valid until dropped
+        unsafe {
+            ffi::opus_multistream_decoder_ctl(self.decoder.as_ptr(), ffi::OPUS_RESET_STATE);
+        }
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        self.buf.clear();
+        let data = packet.buf();
+        // SAFETY: the output holds `MAX_PACKET_FRAMES` frames of every channel
+        let frames = unsafe {
+            ffi::opus_multistream_decode_float(
+                self.decoder.as_ptr(),
+                data.as_ptr(),
+                data.len() as i32,
+                self.pcm.as_mut_ptr(),
+                MAX_PACKET_FRAMES as i32,
+                0,
+            )
+        };
+        if frames < 0 {
+            return decode_error("opus: invalid packet");
+        }
+        let frames = frames as usize;
+        let skip = self.skip.min(frames);
+        self.skip -= skip;
+        self.buf.render_reserved(Some(frames - skip));
+        let channels = self.head.channels;
+        let samples = &self.pcm[skip * channels..frames * channels];
+        for (plane, &channel) in self.planes.iter().enumerate() {
+            let decoded = samples[channel..].iter().step_by(channels);
+            for (out, sample) in self.buf.chan_mut(plane).iter_mut().zip(decoded) {
+                *out = sample * self.head.gain;
+            }
+        }
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buf.as_audio_buffer_ref()
+    }
+}
+
+impl Drop for OpusDecoder {
+    fn drop(&mut self) {
+        // SAFETY: created by `opus_multistream_decoder_create` and not used afterwards
+        unsafe { ffi::opus_multistream_decoder_destroy(self.decoder.as_ptr()) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symphonia::core::audio::SampleBuffer;
+
+    fn opus_head(pre_skip: u16, gain_db: f32) -> Vec<u8> {
+        let mut head = b"OpusHead".to_vec();
+        head.extend([1, 2]);
+        head.extend(pre_skip.to_le_bytes());
+        head.extend(SAMPLE_RATE.to_le_bytes());
+        head.extend(((gain_db * 256.) as i16).to_le_bytes());
+        head.push(0);
+        head
+    }
+
+    #[test]
+    fn test_opus_head() {
+        let head = OpusHead::parse(&opus_head(312, 6.)).unwrap();
+        assert_eq!(head.channels, 2);
+        assert_eq!(head.pre_skip, 312);
+        assert!((head.gain - 1.9953).abs() < 1e-4);
+        assert_eq!((head.streams, head.coupled_streams), (1, 1));
+        assert_eq!(head.mapping, [0, 1]);
+
+        // 5.1 in Vorbis order: L, C, R, rear L, rear R, LFE
+        let mut surround = opus_head(0, 0.);
+        surround[9] = 6;
+        surround[18] = 1;
+        surround.extend([4, 2, 0, 4, 1, 2, 3, 5]);
+        let head = OpusHead::parse(&surround).unwrap();
+        assert_eq!((head.streams, head.coupled_streams), (4, 2));
+        assert_eq!(head.speakers().unwrap().count(), 6);
+        assert_eq!(head.planes(), [0, 2, 1, 5, 3, 4]);
+
+        assert!(OpusHead::parse(b"OpusTags").is_err());
+        assert!(OpusHead::parse(&surround[..24]).is_err());
+    }
+
+    /// Encodes 0.5 s of a stereo tone and decodes it with and without an output gain.
+    #[test]
+    fn test_decode() {
+        const FRAMES: usize = 960;
+        let mut error = 0;
+        // SAFETY: the encoder is destroyed after the last packet
+        let encoder = unsafe {
+            ffi::opus_encoder_create(
+                SAMPLE_RATE as i32,
+                2,
+                ffi::OPUS_APPLICATION_AUDIO,
+                &mut error,
+            )
+        };
+        assert_eq!(error, ffi::OPUS_OK);
+        let tone: Vec<f32> = (0..FRAMES * 25)
+            .flat_map(|i| {
+                let s = 0.5 * (i as f32 * 1000. * std::f32::consts::TAU / 48000.).sin();
+                [s, s]
+            })
+            .collect();
+        let packets: Vec<Vec<u8>> = tone
+            .chunks(FRAMES * 2)
+            .map(|frame| {
+                let mut packet = vec![0; 4000];
+                // SAFETY: the frame holds `FRAMES` stereo frames, the packet 4000 bytes
+                let len = unsafe {
+                    ffi::opus_encode_float(
+                        encoder,
+                        frame.as_ptr(),
+                        FRAMES as i32,
+                        packet.as_mut_ptr(),
+                        packet.len() as i32,
+                    )
+                };
+                assert!(len > 0);
+                packet.truncate(len as usize);
+                packet
+            })
+            .collect();
+        unsafe { ffi::opus_encoder_destroy(encoder) };
+
+        let decode = |head: Vec<u8>| {
+            let mut params = CodecParameters::new();
+            params
+                .for_codec(CODEC_TYPE_OPUS)
+                .with_extra_data(head.into());
+            let mut decoder = OpusDecoder::try_new(&params, &DecoderOptions::default()).unwrap();
+            let mut samples = Vec::new();
+            for (i, packet) in packets.iter().enumerate() {
+                let packet = Packet::new_from_slice(0, (i * FRAMES) as u64, FRAMES as u64, packet);
+                let decoded = decoder.decode(&packet).unwrap();
+                let mut buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+                buf.copy_interleaved_ref(decoded);
+                samples.extend_from_slice(buf.samples());
+            }
+            samples
+        };
+        let plain = decode(opus_head(312, 0.));
+        assert_eq!(plain.len(), tone.len() - 312 * 2);
+        // the tone is decoded at about its level, the peak is within 1 dB
+        let peak = plain.iter().fold(0f32, |peak, s| peak.max(s.abs()));
+        assert!((peak / 0.5).log10().abs() < 0.05, "{peak}");
+
+        let quieter = decode(opus_head(312, -6.));
+        let factor = 10f32.powf(-6. / 20.);
+        assert_eq!(quieter.len(), plain.len());
+        assert!(
+            quieter
+                .iter()
+                .zip(&plain)
+                .all(|(quieter, plain)| (quieter - plain * factor).abs() < 1e-6)
+        );
+    }
+}
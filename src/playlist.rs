@@ -0,0 +1,176 @@
+//! Playlist (queue) of audio files with M3U/M3U8 import and export.
+use eyre::Result;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Extensions of playlist files
+pub const PLAYLIST_FORMATS: [&str; 2] = ["m3u", "m3u8"];
+
+#[derive(Default)]
+pub struct Playlist {
+    tracks: Vec<PathBuf>,
+    /// Index of the track loaded into the player
+    current: Option<usize>,
+    /// Load and play the next track when the current one ends
+    pub auto_advance: bool,
+}
+
+impl Playlist {
+    /// Returns `true` if `path` is an M3U/M3U8 playlist.
+    pub fn is_playlist_file(path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| PLAYLIST_FORMATS.contains(&e.to_lowercase().as_str()))
+    }
+
+    /// Replaces the tracks of the playlist. Playlist files among `paths` are expanded.
+    pub fn set_tracks(&mut self, paths: Vec<PathBuf>) -> Result<()> {
+        self.tracks.clear();
+        self.current = None;
+        self.append(paths)
+    }
+
+    /// Appends tracks to the end of the playlist. Playlist files among `paths` are expanded.
+    pub fn append(&mut self, paths: Vec<PathBuf>) -> Result<()> {
+        for path in paths {
+            if Self::is_playlist_file(&path) {
+                self.tracks.extend(Self::read_m3u(&path)?);
+            } else {
+                self.tracks.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads paths listed in an M3U/M3U8 file. Relative paths are resolved against the playlist directory.
+    pub fn read_m3u(path: &Path) -> Result<Vec<PathBuf>> {
+        let contents = fs::read_to_string(path)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        let tracks = contents
+            .lines()
+            .map(|line| line.trim().trim_start_matches('\u{feff}'))
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let line = line.strip_prefix("file://").unwrap_or(line);
+                base.join(line)
+            })
+            .collect();
+        Ok(tracks)
+    }
+
+    /// Writes the playlist to an extended M3U file.
+    /// Paths inside the playlist directory are written relative to it.
+    pub fn write_m3u(&self, path: &Path) -> Result<()> {
+        let base = path.parent().unwrap_or(Path::new(""));
+        let mut contents = String::from("#EXTM3U\n");
+        for track in &self.tracks {
+            let title = track.file_stem().unwrap_or_default().to_string_lossy();
+            let track = track.strip_prefix(base).unwrap_or(track);
+            contents.push_str(&format!("#EXTINF:-1,{title}\n{}\n", track.display()));
+        }
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Marks the track with the given index as current and returns its path.
+    pub fn select(&mut self, index: usize) -> Option<&PathBuf> {
+        let track = self.tracks.get(index)?;
+        self.current = Some(index);
+        Some(track)
+    }
+
+    /// Moves to the next track and returns its path.
+    pub fn next(&mut self) -> Option<&PathBuf> {
+        let index = self.current.map_or(0, |i| i + 1);
+        self.select(index)
+    }
+
+    /// Moves to the previous track and returns its path.
+    pub fn prev(&mut self) -> Option<&PathBuf> {
+        let index = self.current?.checked_sub(1)?;
+        self.select(index)
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.tracks.len() {
+            return;
+        }
+        self.tracks.remove(index);
+        self.current = match self.current {
+            Some(c) if c == index => None,
+            Some(c) if c > index => Some(c - 1),
+            c => c,
+        };
+    }
+
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub fn tracks(&self) -> &[PathBuf] {
+        &self.tracks
+    }
+
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_and_prev() {
+        let mut playlist = Playlist::default();
+        playlist
+            .set_tracks(vec!["a.wav".into(), "b.wav".into(), "c.wav".into()])
+            .unwrap();
+
+        assert!(playlist.prev().is_none());
+        assert_eq!(playlist.next().unwrap(), Path::new("a.wav"));
+        assert_eq!(playlist.next().unwrap(), Path::new("b.wav"));
+        assert_eq!(playlist.next().unwrap(), Path::new("c.wav"));
+        assert!(playlist.next().is_none());
+        // the current track does not change at the end of the playlist
+        assert_eq!(playlist.current(), Some(2));
+        assert_eq!(playlist.prev().unwrap(), Path::new("b.wav"));
+
+        playlist.remove(0);
+        assert_eq!(playlist.current(), Some(0));
+        playlist.remove(0);
+        assert_eq!(playlist.current(), None);
+    }
+
+    #[test]
+    fn test_m3u_roundtrip() {
+        let dir = std::env::temp_dir().join(format!(
+            "soundscope_playlist_roundtrip_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let m3u = dir.join("album.m3u8");
+
+        let mut playlist = Playlist::default();
+        playlist
+            .set_tracks(vec![
+                dir.join("01 Intro.flac"),
+                PathBuf::from("/music/02.mp3"),
+            ])
+            .unwrap();
+        playlist.write_m3u(&m3u).unwrap();
+
+        let contents = fs::read_to_string(&m3u).unwrap();
+        assert!(contents.starts_with("#EXTM3U\n#EXTINF:-1,01 Intro\n01 Intro.flac\n"));
+
+        let tracks = Playlist::read_m3u(&m3u).unwrap();
+        assert_eq!(tracks, playlist.tracks());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
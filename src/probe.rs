@@ -0,0 +1,278 @@
+//! Hints for symphonia's probe: the real extension of the file and the format sniffed from its
+//! first bytes. The sniffed format is also used to explain why a file could not be opened.
+use crate::wavpack::{WavPackDecoder, WavPackReader};
+use eyre::{Report, Result, eyre};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    sync::LazyLock,
+};
+use symphonia::core::{
+    codecs::{self, CodecParameters, CodecRegistry, CodecType, Decoder, DecoderOptions},
+    probe::{Hint, Probe},
+};
+
+/// Number of bytes read to sniff the format
+const HEADER_LEN: u64 = 64;
+
+/// symphonia's decoders and the built-in ones
+static CODECS: LazyLock<CodecRegistry> = LazyLock::new(|| {
+    let mut registry = CodecRegistry::new();
+    symphonia::default::register_enabled_codecs(&mut registry);
+    registry.register_all::<WavPackDecoder>();
+    #[cfg(feature = "opus")]
+    registry.register_all::<crate::opus::OpusDecoder>();
+    registry
+});
+
+/// symphonia's container readers and the built-in ones
+static FORMATS: LazyLock<Probe> = LazyLock::new(|| {
+    let mut probe = Probe::default();
+    symphonia::default::register_enabled_formats(&mut probe);
+    probe.register_all::<WavPackReader>();
+    probe
+});
+
+/// Probe of the formats that can be read, to be used instead of `symphonia::default::get_probe`.
+pub fn formats() -> &'static Probe {
+    &FORMATS
+}
+
+/// Format recognized from the first bytes of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sniffed {
+    pub mime: &'static str,
+    /// Name shown in the file info popup and in errors
+    pub name: &'static str,
+    /// Whether there is a reader for this format
+    pub supported: bool,
+}
+
+impl Sniffed {
+    const fn new(mime: &'static str, name: &'static str, supported: bool) -> Self {
+        Self {
+            mime,
+            name,
+            supported,
+        }
+    }
+}
+
+/// Recognizes a format from its magic bytes.
+pub fn sniff(header: &[u8]) -> Option<Sniffed> {
+    let at = |offset: usize, magic: &[u8]| header.get(offset..offset + magic.len()) == Some(magic);
+    let sniffed = match header {
+        _ if at(0, b"RIFF") && at(8, b"WAVE") => Sniffed::new("audio/wav", "WAV", true),
+        _ if at(0, b"RF64") && at(8, b"WAVE") => Sniffed::new("audio/wav", "RF64", false),
+        _ if at(0, b"FORM") && (at(8, b"AIFF") || at(8, b"AIFC")) => {
+            Sniffed::new("audio/aiff", "AIFF", true)
+        }
+        _ if at(0, b"fLaC") => Sniffed::new("audio/flac", "FLAC", true),
+        // the first page of an Ogg stream holds the codec header
+        _ if at(0, b"OggS") && at(28, b"OpusHead") => Sniffed::new("audio/ogg", "Ogg Opus", true),
+        _ if at(0, b"OggS") => Sniffed::new("audio/ogg", "Ogg", true),
+        _ if at(0, b"caff") => Sniffed::new("audio/x-caf", "CAF", true),
+        _ if at(4, b"ftyp") => Sniffed::new("audio/mp4", "MP4", true),
+        // WebM is Matroska with the `webm` doc type in the EBML header
+        _ if at(0, &[0x1A, 0x45, 0xDF, 0xA3]) && header.windows(4).any(|w| w == b"webm") => {
+            Sniffed::new("audio/webm", "WebM", true)
+        }
+        _ if at(0, &[0x1A, 0x45, 0xDF, 0xA3]) => Sniffed::new("audio/x-matroska", "Matroska", true),
+        _ if at(0, b"wvpk") => Sniffed::new("audio/x-wavpack", "WavPack", true),
+        _ if at(0, b"MAC ") => Sniffed::new("audio/x-ape", "Monkey's Audio", false),
+        // MPEG frame sync, the layer bits are 0 for ADTS AAC
+        [0xFF, b, ..] if b & 0xF6 == 0xF0 => Sniffed::new("audio/aac", "ADTS AAC", true),
+        [0xFF, b, ..] if b & 0xE0 == 0xE0 => Sniffed::new("audio/mpeg", "MP3", true),
+        _ => return None,
+    };
+    Some(sniffed)
+}
+
+/// Reads the start of the file and sniffs its format, looking past a leading ID3v2 tag.
+pub fn sniff_file(path: &Path) -> Result<Option<Sniffed>> {
+    let mut file = File::open(path)?;
+    let mut header = Vec::new();
+    (&mut file).take(HEADER_LEN).read_to_end(&mut header)?;
+    if !header.starts_with(b"ID3") || header.len() < 10 {
+        return Ok(sniff(&header));
+    }
+    // the tag size is a 28 bit synchsafe integer, the 10 bytes of the header are not included
+    let size = header[6..10]
+        .iter()
+        .fold(0u64, |size, b| size << 7 | (b & 0x7F) as u64);
+    file.seek(SeekFrom::Start(10 + size))?;
+    header.clear();
+    file.take(HEADER_LEN).read_to_end(&mut header)?;
+    // ID3 tags are mostly found in MP3 files
+    Ok(Some(sniff(&header).unwrap_or(Sniffed::new(
+        "audio/mpeg",
+        "MP3",
+        true,
+    ))))
+}
+
+/// Name of a codec, e.g. `FLAC`. Codecs without a decoder are only known to the
+/// container readers, the common ones are named so they can be explained.
+pub fn codec_name(codec: CodecType) -> String {
+    if let Some(descriptor) = CODECS.get_codec(codec) {
+        return descriptor.short_name.to_uppercase();
+    }
+    let name = match codec {
+        codecs::CODEC_TYPE_OPUS => "OPUS",
+        codecs::CODEC_TYPE_WAVPACK => "WAVPACK",
+        codecs::CODEC_TYPE_EAC3 => "E-AC3",
+        codecs::CODEC_TYPE_DCA => "DTS",
+        codecs::CODEC_TYPE_WMA => "WMA",
+        codecs::CODEC_TYPE_SPEEX => "SPEEX",
+        _ => "unknown",
+    };
+    name.to_string()
+}
+
+/// Creates the decoder of a track. The container readers know more codecs than there are
+/// decoders, a track without one gets an error explaining that.
+pub fn make_decoder(params: &CodecParameters) -> Result<Box<dyn Decoder>> {
+    if CODECS.get_codec(params.codec).is_none() {
+        return Err(missing_decoder_error(params.codec));
+    }
+    Ok(CODECS.make(params, &DecoderOptions::default())?)
+}
+
+/// Explains why a track can't be decoded.
+fn missing_decoder_error(codec: CodecType) -> Report {
+    // Opus is decoded by libopus, which is optional
+    let remedy = if codec == codecs::CODEC_TYPE_OPUS {
+        "install soundscope with `--features opus` to decode it"
+    } else {
+        "convert the file to e.g. FLAC to analyze it"
+    };
+    eyre!(
+        "No decoder for {} audio is built in, {remedy}",
+        codec_name(codec)
+    )
+}
+
+/// What is known about a file before probing it.
+#[derive(Default)]
+pub struct ProbeHint {
+    pub extension: Option<String>,
+    pub sniffed: Option<Sniffed>,
+}
+
+impl ProbeHint {
+    /// Builds the hint from the extension and the content of the file.
+    /// A file that can't be sniffed is still probed, symphonia may recognize it anyway.
+    pub fn new(path: &Path) -> Self {
+        Self {
+            extension: path.extension().map(|e| e.to_string_lossy().to_lowercase()),
+            sniffed: sniff_file(path).ok().flatten(),
+        }
+    }
+
+    pub fn hint(&self) -> Hint {
+        let mut hint = Hint::new();
+        if let Some(extension) = &self.extension {
+            hint.with_extension(extension);
+        }
+        if let Some(sniffed) = self.sniffed {
+            hint.mime_type(sniffed.mime);
+        }
+        hint
+    }
+
+    /// Name of the container, the sniffed one or the extension.
+    pub fn container(&self) -> String {
+        match (self.sniffed, &self.extension) {
+            (Some(sniffed), _) => sniffed.name.to_string(),
+            (None, Some(extension)) => extension.to_uppercase(),
+            (None, None) => String::from("unknown"),
+        }
+    }
+
+    /// Explains a failed probe, listing what was tried.
+    pub fn error(&self, err: impl std::fmt::Display) -> Report {
+        let extension = match &self.extension {
+            Some(extension) => format!("the extension \".{extension}\""),
+            None => String::from("no extension"),
+        };
+        let content = match self.sniffed {
+            Some(Sniffed {
+                name,
+                mime,
+                supported: false,
+            }) => format!("the content looks like {name} ({mime}), which is not supported"),
+            Some(Sniffed { name, mime, .. }) => {
+                format!("the content looks like {name} ({mime}) but could not be read")
+            }
+            None => String::from("the content matches no known format"),
+        };
+        eyre!("Unrecognized format ({err}): tried {extension}, {content}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff() {
+        let name = |header: &[u8]| sniff(header).map(|s| s.name);
+        assert_eq!(name(b"RIFF\x24\0\0\0WAVEfmt "), Some("WAV"));
+        assert_eq!(name(b"fLaC\0\0\0\x22"), Some("FLAC"));
+        assert_eq!(name(b"\0\0\0\x20ftypM4A "), Some("MP4"));
+        assert_eq!(name(&[0xFF, 0xF1, 0x50, 0x80]), Some("ADTS AAC"));
+        assert_eq!(name(&[0xFF, 0xFB, 0x90, 0x64]), Some("MP3"));
+        assert_eq!(name(b"wvpk"), Some("WavPack"));
+        let mut ogg = b"OggS".to_vec();
+        ogg.resize(28, 0);
+        assert_eq!(name(&ogg), Some("Ogg"));
+        ogg.extend_from_slice(b"OpusHead");
+        assert_eq!(name(&ogg), Some("Ogg Opus"));
+        assert_eq!(name(b"\x1A\x45\xDF\xA3\x9F\x42\x82\x84webm"), Some("WebM"));
+        assert_eq!(name(b"hello world"), None);
+        assert_eq!(name(b""), None);
+
+        let hint = ProbeHint {
+            extension: Some(String::from("ape")),
+            sniffed: sniff(b"MAC \x96\x0f"),
+        };
+        assert_eq!(
+            hint.error("end of stream").to_string(),
+            "Unrecognized format (end of stream): tried the extension \".ape\", \
+             the content looks like Monkey's Audio (audio/x-ape), which is not supported"
+        );
+        let hint = ProbeHint {
+            extension: Some(String::from("wv")),
+            sniffed: sniff(b"wvpk"),
+        };
+        assert_eq!(
+            hint.error("end of stream").to_string(),
+            "Unrecognized format (end of stream): tried the extension \".wv\", \
+             the content looks like WavPack (audio/x-wavpack) but could not be read"
+        );
+
+        assert_eq!(codec_name(codecs::CODEC_TYPE_FLAC), "FLAC");
+        assert_eq!(codec_name(codecs::CODEC_TYPE_OPUS), "OPUS");
+        assert_eq!(codec_name(codecs::CODEC_TYPE_EAC3), "E-AC3");
+
+        let mut params = CodecParameters::new();
+        params.for_codec(codecs::CODEC_TYPE_DCA);
+        assert_eq!(
+            make_decoder(&params).err().unwrap().to_string(),
+            "No decoder for DTS audio is built in, convert the file to e.g. FLAC to analyze it"
+        );
+        params.for_codec(codecs::CODEC_TYPE_OPUS);
+        let error = make_decoder(&params).err().map(|err| err.to_string());
+        if cfg!(feature = "opus") {
+            // the header is checked by the decoder
+            assert!(error.is_some_and(|err| err.contains("identification header")));
+        } else {
+            assert_eq!(
+                error.unwrap(),
+                "No decoder for OPUS audio is built in, \
+                 install soundscope with `--features opus` to decode it"
+            );
+        }
+    }
+}
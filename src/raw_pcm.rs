@@ -0,0 +1,263 @@
+//! Headerless PCM, described by explicit format flags (`--raw s16le --rate 48000 --channels 2`)
+//! or the raw format dialog shown when a `.raw`/`.pcm` file is opened.
+use eyre::{Result, eyre};
+use std::{fmt::Display, str::FromStr};
+
+/// Sample encoding, named like ffmpeg's raw formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawEncoding {
+    U8,
+    S8,
+    S16Le,
+    S16Be,
+    S24Le,
+    S24Be,
+    S32Le,
+    S32Be,
+    F32Le,
+    F32Be,
+    F64Le,
+    F64Be,
+}
+
+impl RawEncoding {
+    const ALL: [RawEncoding; 12] = [
+        RawEncoding::U8,
+        RawEncoding::S8,
+        RawEncoding::S16Le,
+        RawEncoding::S16Be,
+        RawEncoding::S24Le,
+        RawEncoding::S24Be,
+        RawEncoding::S32Le,
+        RawEncoding::S32Be,
+        RawEncoding::F32Le,
+        RawEncoding::F32Be,
+        RawEncoding::F64Le,
+        RawEncoding::F64Be,
+    ];
+
+    pub fn bytes_per_sample(self) -> usize {
+        match self {
+            RawEncoding::U8 | RawEncoding::S8 => 1,
+            RawEncoding::S16Le | RawEncoding::S16Be => 2,
+            RawEncoding::S24Le | RawEncoding::S24Be => 3,
+            RawEncoding::S32Le | RawEncoding::S32Be | RawEncoding::F32Le | RawEncoding::F32Be => 4,
+            RawEncoding::F64Le | RawEncoding::F64Be => 8,
+        }
+    }
+
+    /// Converts one sample to `-1.0..=1.0`, `bytes` must be [`Self::bytes_per_sample`] long.
+    fn sample(self, bytes: &[u8]) -> f32 {
+        const I32_SCALE: f32 = 2_147_483_648.;
+        match self {
+            RawEncoding::U8 => (bytes[0] as f32 - 128.) / 128.,
+            RawEncoding::S8 => bytes[0] as i8 as f32 / 128.,
+            RawEncoding::S16Le => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.,
+            RawEncoding::S16Be => i16::from_be_bytes([bytes[0], bytes[1]]) as f32 / 32768.,
+            // shift into the upper bytes of an i32 to keep the sign
+            RawEncoding::S24Le => {
+                i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) as f32 / I32_SCALE
+            }
+            RawEncoding::S24Be => {
+                i32::from_be_bytes([bytes[0], bytes[1], bytes[2], 0]) as f32 / I32_SCALE
+            }
+            RawEncoding::S32Le => i32::from_le_bytes(bytes.try_into().unwrap()) as f32 / I32_SCALE,
+            RawEncoding::S32Be => i32::from_be_bytes(bytes.try_into().unwrap()) as f32 / I32_SCALE,
+            RawEncoding::F32Le => f32::from_le_bytes(bytes.try_into().unwrap()),
+            RawEncoding::F32Be => f32::from_be_bytes(bytes.try_into().unwrap()),
+            RawEncoding::F64Le => f64::from_le_bytes(bytes.try_into().unwrap()) as f32,
+            RawEncoding::F64Be => f64::from_be_bytes(bytes.try_into().unwrap()) as f32,
+        }
+    }
+
+    /// Bits of one sample, shown in the file info popup.
+    pub fn bits_per_sample(self) -> u32 {
+        self.bytes_per_sample() as u32 * 8
+    }
+
+    /// Decodes whole samples, trailing bytes of an incomplete sample are ignored.
+    pub fn decode(self, bytes: &[u8]) -> Vec<f32> {
+        bytes
+            .chunks_exact(self.bytes_per_sample())
+            .map(|sample| self.sample(sample))
+            .collect()
+    }
+}
+
+impl FromStr for RawEncoding {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|encoding| encoding.to_string() == s.to_lowercase())
+            .ok_or_else(|| {
+                let names: Vec<String> = Self::ALL.iter().map(|e| e.to_string()).collect();
+                eyre!(
+                    "Unknown raw format: {s}, expected one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl Display for RawEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RawEncoding::U8 => write!(f, "u8"),
+            RawEncoding::S8 => write!(f, "s8"),
+            RawEncoding::S16Le => write!(f, "s16le"),
+            RawEncoding::S16Be => write!(f, "s16be"),
+            RawEncoding::S24Le => write!(f, "s24le"),
+            RawEncoding::S24Be => write!(f, "s24be"),
+            RawEncoding::S32Le => write!(f, "s32le"),
+            RawEncoding::S32Be => write!(f, "s32be"),
+            RawEncoding::F32Le => write!(f, "f32le"),
+            RawEncoding::F32Be => write!(f, "f32be"),
+            RawEncoding::F64Le => write!(f, "f64le"),
+            RawEncoding::F64Be => write!(f, "f64be"),
+        }
+    }
+}
+
+/// Everything needed to interpret headerless PCM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RawFormat {
+    pub encoding: RawEncoding,
+    pub sample_rate: u32,
+    pub channels: usize,
+    /// Bytes skipped at the start, e.g. a proprietary header
+    pub offset: u64,
+}
+
+impl RawFormat {
+    pub const DEFAULT_SAMPLE_RATE: u32 = 48000;
+    pub const DEFAULT_CHANNELS: usize = 2;
+    /// The spectrum of the live modes needs 2^14 samples of its 15 second window
+    pub const MIN_SAMPLE_RATE: u32 = 1100;
+
+    pub fn bytes_per_frame(&self) -> usize {
+        self.encoding.bytes_per_sample() * self.channels
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.sample_rate == 0 || self.channels == 0 {
+            return Err(eyre!(
+                "The sample rate and the channel count must be greater than 0"
+            ));
+        }
+        if self.sample_rate < Self::MIN_SAMPLE_RATE {
+            return Err(eyre!(
+                "The sample rate must be at least {} Hz",
+                Self::MIN_SAMPLE_RATE
+            ));
+        }
+        Ok(())
+    }
+
+    /// Text of the raw format dialog, parsed back by [`RawFormat::from_str`].
+    pub fn to_input(self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.encoding, self.sample_rate, self.channels, self.offset
+        )
+    }
+}
+
+impl Default for RawFormat {
+    fn default() -> Self {
+        Self {
+            encoding: RawEncoding::S16Le,
+            sample_rate: Self::DEFAULT_SAMPLE_RATE,
+            channels: Self::DEFAULT_CHANNELS,
+            offset: 0,
+        }
+    }
+}
+
+/// Parses `<encoding> <rate> <channels> [offset]`, e.g. `s16le 48000 2 44`.
+impl FromStr for RawFormat {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        let [encoding, sample_rate, channels, offset @ ..] = parts.as_slice() else {
+            return Err(eyre!(
+                "Expected <format> <sample rate> <channels> [offset], e.g. s16le 48000 2"
+            ));
+        };
+        let number = |name: &str, value: &str| {
+            value
+                .parse::<u64>()
+                .map_err(|_| eyre!("Invalid {name}: {value}"))
+        };
+        let format = Self {
+            encoding: encoding.parse()?,
+            sample_rate: number("sample rate", sample_rate)? as u32,
+            channels: number("channel count", channels)? as usize,
+            offset: match offset {
+                [] => 0,
+                [offset] => number("offset", offset)?,
+                _ => return Err(eyre!("Too many values: {s}")),
+            },
+        };
+        format.validate()?;
+        Ok(format)
+    }
+}
+
+impl Display for RawFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, {} Hz, {} ch",
+            self.encoding, self.sample_rate, self.channels
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let bytes = [0x00, 0x80, 0xFF, 0x7F, 0x00, 0x00];
+        assert_eq!(
+            RawEncoding::S16Le.decode(&bytes),
+            [-1., 32767. / 32768., 0.]
+        );
+        // -1 in 24 bit, the trailing byte is ignored
+        assert_eq!(
+            RawEncoding::S24Le.decode(&[0xFF, 0xFF, 0xFF, 0x01]),
+            [-1. / 8_388_608.]
+        );
+        assert_eq!(RawEncoding::U8.decode(&[128, 0]), [0., -1.]);
+        assert_eq!(RawEncoding::F32Le.decode(&0.25f32.to_le_bytes()), [0.25]);
+
+        assert_eq!(RawEncoding::S24Be.decode(&[0x80, 0x00, 0x00]), [-1.]);
+        assert_eq!(RawEncoding::S8.decode(&[0x80]), [-1.]);
+
+        assert_eq!("S16LE".parse::<RawEncoding>().unwrap(), RawEncoding::S16Le);
+        assert!("pcm".parse::<RawEncoding>().is_err());
+    }
+
+    #[test]
+    fn test_parse_format() {
+        let format: RawFormat = "f32be 8000 1 512".parse().unwrap();
+        assert_eq!(
+            format,
+            RawFormat {
+                encoding: RawEncoding::F32Be,
+                sample_rate: 8000,
+                channels: 1,
+                offset: 512,
+            }
+        );
+        assert_eq!(format.to_input().parse::<RawFormat>().unwrap(), format);
+        assert_eq!("s16le 44100 2".parse::<RawFormat>().unwrap().offset, 0);
+        assert!("s16le 44100".parse::<RawFormat>().is_err());
+        assert!("s16le 0 2".parse::<RawFormat>().is_err());
+        assert!("s16le 1000 2".parse::<RawFormat>().is_err());
+    }
+}
@@ -0,0 +1,572 @@
+//! Recording of the microphone input to 32-bit float WAV files.
+//! The capture callback hands the samples to a [`SharedRecorder`], which writes them on its own
+//! thread so the callback never waits for the disk. The TUI starts and stops it.
+//! A [`Trigger`] records one file per loud event, e.g. to catch intermittent noises overnight.
+//! It decides in the callback and creates and writes the files on its own thread as well.
+use crate::config::{TriggerConfig, TriggerUnit};
+use crossbeam::channel::{Receiver, Sender, unbounded};
+use ebur128::{EbuR128, Mode};
+use eyre::{Result, eyre};
+use ringbuffer::{AllocRingBuffer, RingBuffer};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, ErrorKind, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+/// `WAVE_FORMAT_IEEE_FLOAT`
+const FORMAT_FLOAT: u16 = 3;
+/// Bytes before the samples: RIFF header, `fmt ` (18 bytes), `fact` and the `data` header
+const HEADER_LEN: u32 = 12 + 8 + 18 + 12 + 8;
+/// Offsets of the sizes written when the recording is finished
+const RIFF_SIZE_OFFSET: u64 = 4;
+const FACT_FRAMES_OFFSET: u64 = 12 + 8 + 18 + 8;
+const DATA_SIZE_OFFSET: u64 = HEADER_LEN as u64 - 4;
+
+/// The recording in progress, `None` while not recording.
+pub type SharedRecorder = Arc<Mutex<Option<Recorder>>>;
+/// The armed trigger, `None` while triggered recording is off.
+pub type SharedTrigger = Arc<Mutex<Option<Trigger>>>;
+
+/// Handle of a recording, the file is written by its writer thread.
+pub struct Recorder {
+    tx: Sender<Vec<f32>>,
+    writer: JoinHandle<Result<PathBuf>>,
+    sample_rate: u32,
+    channels: u16,
+    /// Frames handed to the writer thread
+    frames: u64,
+    /// First write error, set by the writer thread which stops after it
+    error: Arc<OnceLock<String>>,
+}
+
+impl Recorder {
+    /// Creates `recording-<UTC time>.wav` in `dir`, creating the directory if needed,
+    /// and starts the thread writing it.
+    pub fn create(dir: &Path, sample_rate: u32, channels: u16) -> Result<Self> {
+        let mut wav = WavWriter::create(dir, sample_rate, channels)?;
+        let (tx, rx) = unbounded::<Vec<f32>>();
+        let error = Arc::new(OnceLock::new());
+        let writer_error = error.clone();
+        let writer = thread::spawn(move || {
+            for samples in rx {
+                if let Err(err) = wav.write(&samples) {
+                    let _ = writer_error.set(err.to_string());
+                    return Err(err);
+                }
+            }
+            wav.finish()
+        });
+        Ok(Self {
+            tx,
+            writer,
+            sample_rate,
+            channels,
+            frames: 0,
+            error,
+        })
+    }
+
+    /// Hands interleaved samples with the channel count the recording was created with
+    /// to the writer thread. Called from the capture callback, so it never blocks.
+    pub fn write(&mut self, samples: &[f32]) {
+        // the channel is closed once the writer thread stopped at an error
+        if self.tx.send(samples.to_vec()).is_ok() {
+            self.frames += (samples.len() / self.channels.max(1) as usize) as u64;
+        }
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.get().map(String::as_str)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64(self.frames as f64 / self.sample_rate.max(1) as f64)
+    }
+
+    /// Waits for the writer thread to write the remaining samples and the header,
+    /// returns the path of the file, the last one if the recording reached 4 GiB.
+    pub fn finish(self) -> Result<PathBuf> {
+        drop(self.tx);
+        self.writer
+            .join()
+            .map_err(|_| eyre!("The recording thread panicked"))?
+    }
+}
+
+/// 32-bit float WAV file being written. WAV sizes are 32 bit, so a recording that reaches
+/// 4 GiB goes on in a new file.
+struct WavWriter {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    dir: PathBuf,
+    sample_rate: u32,
+    channels: u16,
+    frames: u64,
+    /// Frames after which the next file is started
+    max_frames: u64,
+}
+
+impl WavWriter {
+    /// Creates `recording-<UTC time>.wav` in `dir`, creating the directory if needed.
+    /// A number is appended when a recording was already started in the same second.
+    fn create(dir: &Path, sample_rate: u32, channels: u16) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let name = format!("recording-{}", timestamp(SystemTime::now()));
+        let mut path = dir.join(format!("{name}.wav"));
+        let mut number = 1;
+        let file = loop {
+            match File::create_new(&path) {
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                    number += 1;
+                    path = dir.join(format!("{name}-{number}.wav"));
+                }
+                result => break result?,
+            }
+        };
+        let mut writer = BufWriter::new(file);
+        let block_align = channels * 4;
+        // the sizes are written by `finish`
+        writer.write_all(b"RIFF\0\0\0\0WAVE")?;
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&18u32.to_le_bytes())?;
+        writer.write_all(&FORMAT_FLOAT.to_le_bytes())?;
+        writer.write_all(&channels.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&32u16.to_le_bytes())?;
+        // no extension
+        writer.write_all(&0u16.to_le_bytes())?;
+        // non-PCM formats need the number of frames in `fact`
+        writer.write_all(b"fact")?;
+        writer.write_all(&4u32.to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(b"data\0\0\0\0")?;
+        Ok(Self {
+            writer,
+            path,
+            dir: dir.to_path_buf(),
+            sample_rate,
+            channels,
+            frames: 0,
+            max_frames: max_frames(channels),
+        })
+    }
+
+    /// Appends interleaved samples with the channel count the file was created with.
+    /// When the file is full it is finished and the samples go on in a new one.
+    fn write(&mut self, mut samples: &[f32]) -> Result<()> {
+        let channels = self.channels.max(1) as usize;
+        loop {
+            let room = (self.max_frames - self.frames) as usize * channels;
+            let (now, rest) = samples.split_at(room.min(samples.len()));
+            for sample in now {
+                self.writer.write_all(&sample.to_le_bytes())?;
+            }
+            self.frames += (now.len() / channels) as u64;
+            if rest.is_empty() {
+                return Ok(());
+            }
+            let mut next = Self::create(&self.dir, self.sample_rate, self.channels)?;
+            next.max_frames = self.max_frames;
+            std::mem::replace(self, next).finish()?;
+            samples = rest;
+        }
+    }
+
+    /// Writes the sizes into the header and returns the path of the file.
+    fn finish(mut self) -> Result<PathBuf> {
+        let [riff_size, frames, data_size] = header_sizes(self.frames, self.channels)?;
+        self.writer.flush()?;
+        let file = self.writer.get_mut();
+        for (offset, value) in [
+            (RIFF_SIZE_OFFSET, riff_size),
+            (FACT_FRAMES_OFFSET, frames),
+            (DATA_SIZE_OFFSET, data_size),
+        ] {
+            file.seek(SeekFrom::Start(offset))?;
+            file.write_all(&value.to_le_bytes())?;
+        }
+        file.flush()?;
+        Ok(self.path)
+    }
+}
+
+/// Frames of a file of the largest size WAV can describe.
+fn max_frames(channels: u16) -> u64 {
+    (u32::MAX - HEADER_LEN) as u64 / (channels.max(1) as u64 * 4)
+}
+
+/// The RIFF size, the frames in `fact` and the `data` size of a file with `frames` frames.
+fn header_sizes(frames: u64, channels: u16) -> Result<[u32; 3]> {
+    let data_size = frames * channels as u64 * 4;
+    let size = |value: u64| {
+        u32::try_from(value).map_err(|_| eyre!("{frames} frames don't fit in a WAV file"))
+    };
+    Ok([
+        size((HEADER_LEN - 8) as u64 + data_size)?,
+        size(frames)?,
+        size(data_size)?,
+    ])
+}
+
+/// Starts a recording when the input exceeds the threshold and finishes it once the input
+/// stayed below it for the hold time. The recording starts with the pre-roll, the audio before
+/// the trigger, taken from the buffer of the live analysis.
+pub struct Trigger {
+    config: TriggerConfig,
+    sample_rate: u32,
+    channels: u16,
+    /// Momentary loudness, only for [`TriggerUnit::Lufs`]
+    loudness: Option<EbuR128>,
+    tx: Sender<TriggerEvent>,
+    writer: JoinHandle<()>,
+    /// Frames of the current event, `None` while waiting for the trigger
+    event_frames: Option<u64>,
+    /// Frames below the threshold since the input was last loud
+    quiet_frames: u64,
+    /// Finished recordings, counted by the writer thread
+    recordings: Arc<AtomicUsize>,
+    /// Error that stopped the trigger
+    error: Arc<OnceLock<String>>,
+}
+
+/// What the capture callback tells the writer thread of a [`Trigger`].
+enum TriggerEvent {
+    /// The input exceeded the threshold, a new file starts with the pre-roll
+    Start(Vec<f32>),
+    Samples(Vec<f32>),
+    /// The input stayed below the threshold for the hold time
+    Finish,
+}
+
+impl Trigger {
+    pub fn new(
+        config: TriggerConfig,
+        dir: PathBuf,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<Self> {
+        let loudness = match config.unit {
+            TriggerUnit::Dbfs => None,
+            TriggerUnit::Lufs => Some(EbuR128::new(channels as u32, sample_rate, Mode::M)?),
+        };
+        let (tx, rx) = unbounded();
+        let recordings = Arc::new(AtomicUsize::new(0));
+        let error = Arc::new(OnceLock::new());
+        let writer = {
+            let (recordings, error) = (recordings.clone(), error.clone());
+            thread::spawn(move || {
+                if let Err(err) = write_events(&rx, &dir, sample_rate, channels, &recordings) {
+                    let _ = error.set(err.to_string());
+                }
+            })
+        };
+        Ok(Self {
+            config,
+            sample_rate,
+            channels,
+            loudness,
+            tx,
+            writer,
+            event_frames: None,
+            quiet_frames: 0,
+            recordings,
+            error,
+        })
+    }
+
+    /// Handles a block of captured samples. `buffer` holds the input before `samples`,
+    /// laid out as the capture callback writes it (mono is written as left with a silent right).
+    pub fn write(&mut self, samples: &[f32], buffer: &AllocRingBuffer<f32>) {
+        if self.error.get().is_some() {
+            return;
+        }
+        let channels = self.channels.max(1) as usize;
+        let level = match &mut self.loudness {
+            Some(meter) => {
+                if let Err(err) = meter.add_frames_f32(samples) {
+                    let _ = self.error.set(err.to_string());
+                    return;
+                }
+                meter.loudness_momentary().unwrap_or(f64::NEG_INFINITY)
+            }
+            None => {
+                let peak = samples.iter().fold(0f32, |peak, s| peak.max(s.abs()));
+                20. * (peak as f64).log10()
+            }
+        };
+        let frames = (samples.len() / channels) as u64;
+        if level >= self.config.threshold {
+            self.quiet_frames = 0;
+            if self.event_frames.is_none() {
+                self.start(buffer);
+            }
+        } else {
+            self.quiet_frames += frames;
+        }
+        let Some(event_frames) = &mut self.event_frames else {
+            return;
+        };
+        *event_frames += frames;
+        // the channel is closed once the writer thread stopped at an error
+        let _ = self.tx.send(TriggerEvent::Samples(samples.to_vec()));
+        if self.quiet_frames as f64 >= self.config.hold * self.sample_rate as f64 {
+            let _ = self.tx.send(TriggerEvent::Finish);
+            self.event_frames = None;
+        }
+    }
+
+    fn start(&mut self, buffer: &AllocRingBuffer<f32>) {
+        // mono takes two samples per frame in the buffer
+        let stride = if self.channels == 1 { 2 } else { 1 };
+        let frames = (self.config.pre_roll.max(0.) * self.sample_rate as f64) as usize;
+        let len = (frames * self.channels as usize * stride).min(buffer.len());
+        let pre_roll: Vec<f32> = buffer
+            .iter()
+            .skip(buffer.len() - len)
+            .step_by(stride)
+            .copied()
+            .collect();
+        self.event_frames = Some((pre_roll.len() / self.channels.max(1) as usize) as u64);
+        let _ = self.tx.send(TriggerEvent::Start(pre_roll));
+    }
+
+    /// Finishes the recording of the current event, if any, and waits for the writer thread.
+    pub fn disarm(self) {
+        drop(self.tx);
+        let _ = self.writer.join();
+    }
+
+    /// Length of the recording of the current event, `None` while waiting for the trigger.
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.event_frames
+            .map(|frames| Duration::from_secs_f64(frames as f64 / self.sample_rate.max(1) as f64))
+    }
+
+    /// Number of finished recordings.
+    pub fn recordings(&self) -> usize {
+        self.recordings.load(Ordering::Relaxed)
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.get().map(String::as_str)
+    }
+}
+
+/// Writes the files of a [`Trigger`] until it is disarmed, which finishes the current file.
+fn write_events(
+    rx: &Receiver<TriggerEvent>,
+    dir: &Path,
+    sample_rate: u32,
+    channels: u16,
+    recordings: &AtomicUsize,
+) -> Result<()> {
+    let mut wav: Option<WavWriter> = None;
+    let finish = |wav: Option<WavWriter>| -> Result<()> {
+        if let Some(wav) = wav {
+            wav.finish()?;
+            recordings.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    };
+    for event in rx {
+        match event {
+            TriggerEvent::Start(pre_roll) => {
+                let mut new = WavWriter::create(dir, sample_rate, channels)?;
+                new.write(&pre_roll)?;
+                wav = Some(new);
+            }
+            TriggerEvent::Samples(samples) => {
+                if let Some(wav) = &mut wav {
+                    wav.write(&samples)?;
+                }
+            }
+            TriggerEvent::Finish => finish(wav.take())?,
+        }
+    }
+    finish(wav)
+}
+
+/// Calendar date and time of day in UTC, used in file names and logs.
+pub struct UtcTime {
+    pub year: u64,
+    pub month: u64,
+    pub day: u64,
+    pub hour: u64,
+    pub minute: u64,
+    pub second: u64,
+}
+
+impl UtcTime {
+    pub fn new(time: SystemTime) -> Self {
+        let secs = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let (days, secs_of_day) = (secs / 86400, secs % 86400);
+        // civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+        let z = days + 719_468;
+        let era = z / 146_097;
+        let day_of_era = z % 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        Self {
+            year: year_of_era + era * 400 + u64::from(month <= 2),
+            month,
+            day,
+            hour: secs_of_day / 3600,
+            minute: secs_of_day / 60 % 60,
+            second: secs_of_day % 60,
+        }
+    }
+
+    /// `2024-05-31`
+    pub fn date(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Formats a time as `2024-05-31_13-05-09` (UTC), safe to use in file names.
+fn timestamp(time: SystemTime) -> String {
+    let time = UtcTime::new(time);
+    format!(
+        "{}_{:02}-{:02}-{:02}",
+        time.date(),
+        time.hour,
+        time.minute,
+        time.second
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::riff;
+
+    #[test]
+    fn test_timestamp() {
+        let at = |secs| timestamp(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(at(0), "1970-01-01_00-00-00");
+        assert_eq!(at(951_782_400 + 3723), "2000-02-29_01-02-03");
+        assert_eq!(at(1_717_160_709), "2024-05-31_13-05-09");
+    }
+
+    #[test]
+    fn test_recording_is_a_valid_wav() {
+        let dir =
+            std::env::temp_dir().join(format!("soundscope-test-recorder-{}", std::process::id()));
+        let mut recorder = Recorder::create(&dir, 48000, 2).unwrap();
+        recorder.write(&[0.5, -0.5, 0.25, -0.25]);
+        assert_eq!(recorder.error(), None);
+        assert_eq!(recorder.elapsed(), Duration::from_secs_f64(2. / 48000.));
+        let path = recorder.finish().unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), HEADER_LEN as usize + 16);
+        assert_eq!(&bytes[4..8], &(HEADER_LEN - 8 + 16).to_le_bytes());
+        let chunks = riff::read_chunks(&path, &[b"fmt ", b"fact"]).unwrap();
+        assert_eq!(chunks[0].data[..2], FORMAT_FLOAT.to_le_bytes());
+        assert_eq!(chunks[1].data, 2u32.to_le_bytes());
+        assert_eq!(bytes[bytes.len() - 4..], (-0.25f32).to_le_bytes());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_header_sizes_at_the_limit() {
+        for channels in [1, 2, 6] {
+            let frames = max_frames(channels);
+            let [riff_size, fact_frames, data_size] = header_sizes(frames, channels).unwrap();
+            assert_eq!(data_size as u64, frames * channels as u64 * 4);
+            assert_eq!(riff_size, HEADER_LEN - 8 + data_size);
+            assert_eq!(fact_frames as u64, frames);
+            // the whole file is at most 4 GiB
+            assert!(HEADER_LEN as u64 + data_size as u64 <= u32::MAX as u64);
+            assert!(header_sizes(frames * 2, channels).is_err());
+        }
+    }
+
+    #[test]
+    fn test_full_file_goes_on_in_a_new_one() {
+        let dir =
+            std::env::temp_dir().join(format!("soundscope-test-split-{}", std::process::id()));
+        let mut wav = WavWriter::create(&dir, 48000, 2).unwrap();
+        wav.max_frames = 3;
+        let first = wav.path.clone();
+        wav.write(&[0.1, 0.1, 0.2, 0.2]).unwrap();
+        wav.write(&[0.3, 0.3, 0.4, 0.4, 0.5, 0.5]).unwrap();
+        let last = wav.finish().unwrap();
+        assert_ne!(first, last);
+
+        for (path, samples) in [
+            (first, vec![0.1, 0.1, 0.2, 0.2, 0.3, 0.3]),
+            (last, vec![0.4, 0.4, 0.5, 0.5]),
+        ] {
+            let bytes = fs::read(&path).unwrap();
+            let data_size = samples.len() as u32 * 4;
+            assert_eq!(bytes[4..8], (HEADER_LEN - 8 + data_size).to_le_bytes());
+            let chunks = riff::read_chunks(&path, &[b"fact", b"data"]).unwrap();
+            assert_eq!(chunks[0].data, (samples.len() as u32 / 2).to_le_bytes());
+            let written: Vec<f32> = chunks[1]
+                .data
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            assert_eq!(written, samples);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_trigger() {
+        let dir =
+            std::env::temp_dir().join(format!("soundscope-test-trigger-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = TriggerConfig {
+            threshold: -20.,
+            unit: TriggerUnit::Dbfs,
+            pre_roll: 0.5,
+            hold: 1.,
+        };
+        // mono at 4 Hz, the buffer holds mono as left and a silent right
+        let mut trigger = Trigger::new(config, dir.clone(), 4, 1).unwrap();
+        let mut buffer = AllocRingBuffer::new(16);
+        buffer.extend([0.01, 0., 0.02, 0., 0.03, 0.]);
+
+        trigger.write(&[0.01, 0.01], &buffer);
+        assert_eq!(trigger.elapsed(), None);
+        trigger.write(&[0.5, 0.01], &buffer);
+        assert_eq!(trigger.elapsed(), Some(Duration::from_secs(1)));
+        trigger.write(&[0.01, 0.01], &buffer);
+        assert_eq!(trigger.recordings(), 0);
+        trigger.write(&[0.01, 0.01], &buffer);
+        assert_eq!(trigger.elapsed(), None);
+        assert_eq!(trigger.error(), None);
+        trigger.disarm();
+
+        let files: Vec<PathBuf> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        let bytes = fs::read(&files[0]).unwrap();
+        let samples: Vec<f32> = bytes[HEADER_LEN as usize..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        // 2 frames of pre-roll, then the event until the input was quiet for 1 s
+        assert_eq!(samples, [0.02, 0.03, 0.5, 0.01, 0.01, 0.01, 0.01, 0.01]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
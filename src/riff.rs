@@ -0,0 +1,85 @@
+//! Minimal RIFF reader used to pull metadata chunks (cue points, labels, bext, etc.)
+//! out of WAV and BWF (RF64/BW64) files. Symphonia skips these chunks, so we read them ourselves.
+use eyre::{Result, eyre};
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// A chunk read from a RIFF file.
+pub struct Chunk {
+    pub id: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+/// Reads every top-level chunk whose id is in `ids` from a RIFF/RF64/BW64 file.
+/// The `data` chunk is always skipped without reading it, so this is cheap even on huge files.
+/// Reading stops at a chunk larger than the rest of the file, e.g. in a truncated recording.
+pub fn read_chunks(path: &Path, ids: &[&[u8; 4]]) -> Result<Vec<Chunk>> {
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+
+    let mut header = [0u8; 12];
+    reader.read_exact(&mut header)?;
+    let form = &header[0..4];
+    if !(form == b"RIFF" || form == b"RF64" || form == b"BW64") || &header[8..12] != b"WAVE" {
+        return Err(eyre!("Not a RIFF WAVE file"));
+    }
+
+    let mut chunks = Vec::new();
+    // RF64 files store the real size of the data chunk in `ds64`
+    let mut ds64_data_size = None;
+    loop {
+        let mut chunk_header = [0u8; 8];
+        if reader.read_exact(&mut chunk_header).is_err() {
+            break;
+        }
+        let id: [u8; 4] = chunk_header[0..4].try_into().unwrap();
+        let size = u32::from_le_bytes(chunk_header[4..8].try_into().unwrap());
+        let size = match (&id, size, ds64_data_size) {
+            (b"data", u32::MAX, Some(data_size)) => data_size,
+            _ => size as u64,
+        };
+        // the size comes from the file, it is checked before allocating the chunk
+        let read = &id == b"ds64" || ids.contains(&&id);
+        if read && size > file_len.saturating_sub(reader.stream_position()?) {
+            break;
+        }
+
+        if &id == b"ds64" {
+            let mut data = vec![0u8; size as usize];
+            reader.read_exact(&mut data)?;
+            // riffSize (8 bytes), then dataSize (8 bytes)
+            if data.len() >= 16 {
+                ds64_data_size = Some(u64::from_le_bytes(data[8..16].try_into().unwrap()));
+            }
+        } else if read {
+            let mut data = vec![0u8; size as usize];
+            reader.read_exact(&mut data)?;
+            chunks.push(Chunk { id, data });
+        } else {
+            reader.seek(SeekFrom::Current(size as i64))?;
+        }
+
+        // chunks are padded to an even number of bytes
+        if size % 2 == 1 {
+            reader.seek(SeekFrom::Current(1))?;
+        }
+    }
+
+    Ok(chunks)
+}
+
+/// Reads a little endian `u32` at `offset`, returns `None` if `data` is too short.
+pub fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+/// Converts a fixed-size, possibly NUL-terminated text field to a `String`.
+pub fn read_text(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
@@ -0,0 +1,155 @@
+//! Decoded samples of a file, shared by the decoding thread, the player and the analysis.
+//! The samples are kept in fixed-size chunks that are published as soon as they are full,
+//! so playback starts before the file is decoded and no large vector is ever reallocated.
+use std::{
+    ops::Range,
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+
+/// Frames in a chunk, about 1.4 s at 48 kHz
+const CHUNK_FRAMES: usize = 1 << 16;
+
+pub struct SampleStore {
+    /// Interleaved samples in a chunk, whole frames so that published samples end on a frame
+    chunk_len: usize,
+    /// Published chunks, all of them are full except the last one of a complete store
+    chunks: RwLock<Vec<Arc<[f32]>>>,
+    /// Published samples
+    len: AtomicUsize,
+    /// The decoding has finished, or stopped at an error
+    complete: AtomicBool,
+}
+
+impl SampleStore {
+    /// Returns an empty store and the writer that fills it.
+    pub fn new(channels: usize) -> (Arc<Self>, SampleWriter) {
+        let store = Arc::new(Self::with_chunk_len(CHUNK_FRAMES * channels.max(1)));
+        let writer = SampleWriter {
+            chunk: Vec::with_capacity(store.chunk_len),
+            store: store.clone(),
+        };
+        (store, writer)
+    }
+
+    fn with_chunk_len(chunk_len: usize) -> Self {
+        Self {
+            chunk_len,
+            chunks: RwLock::new(Vec::new()),
+            len: AtomicUsize::new(0),
+            complete: AtomicBool::new(false),
+        }
+    }
+
+    /// Samples that can be read.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete.load(Ordering::Acquire)
+    }
+
+    pub fn chunk_len(&self) -> usize {
+        self.chunk_len
+    }
+
+    /// The `index`-th chunk, `None` if it is not published yet.
+    pub fn chunk(&self, index: usize) -> Option<Arc<[f32]>> {
+        self.chunks.read().unwrap().get(index).cloned()
+    }
+
+    /// Copies the published samples in `range`.
+    pub fn read(&self, range: Range<usize>) -> Vec<f32> {
+        let end = range.end.min(self.len());
+        let mut samples = Vec::with_capacity(end.saturating_sub(range.start));
+        let chunks = self.chunks.read().unwrap();
+        let mut pos = range.start;
+        while pos < end {
+            let chunk = &chunks[pos / self.chunk_len];
+            let offset = pos % self.chunk_len;
+            let len = (chunk.len() - offset).min(end - pos);
+            samples.extend_from_slice(&chunk[offset..offset + len]);
+            pos += len;
+        }
+        samples
+    }
+}
+
+impl Default for SampleStore {
+    /// An empty, complete store.
+    fn default() -> Self {
+        let store = Self::with_chunk_len(CHUNK_FRAMES);
+        store.complete.store(true, Ordering::Release);
+        store
+    }
+}
+
+/// Appends to a [`SampleStore`], which is complete when the writer is dropped.
+pub struct SampleWriter {
+    store: Arc<SampleStore>,
+    /// Chunk being filled, not visible to the readers yet
+    chunk: Vec<f32>,
+}
+
+impl SampleWriter {
+    /// Appends interleaved samples, publishing every chunk that gets full.
+    pub fn push(&mut self, mut samples: &[f32]) {
+        while !samples.is_empty() {
+            let len = (self.store.chunk_len - self.chunk.len()).min(samples.len());
+            self.chunk.extend_from_slice(&samples[..len]);
+            samples = &samples[len..];
+            if self.chunk.len() == self.store.chunk_len {
+                self.publish();
+            }
+        }
+    }
+
+    fn publish(&mut self) {
+        let chunk: Arc<[f32]> =
+            std::mem::replace(&mut self.chunk, Vec::with_capacity(self.store.chunk_len)).into();
+        let len = chunk.len();
+        self.store.chunks.write().unwrap().push(chunk);
+        self.store.len.fetch_add(len, Ordering::Release);
+    }
+}
+
+impl Drop for SampleWriter {
+    fn drop(&mut self) {
+        if !self.chunk.is_empty() {
+            self.publish();
+        }
+        self.store.complete.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks() {
+        let store = Arc::new(SampleStore::with_chunk_len(4));
+        let mut writer = SampleWriter {
+            chunk: Vec::new(),
+            store: store.clone(),
+        };
+        let samples: Vec<f32> = (0..10).map(|i| i as f32).collect();
+        writer.push(&samples[..3]);
+        // the first chunk is not full yet
+        assert_eq!(store.len(), 0);
+        writer.push(&samples[3..]);
+        assert_eq!(store.len(), 8);
+        assert!(!store.is_complete());
+        assert_eq!(store.read(2..6), [2., 3., 4., 5.]);
+        assert_eq!(store.read(6..20), [6., 7.]);
+
+        drop(writer);
+        assert!(store.is_complete());
+        assert_eq!(store.read(0..10), samples);
+        assert_eq!(*store.chunk(2).unwrap(), [8., 9.]);
+        assert!(store.chunk(3).is_none());
+    }
+}
@@ -0,0 +1,338 @@
+//! Audio piped to stdin (`ffmpeg ... | soundscope -`), analyzed live like the microphone.
+//! WAV/FLAC/... streams are decoded with symphonia, headerless PCM needs a [`RawFormat`].
+//! The stream decoding is shared with [`crate::stream_input`], the raw PCM reading with the
+//! system capture of [`crate::audio_capture`].
+use crate::{loudness_log::SharedLog, probe, raw_pcm::RawFormat, tui::RBuffer};
+use crossbeam::channel::{Receiver, Sender, unbounded};
+use eyre::{Result, eyre};
+use ringbuffer::{AllocRingBuffer, RingBuffer};
+use std::{
+    io::{self, Read},
+    process::{Child, Command, Stdio},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use symphonia::core::{
+    audio::{SampleBuffer, SignalSpec},
+    codecs::CODEC_TYPE_NULL,
+    errors::Error,
+    formats::{FormatOptions, FormatReader},
+    io::{MediaSourceStream, MediaSourceStreamOptions, ReadOnlySource},
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+/// Seconds of audio kept in the ring buffer, the same as for the microphone
+const BUFFER_SECONDS: usize = 30;
+/// Raw PCM is read in blocks of this many milliseconds
+const RAW_BLOCK_MILLIS: usize = 20;
+
+pub enum InputEvent {
+    /// The format is known and samples are written to `buffer`
+    Started {
+        buffer: RBuffer,
+        sample_rate: u32,
+        /// Shown next to the mode, e.g. "FLAC, 44100 Hz, 2 ch"
+        description: String,
+    },
+    /// Title of what is playing, e.g. the ICY `StreamTitle` of internet radio
+    Title(String),
+    /// The pipe was closed
+    Ended,
+    Error(String),
+}
+
+/// An input read on its own thread, which stops reading when this is dropped.
+pub struct InputThread {
+    pub events: Receiver<InputEvent>,
+    stopped: Arc<AtomicBool>,
+    /// Program whose output is read, killed together with the thread
+    child: Option<Child>,
+}
+
+impl Drop for InputThread {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(child) = &mut self.child {
+            // the reading thread ends with the pipe
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Runs `read` on a new thread, its result is sent as [`InputEvent::Ended`] or
+/// [`InputEvent::Error`] prefixed with `name`. `read` should return once the flag is set.
+pub fn spawn_reader(
+    name: String,
+    read: impl FnOnce(&Sender<InputEvent>, &AtomicBool) -> Result<()> + Send + 'static,
+) -> InputThread {
+    let (tx, rx) = unbounded();
+    let stopped = Arc::new(AtomicBool::new(false));
+    let flag = stopped.clone();
+    thread::spawn(move || {
+        let event = match read(&tx, &flag) {
+            Ok(()) => InputEvent::Ended,
+            Err(err) => InputEvent::Error(format!("Error reading {name}: {err}")),
+        };
+        if let Err(_err) = tx.send(event) {
+            // the TUI has quit
+        }
+    });
+    InputThread {
+        events: rx,
+        stopped,
+        child: None,
+    }
+}
+
+/// Reads stdin on its own thread. `raw` is the format of headerless PCM,
+/// `None` if the stream has a container.
+pub fn spawn(raw: Option<RawFormat>) -> InputThread {
+    spawn_reader(String::from("stdin"), move |tx, stopped| match raw {
+        Some(format) => read_raw(io::stdin().lock(), format, None, tx, stopped),
+        None => {
+            let format = probe_stream(io::stdin(), &Hint::new()).map_err(|err| {
+                eyre!("{err}. Headerless PCM needs --raw <FORMAT> --rate <HZ> --channels <N>")
+            })?;
+            decode_stream(format, tx, stopped)
+        }
+    })
+}
+
+/// Reads headerless PCM from the standard output of `command`, e.g. a recorder of the system output.
+/// The samples are also written to `log` while it logs.
+pub fn spawn_command(
+    mut command: Command,
+    format: RawFormat,
+    name: String,
+    log: Option<SharedLog>,
+) -> Result<InputThread> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| eyre!("Could not start {program}: {err}"))?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| eyre!("No output from {program}"))?;
+    let mut thread = spawn_reader(name, move |tx, stopped| {
+        read_raw(stdout, format, log, tx, stopped)
+    });
+    thread.child = Some(child);
+    Ok(thread)
+}
+
+/// Ring buffer of interleaved stereo samples, the layout the live analysis expects.
+fn new_buffer(sample_rate: u32) -> RBuffer {
+    let mut buf = AllocRingBuffer::new(sample_rate as usize * BUFFER_SECONDS);
+    buf.fill(0.0);
+    Arc::new(Mutex::new(buf))
+}
+
+/// Writes interleaved samples as stereo: mono is copied to both sides,
+/// only the first two channels of multichannel audio are kept.
+fn push_stereo(buffer: &RBuffer, samples: &[f32], channels: usize) {
+    let mut buffer = buffer.lock().unwrap();
+    for frame in samples.chunks_exact(channels.max(1)) {
+        let (l, r) = match frame {
+            [mono] => (*mono, *mono),
+            [l, r, ..] => (*l, *r),
+            [] => continue,
+        };
+        buffer.enqueue(l);
+        buffer.enqueue(r);
+    }
+}
+
+/// Keeps a stream that is faster than real time (e.g. ffmpeg decoding a file)
+/// from filling the buffer at once, live sources are never ahead.
+struct Pacer {
+    start: Instant,
+    frames: u64,
+    sample_rate: u32,
+}
+
+impl Pacer {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            start: Instant::now(),
+            frames: 0,
+            sample_rate: sample_rate.max(1),
+        }
+    }
+
+    fn wait(&mut self, frames: usize) {
+        self.frames += frames as u64;
+        let position = Duration::from_secs_f64(self.frames as f64 / self.sample_rate as f64);
+        if let Some(ahead) = position.checked_sub(self.start.elapsed()) {
+            thread::sleep(ahead);
+        }
+    }
+}
+
+fn read_raw(
+    mut source: impl Read,
+    format: RawFormat,
+    log: Option<SharedLog>,
+    tx: &Sender<InputEvent>,
+    stopped: &AtomicBool,
+) -> Result<()> {
+    let buffer = new_buffer(format.sample_rate);
+    tx.send(InputEvent::Started {
+        buffer: buffer.clone(),
+        sample_rate: format.sample_rate,
+        description: format.to_string(),
+    })?;
+
+    let frame_len = format.bytes_per_frame();
+    let block_frames = (format.sample_rate as usize * RAW_BLOCK_MILLIS / 1000).max(1);
+    let mut block = vec![0u8; block_frames * frame_len];
+    let mut pending = Vec::new();
+    let mut pacer = Pacer::new(format.sample_rate);
+    io::copy(&mut (&mut source).take(format.offset), &mut io::sink())?;
+    loop {
+        let read = source.read(&mut block)?;
+        if read == 0 || stopped.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        pending.extend_from_slice(&block[..read]);
+        // keep an incomplete frame for the next read
+        let whole = pending.len() / frame_len * frame_len;
+        let samples = format.encoding.decode(&pending[..whole]);
+        pending.drain(..whole);
+        if let Some(log) = &log
+            && let Some(log) = log.lock().unwrap().as_mut()
+        {
+            log.write(&samples);
+        }
+        push_stereo(&buffer, &samples, format.channels);
+        pacer.wait(whole / frame_len);
+    }
+}
+
+/// Detects the container of an unseekable stream.
+/// Returns a sample buffer for a decoded packet of `frames` frames, a new one when
+/// the packet is longer than the previous ones or the stream changed its spec
+fn sample_buffer(
+    slot: &mut Option<(SampleBuffer<f32>, SignalSpec)>,
+    spec: SignalSpec,
+    frames: usize,
+) -> &mut SampleBuffer<f32> {
+    let fits = slot.as_ref().is_some_and(|(buf, buf_spec)| {
+        *buf_spec == spec && buf.capacity() >= frames * spec.channels.count()
+    });
+    if !fits {
+        *slot = None;
+    }
+    &mut slot
+        .get_or_insert_with(|| (SampleBuffer::new(frames as u64, spec), spec))
+        .0
+}
+
+pub fn probe_stream(
+    source: impl Read + Send + Sync + 'static,
+    hint: &Hint,
+) -> Result<Box<dyn FormatReader>, Error> {
+    let source = ReadOnlySource::new(source);
+    let mss = MediaSourceStream::new(Box::new(source), MediaSourceStreamOptions::default());
+    let probed = probe::formats().format(
+        hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    Ok(probed.format)
+}
+
+/// Decodes the first audio track of a stream in real time until it ends or `stopped` is set.
+pub fn decode_stream(
+    mut format: Box<dyn FormatReader>,
+    tx: &Sender<InputEvent>,
+    stopped: &AtomicBool,
+) -> Result<()> {
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| eyre!("No audio track found with a decodeable codec"))?;
+    let track_id = track.id;
+    let mut decoder = probe::make_decoder(&track.codec_params)?;
+    let codec = probe::codec_name(track.codec_params.codec);
+
+    let mut started = None;
+    let mut sample_buf = None;
+    loop {
+        if stopped.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(_)) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let audio_buf = match decoder.decode(&packet) {
+            Ok(audio_buf) => audio_buf,
+            Err(Error::DecodeError(_)) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        let spec = *audio_buf.spec();
+        let channels = spec.channels.count();
+        let (buffer, pacer) = started.get_or_insert_with(|| {
+            let buffer = new_buffer(spec.rate);
+            if let Err(_err) = tx.send(InputEvent::Started {
+                buffer: buffer.clone(),
+                sample_rate: spec.rate,
+                description: format!("{codec}, {} Hz, {channels} ch", spec.rate),
+            }) {
+                // the TUI has quit
+            }
+            (buffer, Pacer::new(spec.rate))
+        });
+        let buf = sample_buffer(&mut sample_buf, spec, audio_buf.capacity());
+        buf.copy_interleaved_ref(audio_buf);
+        push_stereo(buffer, buf.samples(), channels);
+        pacer.wait(buf.samples().len() / channels.max(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symphonia::core::audio::Channels;
+
+    #[test]
+    fn test_push_stereo() {
+        let buffer = new_buffer(1);
+        push_stereo(&buffer, &[0.1, 0.2], 1);
+        push_stereo(&buffer, &[0.3, 0.4, 0.5, 0.6, 0.7, 0.8], 3);
+        let samples = buffer.lock().unwrap().to_vec();
+        assert_eq!(
+            samples[samples.len() - 8..],
+            [0.1, 0.1, 0.2, 0.2, 0.3, 0.4, 0.6, 0.7]
+        );
+    }
+
+    #[test]
+    fn test_sample_buffer() {
+        let stereo = SignalSpec::new(44100, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+        let mut slot = None;
+        assert_eq!(sample_buffer(&mut slot, stereo, 1152).capacity(), 2304);
+        // shorter packets reuse the buffer, longer ones get a bigger one
+        assert_eq!(sample_buffer(&mut slot, stereo, 576).capacity(), 2304);
+        assert_eq!(sample_buffer(&mut slot, stereo, 4096).capacity(), 8192);
+        let mono = SignalSpec::new(44100, Channels::FRONT_CENTRE);
+        sample_buffer(&mut slot, mono, 1152);
+        assert_eq!(slot.map(|(_, spec)| spec), Some(mono));
+    }
+}
@@ -0,0 +1,346 @@
+//! Internet radio and other HTTP/Icecast streams (`soundscope http://host:8000/live`),
+//! analyzed live like the microphone. ICY `StreamTitle` metadata interleaved with the audio
+//! is stripped before decoding and reported as [`InputEvent::Title`].
+use crate::stdin_input::{self, InputEvent, InputThread};
+use crossbeam::channel::Sender;
+use eyre::{Result, eyre};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+use symphonia::core::probe::Hint;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// A stream that sends nothing for this long is considered ended
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REDIRECTS: usize = 5;
+
+/// Returns `true` if a command line argument is a stream URL rather than a file.
+pub fn is_url(arg: &str) -> bool {
+    let scheme = arg.get(..8).unwrap_or(arg).to_lowercase();
+    scheme.starts_with("http://") || scheme.starts_with("https://")
+}
+
+/// Connects to `url` and decodes the stream on its own thread.
+pub fn spawn(url: String) -> InputThread {
+    stdin_input::spawn_reader(url.clone(), move |tx, stopped| {
+        let response = Response::get(&url)?;
+        if let Some(name) = response.header("icy-name")
+            && let Err(_err) = tx.send(InputEvent::Title(name.to_string()))
+        {
+            // the TUI has quit
+        }
+        let mut hint = Hint::new();
+        if let Some(content_type) = response.header("content-type") {
+            hint.mime_type(content_type);
+        }
+        let metaint = response
+            .header("icy-metaint")
+            .and_then(|m| m.parse().ok())
+            .unwrap_or(0);
+        let source = IcyReader::new(response.body, metaint, tx.clone());
+        let format = stdin_input::probe_stream(source, &hint)?;
+        stdin_input::decode_stream(format, tx, stopped)
+    })
+}
+
+/// Parts of an `http://` URL needed for a request.
+#[derive(Debug, PartialEq)]
+struct Url {
+    host: String,
+    port: u16,
+    /// Path and query, e.g. `/live?type=.mp3`
+    path: String,
+}
+
+impl Url {
+    fn parse(url: &str) -> Result<Self> {
+        let Some(rest) = url
+            .get(..7)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("http://"))
+            .map(|scheme| &url[scheme.len()..])
+        else {
+            return Err(if is_url(url) {
+                eyre!("HTTPS streams are not supported, use the http:// URL of the stream")
+            } else {
+                eyre!("Not an http:// URL: {url}")
+            });
+        };
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(i) if rest[i..].starts_with('?') => (&rest[..i], format!("/{}", &rest[i..])),
+            Some(i) => (&rest[..i], rest[i..].to_string()),
+            None => (rest, String::from("/")),
+        };
+        // `[::1]:8000`, the port is optional
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.ends_with(']') => (
+                host,
+                port.parse()
+                    .map_err(|_| eyre!("Invalid port in URL: {url}"))?,
+            ),
+            _ => (authority, 80),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err(eyre!("No host in URL: {url}"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+}
+
+/// Status and headers of an HTTP (or SHOUTcast `ICY 200 OK`) response, the body is read from `body`.
+struct Response {
+    /// Header names are lowercase
+    headers: Vec<(String, String)>,
+    body: BufReader<TcpStream>,
+}
+
+impl Response {
+    /// Sends a GET request asking for ICY metadata and follows redirects.
+    fn get(url: &str) -> Result<Self> {
+        let mut url = Url::parse(url)?;
+        for _ in 0..=MAX_REDIRECTS {
+            let response = Self::request(&url)?;
+            match response.status {
+                200..=299 => return Ok(response.response),
+                301 | 302 | 303 | 307 | 308 => {
+                    let location = response
+                        .response
+                        .header("location")
+                        .ok_or_else(|| eyre!("Redirect without a location"))?;
+                    url = match location.strip_prefix('/') {
+                        // relative to the same server
+                        Some(path) => Url {
+                            path: format!("/{path}"),
+                            ..url
+                        },
+                        None => Url::parse(location)?,
+                    };
+                }
+                status => return Err(eyre!("The server answered {status}")),
+            }
+        }
+        Err(eyre!("Too many redirects"))
+    }
+
+    fn request(url: &Url) -> Result<StatusResponse> {
+        let address = (url.host.as_str(), url.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| eyre!("Could not resolve {}", url.host))?;
+        let mut stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        // HTTP/1.0 so the body is not chunked
+        write!(
+            stream,
+            "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: soundscope/{}\r\nIcy-MetaData: 1\r\n\r\n",
+            url.path,
+            url.host,
+            env!("CARGO_PKG_VERSION")
+        )?;
+
+        let mut body = BufReader::new(stream);
+        let mut line = String::new();
+        body.read_line(&mut line)?;
+        let status = line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|s| s.parse::<u16>().ok())
+            .ok_or_else(|| eyre!("Not an HTTP response: {}", line.trim()))?;
+        let mut headers = Vec::new();
+        loop {
+            line.clear();
+            if body.read_line(&mut line)? == 0 {
+                break;
+            }
+            let Some((name, value)) = line.trim_end().split_once(':') else {
+                // the empty line before the body
+                break;
+            };
+            headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+        Ok(StatusResponse {
+            status,
+            response: Self { headers, body },
+        })
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+struct StatusResponse {
+    status: u16,
+    response: Response,
+}
+
+/// Removes the metadata blocks Icecast inserts every `metaint` bytes of audio.
+/// A block is a length byte (in units of 16 bytes) followed by e.g. `StreamTitle='...';`.
+struct IcyReader<R> {
+    inner: R,
+    /// 0 if the server sends no metadata
+    metaint: usize,
+    /// Audio bytes left before the next metadata block
+    remaining: usize,
+    tx: Sender<InputEvent>,
+}
+
+impl<R: Read> IcyReader<R> {
+    fn new(inner: R, metaint: usize, tx: Sender<InputEvent>) -> Self {
+        Self {
+            inner,
+            metaint,
+            remaining: metaint,
+            tx,
+        }
+    }
+
+    fn read_metadata(&mut self) -> std::io::Result<()> {
+        let mut len = [0u8];
+        self.inner.read_exact(&mut len)?;
+        let mut metadata = vec![0u8; len[0] as usize * 16];
+        self.inner.read_exact(&mut metadata)?;
+        if let Some(title) = stream_title(&metadata)
+            && let Err(_err) = self.tx.send(InputEvent::Title(title))
+        {
+            // the TUI has quit
+        }
+        self.remaining = self.metaint;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for IcyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.metaint == 0 {
+            return self.inner.read(buf);
+        }
+        if self.remaining == 0 {
+            self.read_metadata()?;
+        }
+        let len = buf.len().min(self.remaining);
+        let read = self.inner.read(&mut buf[..len])?;
+        self.remaining -= read;
+        Ok(read)
+    }
+}
+
+/// Extracts the title from `StreamTitle='Artist - Song';StreamUrl='';`, padded with zeros.
+/// Titles may contain quotes, so the value ends at the last `';`.
+fn stream_title(metadata: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(metadata);
+    let start = text.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &text[start..];
+    let end = rest.find("';StreamUrl=").or_else(|| rest.rfind("';"))?;
+    let title = rest[..end].trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam::channel::unbounded;
+    use std::{io::Cursor, net::TcpListener, thread};
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            Url::parse("http://radio.example:8000/live.mp3?x=1").unwrap(),
+            Url {
+                host: String::from("radio.example"),
+                port: 8000,
+                path: String::from("/live.mp3?x=1"),
+            }
+        );
+        let url = Url::parse("HTTP://[::1]").unwrap();
+        assert_eq!(
+            (url.host.as_str(), url.port, url.path.as_str()),
+            ("::1", 80, "/")
+        );
+        assert!(Url::parse("https://radio.example/live").is_err());
+        assert!(Url::parse("radio.example").is_err());
+        assert!(is_url("https://radio.example/live"));
+        assert!(!is_url("http.wav"));
+    }
+
+    #[test]
+    fn test_icy_reader() {
+        let mut stream = b"abcd".to_vec();
+        let metadata = b"StreamTitle='Rock 'n' Roll';StreamUrl='';";
+        stream.push(3);
+        stream.extend_from_slice(metadata);
+        stream.resize(stream.len() + 48 - metadata.len(), 0);
+        stream.extend_from_slice(b"efgh");
+        stream.push(0);
+        stream.extend_from_slice(b"ij");
+
+        let (tx, rx) = unbounded();
+        let mut audio = Vec::new();
+        IcyReader::new(Cursor::new(stream), 4, tx)
+            .read_to_end(&mut audio)
+            .unwrap();
+        assert_eq!(audio, b"abcdefghij");
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(InputEvent::Title(title)) if title == "Rock 'n' Roll"
+        ));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_stream_from_local_server() {
+        // 100 frames of 8 kHz mono WAV with ICY metadata after the header
+        let mut wav = b"RIFF".to_vec();
+        wav.extend_from_slice(&(36u32 + 200).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        for field in [16u32, 0x0001_0001, 8000, 16000, 0x0010_0002] {
+            wav.extend_from_slice(&field.to_le_bytes());
+        }
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&200u32.to_le_bytes());
+        let mut body = wav.clone();
+        body.push(2);
+        body.extend_from_slice(b"StreamTitle='Live';\0\0\0\0\0\0\0\0\0\0\0\0\0");
+        body.extend_from_slice(&[0u8; 200]);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let metaint = wav.len();
+        thread::spawn(move || {
+            let (mut client, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let _ = client.read(&mut request).unwrap();
+            write!(
+                client,
+                "ICY 200 OK\r\nicy-name: Test Radio\r\nicy-metaint: {metaint}\r\n\r\n"
+            )
+            .unwrap();
+            client.write_all(&body).unwrap();
+        });
+
+        let input = spawn(format!("http://127.0.0.1:{port}/live"));
+        let events: Vec<InputEvent> = input.events.iter().take(4).collect();
+        let titles: Vec<&str> = events
+            .iter()
+            .filter_map(|e| match e {
+                InputEvent::Title(title) => Some(title.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(titles, ["Test Radio", "Live"]);
+        assert!(events.iter().any(|e| matches!(
+            e,
+            InputEvent::Started { description, .. } if description == "PCM_S16LE, 8000 Hz, 1 ch"
+        )));
+        assert!(matches!(events.last(), Some(InputEvent::Ended)));
+    }
+}
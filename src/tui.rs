@@ -1,24 +1,45 @@
 //! This module contains the implementation of the terminal user interface (TUI) used to display audio analysis results.
 //! It uses `ratatui` under the hood.
 use crate::{
-    analyzer::Analyzer,
-    audio_capture::{self, AudioDevice, list_input_devs},
-    audio_player::{self, AudioFile, PlayerCommand},
+    alarms::{self, Alarms, Measurements, Panel},
+    analyzer::{Analyzer, ProgramMeter, SharedProgramMeter},
+    audio_capture::{
+        self, AudioDevice, SYSTEM_FORMAT, SystemSource, list_input_devs, list_system_sources,
+    },
+    audio_player::{
+        self, AudioFile, FileRequest, LoadProgress, POSITION_UPDATE_INTERVAL, PlayerCommand,
+        list_output_devs,
+    },
+    band_solo::{BandSolo, BandSoloKind},
     builtin_themes,
+    cli::Args,
+    config::{Config, config_dir},
+    file_watch::FileWatch,
+    loudness_log::{LoudnessLog, SharedLog},
+    markers::Markers,
+    monitor::MonitorMode,
+    playlist::Playlist,
+    probe,
+    raw_pcm::RawFormat,
+    recorder::{Recorder, SharedRecorder, SharedTrigger, Trigger},
+    stdin_input::{self, InputEvent, InputThread},
+    stream_input,
 };
 use cpal::{Stream, traits::StreamTrait as _};
-use crossbeam::channel::{Receiver, Sender};
+use crossbeam::channel::{Receiver, Sender, TryRecvError};
 use eyre::{Result, eyre};
 use ratatui::{
     DefaultTerminal,
-    crossterm::event::{Event, KeyCode, KeyEvent, MouseEvent, MouseEventKind, poll, read},
+    crossterm::event::{
+        Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind, poll, read,
+    },
     layout::Flex,
     prelude::*,
     style::{Color, Style, Stylize},
     text::{Line, Span, ToLine, ToSpan},
     widgets::{
-        Axis, Block, BorderType, Cell, Chart, Clear, Dataset, FrameExt, GraphType, List, ListItem,
-        Paragraph, Row, Table, Wrap,
+        Axis, Block, BorderType, Cell, Chart, Clear, Dataset, FrameExt, Gauge, GraphType, List,
+        ListItem, ListState, Paragraph, Row, Table, Wrap,
     },
 };
 use ratatui_explorer::{FileExplorer, FileExplorerBuilder};
@@ -26,23 +47,29 @@ use ringbuffer::{AllocRingBuffer, RingBuffer};
 use rodio::Source;
 use serde::Deserialize;
 use std::{
+    collections::HashMap,
     fmt::Display,
     fs::{self, File},
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 
 pub type RBuffer = Arc<Mutex<AllocRingBuffer<f32>>>;
 
 /// Files with extensions listed here will be shown in the explorer
-const SUPPORTED_FORMATS: [&str; 21] = [
+const SUPPORTED_FORMATS: [&str; 31] = [
     "wav", "wave", "aiff", "aif", "flac", // Uncompressed / Lossless
     "mp3", "mp2", "mp1", "mpa", "aac", // MPEG Audio
     "m4a", "m4b", "mp4", "m4r", "m4p", // MP4 / M4A Family (AAC / ALAC)
-    "ogg", "oga", "ogv", // OGG Family
-    "caf", "alac",  // Apple formats
+    "ogg", "oga", "ogv", "opus", // OGG Family
+    "caf", "alac", // Apple formats
+    "mkv", "mka", "webm", // Matroska
+    "wv",   // WavPack
+    "m3u", "m3u8", // Playlists
+    "cue",  // CUE sheets of single-file albums
+    "raw", "pcm",   // Headerless PCM, the format is asked for
     "theme", // Theme file
 ];
 
@@ -62,9 +89,24 @@ struct UI {
     show_waveform: bool,
     show_themes_list: bool,
     show_help_message: bool,
+    show_markers_list: bool,
+    show_chapters_list: bool,
+    show_playlist: bool,
+    show_output_devices_list: bool,
+    /// Audio tracks of a multi-track file
+    show_tracks_list: bool,
+    /// Technical information and tags of the current file
+    show_file_info: bool,
+    /// Alarms triggered in the live modes
+    show_alarm_log: bool,
     error_text: String,
     error_timer: Option<Instant>,
     device_name: String,
+    output_device_name: String,
+    /// Output devices shown in the output devices list
+    output_devices: Vec<String>,
+    /// Sources shown in the devices list in [`Mode::System`], listed when it is opened
+    system_sources: Vec<SystemSource>,
     waveform_window: f64,
     // Used to flash control elements when the button is pressed
     left_arrow_timer: Option<Instant>,
@@ -73,12 +115,32 @@ struct UI {
     minus_sign_timer: Option<Instant>,
     // Used to be able to hover fft chart to get more precise frequencies
     chart_rect: Option<Rect>,
+    // Used to be able to click on the waveform to seek
+    waveform_rect: Option<Rect>,
+    /// Frequency in Hz where dragging on the spectrum started
+    fft_drag_start: Option<f32>,
     /// Track if render is needed to avoid unnecessary redraws
     needs_render: bool,
     /// Selected theme index in themes list
     selected_theme_index: usize,
     /// Selected device index in devices list
     selected_device_index: usize,
+    /// Selected marker index in markers list
+    selected_marker_index: usize,
+    /// Selected chapter index in chapters list
+    selected_chapter_index: usize,
+    /// Selected track index in playlist
+    selected_playlist_index: usize,
+    /// Selected device index in output devices list
+    selected_output_device_index: usize,
+    /// Selected track index in audio tracks list
+    selected_track_index: usize,
+    /// First visible line of the file info popup
+    file_info_scroll: u16,
+    /// Time since the last reload as shown in the waveform title
+    reload_age: Option<String>,
+    /// The file plays while it is decoded, its loudness is known once decoding finishes
+    decoding: bool,
     /// Gain compensation in dB to normalize track to target LUFS
     fft_gain_compensation_db: f32,
 }
@@ -96,30 +158,55 @@ impl Default for UI {
             show_waveform: true,
             show_themes_list: false,
             show_help_message: false,
+            show_markers_list: false,
+            show_chapters_list: false,
+            show_playlist: false,
+            show_output_devices_list: false,
+            show_tracks_list: false,
+            show_file_info: false,
+            show_alarm_log: false,
             error_text: String::new(),
             error_timer: None,
             device_name: String::new(),
+            output_device_name: String::from("default"),
+            output_devices: Vec::new(),
+            system_sources: Vec::new(),
             waveform_window: 15.,
             left_arrow_timer: None,
             right_arrow_timer: None,
             plus_sign_timer: None,
             minus_sign_timer: None,
             chart_rect: None,
+            waveform_rect: None,
+            fft_drag_start: None,
             needs_render: true,
             selected_theme_index: 0,
             selected_device_index: 0,
+            selected_marker_index: 0,
+            selected_chapter_index: 0,
+            selected_playlist_index: 0,
+            selected_output_device_index: 0,
+            selected_track_index: 0,
+            file_info_scroll: 0,
+            reload_age: None,
+            decoding: false,
             fft_gain_compensation_db: 0.0,
         }
     }
 }
 
-/// Mode of the [App]. Currently, only Player and Microphone are supported.
-#[derive(Default)]
+/// Mode of the [App].
+#[derive(Default, Clone, Copy, PartialEq)]
 enum Mode {
     #[default]
     Player,
     Microphone,
-    _System,
+    /// Audio piped to stdin, see [`stdin_input`]
+    Stdin,
+    /// HTTP/Icecast stream, see [`stream_input`]
+    Stream,
+    /// What the computer is playing, see [`SystemSource`]
+    System,
 }
 
 impl Display for Mode {
@@ -127,7 +214,9 @@ impl Display for Mode {
         match self {
             Mode::Player => write!(f, "Player"),
             Mode::Microphone => write!(f, "Microphone"),
-            Mode::_System => write!(f, "System"),
+            Mode::Stdin => write!(f, "Stdin"),
+            Mode::Stream => write!(f, "Stream"),
+            Mode::System => write!(f, "System"),
         }
     }
 }
@@ -178,6 +267,8 @@ impl Theme {
             current_time <- fg,
             total_duration <- fg,
             waveform <- fg,
+            markers <- hl,
+            chapters <- fg,
             background <- bg,
             highlight <- hl,
         );
@@ -202,6 +293,7 @@ impl Theme {
             side_fft <- hl,
             background <- bg,
             highlight <- hl,
+            band_solo <- hl,
         );
 
         fill_fields!(self.explorer.
@@ -270,6 +362,10 @@ pub struct WaveformTheme {
     pub controls: Option<Color>,
     pub controls_highlight: Option<Color>,
     pub labels: Option<Color>,
+    /// Vertical lines of markers
+    pub markers: Option<Color>,
+    /// Vertical lines at chapter boundaries
+    pub chapters: Option<Color>,
     /// Background of the chart
     pub background: Option<Color>,
     pub highlight: Option<Color>,
@@ -288,6 +384,8 @@ pub struct FftTheme {
     /// Background of the chart
     pub background: Option<Color>,
     pub highlight: Option<Color>,
+    /// Edges of the soloed frequency range
+    pub band_solo: Option<Color>,
 }
 
 /// Used to define the theme for the LUFS display.
@@ -354,12 +452,40 @@ pub struct HelpMessageTheme {
     pub highlight: Option<Color>,
 }
 
-/// Settings for the [App]. Currently only the [Mode] is supported.
-#[derive(Default)]
+/// Settings for the [App]: the [Mode] and the playback controls.
 struct Settings {
     mode: Mode,
+    /// Playback volume, 1.0 is the original level
+    volume: f32,
+    muted: bool,
+    monitor_mode: MonitorMode,
+    /// Frequency range the playback is filtered to
+    band_solo: Option<BandSolo>,
+    /// Filter used for the next selected range
+    band_solo_kind: BandSoloKind,
+    /// Reload the loaded file when it is rewritten
+    watch_file: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            mode: Mode::default(),
+            volume: 1.,
+            muted: false,
+            monitor_mode: MonitorMode::default(),
+            band_solo: None,
+            band_solo_kind: BandSoloKind::default(),
+            watch_file: false,
+        }
+    }
 }
 
+/// Volume change of one key press
+const VOLUME_STEP: f32 = 0.1;
+/// Playback can be amplified up to this volume
+const MAX_VOLUME: f32 = 2.;
+
 /// FFT data for the UI.
 #[derive(Default)]
 struct FFTData {
@@ -369,7 +495,6 @@ struct FFTData {
 
 /// Waveform data for the UI.
 struct WaveForm {
-    audio_file_chart: Vec<(f64, f64)>,
     microphone_input_chart: Vec<(f64, f64)>,
     playhead: usize,
 }
@@ -377,13 +502,113 @@ struct WaveForm {
 impl Default for WaveForm {
     fn default() -> Self {
         Self {
-            audio_file_chart: vec![(0., 0.)],
             microphone_input_chart: vec![(0., 0.)],
             playhead: 0,
         }
     }
 }
 
+/// What the text typed into the [Prompt] is used for.
+enum PromptKind {
+    /// Name of a new marker at the given time (in seconds)
+    NewMarker(f64),
+    /// New name of the marker with the given index
+    RenameMarker(usize),
+    /// File name of the exported playlist
+    SavePlaylist,
+    /// Time to move the playhead to
+    GoTo,
+    /// Format of a headerless PCM file, which is then loaded with the given autoplay
+    RawFormat(PathBuf, bool),
+    /// URL of an HTTP/Icecast stream to analyze
+    StreamUrl,
+    /// Asks whether the finished recording is opened in the player
+    OpenRecording(PathBuf),
+}
+
+/// A file decoded in the background, the previous file keeps playing meanwhile.
+struct Loading {
+    title: String,
+    /// Requested audio track, `None` for the default one
+    track: Option<usize>,
+    /// Start playing when the file is loaded
+    autoplay: bool,
+    /// The loaded file is reloaded because it changed on disk
+    reload: bool,
+    progress: Arc<LoadProgress>,
+}
+
+/// Audio piped to stdin or streamed over HTTP. Its buffer and analyzer are swapped with the
+/// microphone ones while its mode is active, so all live modes share the analysis.
+struct LiveInput {
+    thread: InputThread,
+    buffer: RBuffer,
+    analyzer: Analyzer,
+    /// Format of the stream, empty until it is known
+    description: String,
+    /// Where the audio comes from, `stdin` or the URL of the stream
+    source: String,
+    /// Shown as the waveform title instead of the source, e.g. the song playing on a radio
+    title: Option<String>,
+    ended: bool,
+}
+
+impl LiveInput {
+    fn new(thread: InputThread, source: String) -> Self {
+        let mut buf = AllocRingBuffer::new(44100usize * 30);
+        buf.fill(0.0);
+        Self {
+            thread,
+            buffer: Arc::new(Mutex::new(buf)),
+            analyzer: Analyzer::default(),
+            description: String::new(),
+            source,
+            title: None,
+            ended: false,
+        }
+    }
+}
+
+/// A one-line text input shown as a popup.
+struct Prompt {
+    kind: PromptKind,
+    input: String,
+}
+
+impl Prompt {
+    fn new(kind: PromptKind) -> Self {
+        Self {
+            kind,
+            input: String::new(),
+        }
+    }
+
+    fn with_input(mut self, input: &str) -> Self {
+        self.input = input.to_string();
+        self
+    }
+
+    fn title(&self) -> &str {
+        match self.kind {
+            PromptKind::NewMarker(_) => "Marker name",
+            PromptKind::RenameMarker(_) => "Rename marker",
+            PromptKind::SavePlaylist => "Save playlist as",
+            PromptKind::GoTo => "Go to (mm:ss.mmm, samples or %)",
+            PromptKind::RawFormat(..) => "Raw PCM: format rate channels [offset]",
+            PromptKind::StreamUrl => "Stream URL (http:// only)",
+            PromptKind::OpenRecording(_) => "Open the recording? (Enter/Esc)",
+        }
+    }
+
+    /// Width of the popup, URLs are longer than the other inputs.
+    fn width(&self) -> u16 {
+        match self.kind {
+            PromptKind::StreamUrl => 64,
+            _ => 40,
+        }
+    }
+}
+
 /// `App` contains the necessary components for the application like senders, receivers, [`AudioFile`] data, [`UIsettings`].
 struct App {
     /// Audio file which is loaded into the player.
@@ -399,8 +624,19 @@ struct App {
     latest_captured_samples: RBuffer,
     /// The stream that captures the audio through input device
     audio_capture_stream: Option<Stream>,
+    /// Sample rate and channels of `audio_capture_stream`, recordings have the same format
+    capture_format: Option<(u32, u16)>,
+    /// Recording of the input device, written by the capture stream
+    recorder: SharedRecorder,
+    /// Triggered recording of the input device, armed with `T`
+    trigger: SharedTrigger,
+    /// Loudness logs of the input device and of the system output, started with `l`
+    device_log: SharedLog,
+    system_log: SharedLog,
     /// Sends commands like pause and play to the player.
     player_command_tx: Sender<PlayerCommand>,
+    /// Output device being opened by the player, shown and saved once the player confirms it
+    output_device_switch: Option<(String, Receiver<bool>)>,
     /// Gets playback position of an audio file when the mode is player
     /// for an analyzer to know what samples to analyze.
     playback_position_rx: Receiver<usize>,
@@ -410,6 +646,8 @@ struct App {
     file_analyzer: Analyzer,
     /// Used to get LUFS of microphone input.
     device_analyzer: Analyzer,
+    /// Integrated loudness of the microphone input, fed by the capture stream
+    program_meter: SharedProgramMeter,
 
     // Charts data
     /// Data used to render FFT chart.
@@ -426,6 +664,34 @@ struct App {
     current_directory: PathBuf,
     // Used to print info about fft chart when it's hovered
     mouse_position: Option<(u16, u16)>,
+    /// Markers of the loaded file, persisted in a sidecar file.
+    markers: Markers,
+    /// Text input popup, when it is shown every key goes to it.
+    prompt: Option<Prompt>,
+    /// File being loaded, shown as a progress bar.
+    loading: Option<Loading>,
+    /// Audio piped to stdin, `None` if the app was not started with `-`
+    stdin: Option<LiveInput>,
+    /// HTTP/Icecast stream, opened from the command line or with `U`
+    stream: Option<LiveInput>,
+    /// Capture of the system output, started when [`Mode::System`] is first entered
+    system: Option<LiveInput>,
+    /// Audio track chosen on the command line for the files given there
+    cli_tracks: HashMap<PathBuf, usize>,
+    /// Formats of headerless PCM files, from the command line or the raw format prompt
+    raw_formats: HashMap<PathBuf, RawFormat>,
+    /// Detects when the loaded file is rewritten
+    file_watch: Option<FileWatch>,
+    /// When the loaded file was last reloaded
+    last_reload: Option<Instant>,
+    /// Files queued for playback.
+    playlist: Playlist,
+    /// Files selected in the explorer with Space, opened together as a playlist.
+    explorer_selection: Vec<PathBuf>,
+    /// Settings loaded from `config.toml`.
+    config: Config,
+    /// Threshold alarms of the live modes, from the config
+    alarms: Alarms,
 }
 
 macro_rules! help_message_row {
@@ -453,11 +719,18 @@ impl App {
             audio_file_rx,
             latest_captured_samples,
             audio_capture_stream: None,
+            capture_format: None,
+            recorder: SharedRecorder::default(),
+            trigger: SharedTrigger::default(),
+            device_log: SharedLog::default(),
+            system_log: SharedLog::default(),
             player_command_tx,
+            output_device_switch: None,
             playback_position_rx,
             error_rx,
             file_analyzer: Analyzer::default(),
             device_analyzer: Analyzer::default(),
+            program_meter: SharedProgramMeter::default(),
             fft_data: FFTData::default(),
             waveform: WaveForm::default(),
             lufs: [-100.; 300],
@@ -469,6 +742,20 @@ impl App {
             ui: UI::default(),
             current_directory: PathBuf::from(""),
             mouse_position: None,
+            markers: Markers::default(),
+            prompt: None,
+            loading: None,
+            stdin: None,
+            stream: None,
+            system: None,
+            cli_tracks: HashMap::new(),
+            raw_formats: HashMap::new(),
+            file_watch: None,
+            last_reload: None,
+            playlist: Playlist::default(),
+            explorer_selection: Vec::new(),
+            config: Config::default(),
+            alarms: Alarms::default(),
         })
     }
 
@@ -532,7 +819,10 @@ impl App {
             .split(area);
 
         if self.ui.show_waveform {
+            self.ui.waveform_rect = Some(vertical_chunks[0]);
             self.render_waveform(f, vertical_chunks[0]);
+        } else {
+            self.ui.waveform_rect = None;
         }
 
         // draw bottom windows
@@ -583,16 +873,61 @@ impl App {
             let area = Self::get_popup_area_with_percentage(area, 50, 70);
             f.render_widget(Clear, area);
             f.render_widget_ref(self.explorer.widget(), area);
+            self.render_explorer_selection(f, area);
         }
         if self.ui.show_devices_list {
-            self.render_devices_list(f);
+            let title = if self.settings.mode == Mode::System {
+                "System output sources"
+            } else {
+                "Devices"
+            };
+            let devs = self.device_names();
+            self.render_devices_list(f, title, &devs, self.ui.selected_device_index);
+        }
+        if self.ui.show_tracks_list {
+            let tracks: Vec<String> = self
+                .audio_file
+                .tracks()
+                .iter()
+                .map(|t| t.to_string())
+                .collect();
+            self.render_devices_list(f, "Audio tracks", &tracks, self.ui.selected_track_index);
+        }
+        if self.ui.show_output_devices_list {
+            self.render_devices_list(
+                f,
+                "Output devices",
+                &self.ui.output_devices,
+                self.ui.selected_output_device_index,
+            );
         }
         if self.ui.show_themes_list {
             self.render_themes_list(f);
         }
+        if self.ui.show_file_info {
+            self.render_file_info(f);
+        }
+        if self.ui.show_alarm_log {
+            self.render_alarm_log(f);
+        }
         if self.ui.show_help_message {
             self.render_help_message(f);
         }
+        if self.ui.show_markers_list {
+            self.render_markers_list(f);
+        }
+        if self.ui.show_chapters_list {
+            self.render_chapters_list(f);
+        }
+        if self.ui.show_playlist {
+            self.render_playlist(f);
+        }
+        if self.loading.is_some() {
+            self.render_loading(f);
+        }
+        if self.prompt.is_some() {
+            self.render_prompt(f);
+        }
     }
 
     fn render_empty_window(&mut self, frame: &mut Frame, area: Rect) {
@@ -638,6 +973,8 @@ impl App {
         let ct = s.fg(self.ui.theme.waveform.current_time.unwrap());
         let td = s.fg(self.ui.theme.waveform.total_duration.unwrap());
         let wv = s.fg(self.ui.theme.waveform.waveform.unwrap());
+        let mk = s.fg(self.ui.theme.waveform.markers.unwrap());
+        let ch = s.fg(self.ui.theme.waveform.chapters.unwrap());
 
         // playhead is just a function that looks like a vertical line
         let samples_in_one_ms = self.audio_file.sample_rate() / 1000;
@@ -661,36 +998,49 @@ impl App {
         let total_min = total_duration / 60;
         let total_sec = total_duration % 60;
 
-        let (x_min, x_max) = match self.settings.mode {
-            Mode::Microphone | Mode::_System => {
-                let window_millis = self.ui.waveform_window as usize * 1000;
-                (15000. - window_millis as f64, 15000.)
-            }
-            Mode::Player => {
-                let half_window = self.ui.waveform_window * 500.;
-                let playhead_millis = playhead_ms as f64;
-                let max_x = self.waveform.audio_file_chart.len() as f64 / 2.;
-                let min_bound = (playhead_millis - half_window)
-                    .min(max_x - self.ui.waveform_window * 1000.)
-                    .max(0.);
-                let max_bound = (playhead_millis + half_window)
-                    .min(max_x)
-                    .max(self.ui.waveform_window * 1000.);
-                (min_bound, max_bound)
-            }
+        let (x_min, x_max) = self.waveform_x_bounds();
+
+        // only the visible part, the overview of a long file has millions of points
+        let overview = if matches!(self.settings.mode, Mode::Player) {
+            self.audio_file
+                .overview(x_min as usize * 2..(x_max as usize + 1) * 2)
+        } else {
+            Vec::new()
+        };
+
+        // vertical lines of markers
+        let marker_lines: Vec<[(f64, f64); 2]> = if matches!(self.settings.mode, Mode::Player) {
+            self.markers
+                .iter()
+                .map(|m| [(m.time * 1000., 1.), (m.time * 1000., -1.)])
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // vertical lines of chapter boundaries, the start of the file is not a boundary
+        let chapter_lines: Vec<[(f64, f64); 2]> = if matches!(self.settings.mode, Mode::Player) {
+            self.audio_file
+                .chapters()
+                .iter()
+                .filter(|c| c.start > 0.)
+                .map(|c| [(c.start * 1000., 1.), (c.start * 1000., -1.)])
+                .collect()
+        } else {
+            Vec::new()
         };
 
         // make datasets
         // first one to render a waveform
         // the other one to render the playhead
-        let datasets = vec![
+        let mut datasets = vec![
             Dataset::default()
                 .marker(symbols::Marker::Braille)
                 .graph_type(GraphType::Line)
                 .style(wv)
                 .data({
                     if matches!(self.settings.mode, Mode::Player) {
-                        &self.waveform.audio_file_chart
+                        &overview
                     } else {
                         &self.waveform.microphone_input_chart
                     }
@@ -701,18 +1051,129 @@ impl App {
                 .style(pl)
                 .data(&playhead_chart),
         ];
+        datasets.extend(chapter_lines.iter().map(|line| {
+            Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(ch)
+                .data(line)
+        }));
+        datasets.extend(marker_lines.iter().map(|line| {
+            Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(mk)
+                .data(line)
+        }));
 
         // render chart
-        let title = self.audio_file.title();
+        let title = match self.playlist.current() {
+            _ if let Some(input) = self.live_input(self.settings.mode) => {
+                input.title.clone().unwrap_or_else(|| input.source.clone())
+            }
+            Some(i) if self.playlist.len() > 1 => {
+                format!(
+                    "{} ({}/{})",
+                    self.audio_file.title(),
+                    i + 1,
+                    self.playlist.len()
+                )
+            }
+            _ => self.audio_file.title().to_string(),
+        };
+        // playback controls are only shown when they differ from the defaults
+        let mut playback_text = Vec::new();
+        if matches!(self.settings.mode, Mode::Player) {
+            if self.settings.monitor_mode != MonitorMode::Stereo {
+                playback_text.push(format!("monitor: {}", self.settings.monitor_mode));
+            }
+            if self.settings.muted {
+                playback_text.push(String::from("muted"));
+            } else if self.settings.volume != 1. {
+                playback_text.push(format!("vol: {:.0}%", self.settings.volume * 100.));
+            }
+            if self.settings.watch_file {
+                playback_text.push(
+                    self.ui
+                        .reload_age
+                        .clone()
+                        .unwrap_or(String::from("watching")),
+                );
+            }
+            if self.ui.decoding {
+                playback_text.push(match self.audio_file.decoded_ratio() {
+                    Some(ratio) => format!("decoding {:.0}%", ratio * 100.),
+                    None => String::from("decoding"),
+                });
+            }
+        }
+        let title = if playback_text.is_empty() {
+            title
+        } else {
+            format!("{title} [{}]", playback_text.join(", "))
+        };
         let mode_text = self.settings.mode.to_span().style(lb);
         let upper_right_title = match self.settings.mode {
             Mode::Player => Line::from(vec![
+                "o".bold().style(hl),
+                "utput: ".to_span().style(lb),
+                self.ui.output_device_name.to_span().style(lb),
+                " ".to_span(),
                 "m".bold().style(hl),
                 "ode: ".to_span().style(lb),
                 mode_text,
             ])
             .right_aligned(),
-            _ => Line::from(vec![
+            Mode::Stdin | Mode::Stream => {
+                let input = self.live_input(self.settings.mode);
+                let description = match input.map(|s| s.description.as_str()) {
+                    Some("") | None => "waiting for data",
+                    Some(description) => description,
+                };
+                let ended = if input.is_some_and(|s| s.ended) {
+                    " (ended)"
+                } else {
+                    ""
+                };
+                Line::from(vec![
+                    Span::styled(format!("input: {description}{ended}"), lb),
+                    " ".to_span(),
+                    "m".bold().style(hl),
+                    "ode: ".to_span().style(lb),
+                    mode_text,
+                ])
+                .right_aligned()
+            }
+            Mode::System => {
+                let name = self.system.as_ref().map_or("none", |s| s.source.as_str());
+                let ended = if self.system.as_ref().is_some_and(|s| s.ended) {
+                    " (ended)"
+                } else {
+                    ""
+                };
+                Line::from(vec![
+                    self.log_status(&self.system_log)
+                        .map(|status| Span::styled(format!("{status} "), hl))
+                        .unwrap_or_default(),
+                    "d".bold().style(hl),
+                    Span::styled(format!("evice: {name}{ended}"), lb),
+                    " ".to_span(),
+                    "m".bold().style(hl),
+                    "ode: ".to_span().style(lb),
+                    mode_text,
+                ])
+                .right_aligned()
+            }
+            Mode::Microphone => Line::from(vec![
+                self.recording_elapsed()
+                    .map(|elapsed| Span::styled(format!("● rec {} ", format_mm_ss(elapsed)), hl))
+                    .unwrap_or_default(),
+                self.trigger_status()
+                    .map(|status| Span::styled(format!("{status} "), hl))
+                    .unwrap_or_default(),
+                self.log_status(&self.device_log)
+                    .map(|status| Span::styled(format!("{status} "), hl))
+                    .unwrap_or_default(),
                 "d".bold().style(hl),
                 "evice: ".to_span().style(lb),
                 self.ui.device_name.to_span().style(lb),
@@ -739,7 +1200,7 @@ impl App {
                             .right_aligned(),
                     )
                     .title(upper_right_title)
-                    .style(bd),
+                    .style(self.alarm_style(Panel::Waveform, bd)),
             )
             .style(wv)
             .x_axis(Axis::default().bounds([x_min, x_max]))
@@ -748,6 +1209,156 @@ impl App {
         frame.render_widget(chart, area);
     }
 
+    /// Returns the visible part of the waveform in milliseconds.
+    fn waveform_x_bounds(&self) -> (f64, f64) {
+        match self.settings.mode {
+            Mode::Microphone | Mode::Stdin | Mode::Stream | Mode::System => {
+                let window_millis = self.ui.waveform_window as usize * 1000;
+                (15000. - window_millis as f64, 15000.)
+            }
+            Mode::Player => {
+                let half_window = self.ui.waveform_window * 500.;
+                let playhead_millis = (self.waveform.playhead as f64
+                    / self.audio_file.sample_rate() as f64
+                    * 1000.) as u64 as f64;
+                let max_x = self.audio_file.overview_len() as f64 / 2.;
+                let min_bound = (playhead_millis - half_window)
+                    .min(max_x - self.ui.waveform_window * 1000.)
+                    .max(0.);
+                let max_bound = (playhead_millis + half_window)
+                    .min(max_x)
+                    .max(self.ui.waveform_window * 1000.);
+                (min_bound, max_bound)
+            }
+        }
+    }
+
+    /// Maps a terminal column inside the waveform chart to the time in milliseconds.
+    /// Returns `None` if the column is outside of the chart.
+    fn map_column_to_waveform_millis(
+        column: u16,
+        rect: Rect,
+        x_min: f64,
+        x_max: f64,
+    ) -> Option<f64> {
+        // the chart occupies the whole rectangle except for the borders
+        let left = rect.x + 1;
+        let width = rect.width.checked_sub(2).filter(|&w| w > 1)?;
+        if column < left || column >= left + width {
+            return None;
+        }
+        let t = (column - left) as f64 / (width - 1) as f64;
+        Some(x_min + t * (x_max - x_min))
+    }
+
+    fn in_waveform_chart(&self, m: MouseEvent) -> bool {
+        self.ui.waveform_rect.is_some_and(|r| {
+            r.x < m.column
+                && m.column < r.x + r.width - 1
+                && r.y < m.row
+                && m.row < r.y + r.height - 1
+        })
+    }
+
+    fn handle_mouse(&mut self, m: MouseEvent) {
+        match m.kind {
+            MouseEventKind::Moved if self.in_fft_chart(m) => {
+                self.mouse_position = Some((m.column, m.row));
+            }
+            // click or drag on the waveform to seek
+            MouseEventKind::Down(MouseButton::Left) | MouseEventKind::Drag(MouseButton::Left)
+                if self.in_waveform_chart(m)
+                    && self.is_file_selected
+                    && matches!(self.settings.mode, Mode::Player) =>
+            {
+                self.mouse_position = None;
+                let (x_min, x_max) = self.waveform_x_bounds();
+                let Some(millis) = Self::map_column_to_waveform_millis(
+                    m.column,
+                    self.ui.waveform_rect.unwrap(),
+                    x_min,
+                    x_max,
+                ) else {
+                    return;
+                };
+                let pos = Duration::from_secs_f64(millis.max(0.) / 1000.);
+                let cmd = PlayerCommand::SeekTo(pos);
+                // while scrubbing drop positions the player has not caught up with yet
+                let sent = if matches!(m.kind, MouseEventKind::Drag(_)) {
+                    self.player_command_tx.try_send(cmd).is_ok()
+                } else {
+                    self.player_command_tx.send(cmd).is_ok()
+                };
+                if sent {
+                    self.lufs = [-100.; 300];
+                    self.file_analyzer.reset();
+                }
+            }
+            // drag on the spectrum to solo a frequency range
+            MouseEventKind::Down(MouseButton::Left)
+                if self.in_fft_chart(m) && matches!(self.settings.mode, Mode::Player) =>
+            {
+                self.ui.fft_drag_start = Some(self.map_column_to_hz(m.column));
+            }
+            MouseEventKind::Drag(MouseButton::Left) | MouseEventKind::Up(MouseButton::Left)
+                if self.ui.fft_drag_start.is_some() =>
+            {
+                let start = self.ui.fft_drag_start.unwrap();
+                let end = self.map_column_to_hz(m.column);
+                let is_drag = matches!(m.kind, MouseEventKind::Drag(_));
+                if is_drag {
+                    self.mouse_position = Some((m.column, m.row));
+                } else {
+                    self.ui.fft_drag_start = None;
+                }
+                // a click without dragging keeps the current range
+                if end.max(start) / end.min(start) > 1.01 {
+                    self.set_band_solo(
+                        Some(BandSolo {
+                            low: end.min(start),
+                            high: end.max(start),
+                            kind: self.settings.band_solo_kind,
+                        }),
+                        is_drag,
+                    );
+                }
+            }
+            // zoom the waveform with the scroll wheel
+            MouseEventKind::ScrollUp if self.in_waveform_chart(m) => self.zoom_waveform_in(),
+            MouseEventKind::ScrollDown if self.in_waveform_chart(m) => self.zoom_waveform_out(),
+            _ => self.mouse_position = None,
+        }
+    }
+
+    /// Sends the band solo to the player. While dragging, ranges the player
+    /// has not caught up with yet are dropped.
+    fn set_band_solo(&mut self, band: Option<BandSolo>, is_drag: bool) {
+        let cmd = PlayerCommand::SetBandSolo(band);
+        let sent = if is_drag {
+            self.player_command_tx.try_send(cmd).is_ok()
+        } else {
+            self.player_command_tx.send(cmd).is_ok()
+        };
+        if sent {
+            self.settings.band_solo = band;
+        }
+    }
+
+    fn zoom_waveform_in(&mut self) {
+        self.ui.plus_sign_timer = Some(Instant::now());
+        self.ui.waveform_window = f64::max(self.ui.waveform_window - 1., 1.);
+    }
+
+    fn zoom_waveform_out(&mut self) {
+        let bound = if self.audio_file.duration().as_secs_f64() < 15. {
+            self.audio_file.duration().as_secs_f64()
+        } else {
+            15.
+        };
+        self.ui.minus_sign_timer = Some(Instant::now());
+        self.ui.waveform_window = f64::min(self.ui.waveform_window + 1., bound);
+    }
+
     fn get_flashing_controls_text(&self) -> Line<'_> {
         let t = 100;
         let s = Style::default()
@@ -792,6 +1403,7 @@ impl App {
         let mf = s.fg(self.ui.theme.fft.mid_fft.unwrap());
         let sf = s.fg(self.ui.theme.fft.side_fft.unwrap());
         let hl = s.fg(self.ui.theme.fft.highlight.unwrap());
+        let bs = s.fg(self.ui.theme.fft.band_solo.unwrap());
         let x_labels = vec![
             Span::styled("20Hz", fg),
             Span::styled("632.46Hz", fg),
@@ -820,7 +1432,26 @@ impl App {
             vec![(-1000.0, -1000.0)]
         };
 
-        let datasets = vec![
+        // vertical lines at the edges of the soloed range
+        let band_solo_lines: Vec<[(f64, f64); 2]> = self
+            .settings
+            .band_solo
+            .iter()
+            .flat_map(|band| [band.low, band.high])
+            .map(|hz| {
+                let x = map_hz_to_chart_x(hz);
+                [(x, FFT_LOWER_BOUND), (x, FFT_UPPER_BOUND)]
+            })
+            .collect();
+        let band_solo_title = match self.settings.band_solo {
+            Some(band) if band.kind == BandSoloKind::BandPass => {
+                format!(" solo {}-{}", format_hz(band.low), format_hz(band.high))
+            }
+            Some(band) => format!(" boost {}", format_hz(band.center())),
+            None => String::new(),
+        };
+
+        let mut datasets = vec![
             Dataset::default()
                 .marker(symbols::Marker::Braille)
                 // GraphType::Area is not part of the ratatui yet,
@@ -842,15 +1473,23 @@ impl App {
                 // .fill_to_y(FFT_LOWER_BOUND)
                 .data(&side_fft_normalized),
         ];
+        datasets.extend(band_solo_lines.iter().map(|line| {
+            Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(bs)
+                .data(line)
+        }));
 
         let chart = Chart::new(datasets)
             .block(
                 Block::bordered()
                     .border_type(BorderType::Rounded)
-                    .style(bd)
+                    .style(self.alarm_style(Panel::Spectrum, bd))
                     .title(vec![
                         "²".to_span().style(hl).bold(),
                         "spectrum".to_span().style(lb).bold(),
+                        Span::styled(band_solo_title, bs),
                     ])
                     .title({
                         let mut mid = if self.ui.show_mid_fft {
@@ -914,7 +1553,16 @@ impl App {
             .map(|(x, &y)| (x as f64, y))
             .collect::<Vec<(f64, f64)>>();
 
-        let integrated_lufs = match self.file_analyzer.get_integrated_lufs() {
+        // the microphone shows its programme loudness since the last reset
+        let program_meter = self.program_meter.clone();
+        let program_meter = program_meter.lock().unwrap();
+        let program_meter = program_meter
+            .as_ref()
+            .filter(|_| self.settings.mode == Mode::Microphone);
+        let integrated_lufs = match program_meter.map_or_else(
+            || self.file_analyzer.get_integrated_lufs(),
+            ProgramMeter::get_integrated_lufs,
+        ) {
             Ok(lufs) => lufs,
             Err(err) => {
                 self.handle_error(format!("Error getting integrated LUFS: {err}"));
@@ -947,7 +1595,10 @@ impl App {
         let short_term_lufs_text = short_term.to_span().style(nb) + " LUFS".to_span();
 
         // get true peak
-        let (tp_left, tp_right) = match self.file_analyzer.get_true_peak() {
+        let (tp_left, tp_right) = match program_meter.map_or_else(
+            || self.file_analyzer.get_true_peak(),
+            ProgramMeter::get_true_peak,
+        ) {
             Ok((tp_left, tp_right)) => (tp_left, tp_right),
             Err(err) => {
                 self.handle_error(format!("Error getting true peak: {err}"));
@@ -966,7 +1617,10 @@ impl App {
         ];
 
         //get range text
-        let range = match self.file_analyzer.get_loudness_range() {
+        let range = match program_meter.map_or_else(
+            || self.file_analyzer.get_loudness_range(),
+            ProgramMeter::get_loudness_range,
+        ) {
             Ok(range) => range,
             Err(err) => {
                 self.handle_error(format!("Error getting loudness range: {err}"));
@@ -980,7 +1634,7 @@ impl App {
             .block(
                 Block::bordered()
                     .border_type(BorderType::Rounded)
-                    .style(bd)
+                    .style(self.alarm_style(Panel::Loudness, bd))
                     .title_alignment(Alignment::Center)
                     .title("Short term".bold()),
             )
@@ -991,19 +1645,27 @@ impl App {
                     .border_type(BorderType::Rounded)
                     .style(bd)
                     .title_alignment(Alignment::Center)
-                    .title("Integrated".bold()),
+                    .title("Integrated".bold())
+                    .title_bottom(
+                        program_meter
+                            .map(|meter| {
+                                let paused = if meter.is_paused() { "paused " } else { "" };
+                                format!("{paused}{}", format_mm_ss(meter.elapsed()))
+                            })
+                            .unwrap_or_default(),
+                    ),
             )
             .alignment(Alignment::Center);
         let true_peak_paragraph = Paragraph::new(true_peak_text)
             .block(
                 Block::bordered()
                     .border_type(BorderType::Rounded)
-                    .style(bd)
+                    .style(self.alarm_style(Panel::TruePeak, bd))
                     .title_alignment(Alignment::Center)
                     .title("True Peak".bold()),
             )
             .alignment(Alignment::Center)
-            .style(bd);
+            .style(self.alarm_style(Panel::TruePeak, bd));
         let range_paragraph = Paragraph::new(range_text)
             .block(
                 Block::bordered()
@@ -1032,7 +1694,7 @@ impl App {
             .block(
                 Block::bordered()
                     .border_type(BorderType::Rounded)
-                    .style(bd)
+                    .style(self.alarm_style(Panel::Loudness, bd))
                     .title(vec![
                         "³".to_span().style(hl).bold(),
                         "lufs".to_span().style(lb).bold(),
@@ -1054,7 +1716,7 @@ impl App {
         f.render_widget(chart, layout[0]);
     }
 
-    fn render_devices_list(&self, f: &mut Frame) {
+    fn render_devices_list(&self, f: &mut Frame, title: &str, devs: &[String], selected: usize) {
         let s = Style::default()
             .fg(self.ui.theme.devices.foreground.unwrap())
             .bg(self.ui.theme.devices.background.unwrap());
@@ -1062,14 +1724,13 @@ impl App {
         let hl = s.fg(self.ui.theme.devices.highlight.unwrap());
         let area = Self::get_popup_area_with_percentage(f.area(), 20, 30);
         f.render_widget(Clear, area);
-        let devs = list_input_devs();
         let list_items: Vec<ListItem> = devs
             .iter()
             .enumerate()
-            .map(|(i, (name, _dev))| {
+            .map(|(i, name)| {
                 let num = format!("[{}]", i + 1);
                 let name = format!(" {name}");
-                let is_selected = i == self.ui.selected_device_index;
+                let is_selected = i == selected;
 
                 let item_style = if is_selected {
                     hl.bg(self.ui.theme.devices.background.unwrap())
@@ -1085,7 +1746,7 @@ impl App {
         let list = List::new(list_items).style(s).block(
             Block::bordered()
                 .border_type(BorderType::Rounded)
-                .title("Devices")
+                .title(title)
                 .style(bd),
         );
 
@@ -1163,57 +1824,380 @@ impl App {
         f.render_widget(list, area);
     }
 
-    fn render_fft_info(&self, f: &mut Frame<'_>, x: u16, y: u16) {
-        let rect_width = self.ui.chart_rect.unwrap().width;
-        let rect_height = self.ui.chart_rect.unwrap().height;
-
-        let left_bound = 8;
-        let right_bound = rect_width - 1;
-        let upper_bound = self.ui.chart_rect.unwrap().y + 1;
-        let lower_bound = upper_bound + rect_height - 4;
+    fn render_markers_list(&self, f: &mut Frame) {
+        let s = Style::default()
+            .fg(self.ui.theme.devices.foreground.unwrap())
+            .bg(self.ui.theme.devices.background.unwrap());
+        let bd = s.fg(self.ui.theme.devices.borders.unwrap());
+        let hl = s.fg(self.ui.theme.devices.highlight.unwrap());
+        let area = Self::get_popup_area_with_percentage(f.area(), 40, 50);
+        f.render_widget(Clear, area);
 
-        let (hz, db) = Self::map_mouse_position_to_chart_point(
-            x - left_bound,
-            right_bound - left_bound,
-            y - upper_bound,
-            lower_bound - upper_bound,
+        let list_items: Vec<ListItem> = self
+            .markers
+            .iter()
+            .enumerate()
+            .map(|(i, marker)| {
+                let time = format!("{} ", format_time(marker.time));
+                let item_style = if i == self.ui.selected_marker_index {
+                    hl
+                } else {
+                    s
+                };
+                ListItem::from(
+                    time.bold().style(item_style) + Span::styled(marker.name.as_str(), item_style),
+                )
+            })
+            .collect();
+        let list = List::new(list_items).style(s).block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title("Markers")
+                .title_bottom(
+                    Line::from("Enter: jump  r: rename  x: delete")
+                        .style(bd)
+                        .centered(),
+                )
+                .style(bd),
         );
-        let hz_text = format!("{hz:.2} Hz");
-        let db_text = format!("{db:.2} Db");
 
-        let width = hz_text.len().max(db_text.len()) as u16 + 2;
-        let height = 4;
+        f.render_widget(list, area);
+    }
 
-        let text = db_text + "\n" + &hz_text;
+    fn render_file_info(&self, f: &mut Frame) {
+        let s = Style::default()
+            .fg(self.ui.theme.help.foreground.unwrap())
+            .bg(self.ui.theme.help.background.unwrap());
+        let bd = s.fg(self.ui.theme.help.borders.unwrap());
+        let hl = s.fg(self.ui.theme.help.highlight.unwrap());
+        let area = Self::get_popup_area_with_percentage(f.area(), 50, 60);
+        f.render_widget(Clear, area);
 
-        let x = u16::min(x, right_bound - width) + 1;
-        let y = u16::min(y, lower_bound - height) + 1;
-        let area = Rect::new(x, y, width, height);
+        let lines: Vec<Line> = self
+            .audio_file
+            .info()
+            .rows()
+            .into_iter()
+            .map(|(key, value)| Span::styled(format!("{key}: "), hl) + Span::styled(value, s))
+            .collect();
+        let paragraph = Paragraph::new(lines)
+            .style(s)
+            .wrap(Wrap { trim: false })
+            .scroll((self.ui.file_info_scroll, 0))
+            .block(
+                Block::bordered()
+                    .border_type(BorderType::Rounded)
+                    .title(format!("Info: {}", self.audio_file.title()))
+                    .title_bottom(Line::from("Up/Down: scroll").style(bd).centered())
+                    .style(bd),
+            );
+
+        f.render_widget(paragraph, area);
+    }
+
+    fn render_alarm_log(&self, f: &mut Frame) {
+        let s = Style::default()
+            .fg(self.ui.theme.help.foreground.unwrap())
+            .bg(self.ui.theme.help.background.unwrap());
+        let bd = s.fg(self.ui.theme.help.borders.unwrap());
+        let hl = s.fg(self.ui.theme.help.highlight.unwrap());
+        let area = Self::get_popup_area_with_percentage(f.area(), 50, 60);
         f.render_widget(Clear, area);
-        f.render_widget(
-            Paragraph::new(text).block(
-                Block::bordered().border_type(BorderType::Rounded).style(
-                    Style::default().fg(self.ui.theme.global.foreground).bg(self
-                        .ui
-                        .theme
-                        .global
-                        .background),
-                ),
-            ),
-            area,
+
+        // newest first
+        let lines: Vec<Line> = if self.alarms.log().is_empty() {
+            vec![Line::styled("No alarm so far", s)]
+        } else {
+            self.alarms
+                .log()
+                .iter()
+                .rev()
+                .map(|entry| {
+                    Span::styled(format!("{} ", entry.time), hl)
+                        + Span::styled(entry.message.as_str(), s)
+                })
+                .collect()
+        };
+        let paragraph = Paragraph::new(lines).style(s).block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title("Alarm log (UTC)")
+                .style(bd),
+        );
+
+        f.render_widget(paragraph, area);
+    }
+
+    /// Border style of a panel, flashing in the error color while an alarm on it is active.
+    fn alarm_style(&self, panel: Panel, style: Style) -> Style {
+        let millis = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        if self.alarms.is_active(panel) && (millis / 500).is_multiple_of(2) {
+            style.fg(self.ui.theme.error.borders.unwrap())
+        } else {
+            style
+        }
+    }
+
+    fn render_chapters_list(&self, f: &mut Frame) {
+        let s = Style::default()
+            .fg(self.ui.theme.devices.foreground.unwrap())
+            .bg(self.ui.theme.devices.background.unwrap());
+        let bd = s.fg(self.ui.theme.devices.borders.unwrap());
+        let hl = s.fg(self.ui.theme.devices.highlight.unwrap());
+        let area = Self::get_popup_area_with_percentage(f.area(), 60, 60);
+        f.render_widget(Clear, area);
+
+        let db = |value: Option<f64>, unit: &str| {
+            value.map_or_else(|| String::from("-"), |v| format!("{v:.1} {unit}"))
+        };
+        let current = self.current_chapter();
+        let list_items: Vec<ListItem> = self
+            .audio_file
+            .chapters()
+            .iter()
+            .enumerate()
+            .map(|(i, chapter)| {
+                let item_style = if i == self.ui.selected_chapter_index {
+                    hl
+                } else {
+                    s
+                };
+                let playing = if current == Some(i) { "> " } else { "  " };
+                let stats = match chapter.loudness {
+                    Some(loudness) => format!(
+                        "  {}  LRA {}  peak {}",
+                        db(loudness.integrated, "LUFS"),
+                        db(loudness.range, "LU"),
+                        db(Some(loudness.peak), "dBFS"),
+                    ),
+                    None => String::new(),
+                };
+                ListItem::from(
+                    Span::styled(playing, item_style)
+                        + format!("{} ", format_time(chapter.start))
+                            .bold()
+                            .style(item_style)
+                        + Span::styled(chapter.title.as_str(), item_style)
+                        + Span::styled(stats, item_style),
+                )
+            })
+            .collect();
+        // the loudness of the whole file, i.e. the album loudness of a CUE sheet
+        let title = match (list_items.is_empty(), self.audio_file.integrated_lufs()) {
+            (true, _) => String::from("Chapters (none)"),
+            (false, Some(lufs)) => format!("Chapters (whole file: {lufs:.1} LUFS)"),
+            (false, None) => String::from("Chapters"),
+        };
+        let list = List::new(list_items).style(s).block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title(title)
+                .title_bottom(Line::from("Enter: jump").style(bd).centered())
+                .style(bd),
+        );
+
+        let mut state = ListState::default().with_selected(Some(self.ui.selected_chapter_index));
+        f.render_stateful_widget(list, area, &mut state);
+    }
+
+    fn render_playlist(&self, f: &mut Frame) {
+        let s = Style::default()
+            .fg(self.ui.theme.devices.foreground.unwrap())
+            .bg(self.ui.theme.devices.background.unwrap());
+        let bd = s.fg(self.ui.theme.devices.borders.unwrap());
+        let hl = s.fg(self.ui.theme.devices.highlight.unwrap());
+        let area = Self::get_popup_area_with_percentage(f.area(), 50, 60);
+        f.render_widget(Clear, area);
+
+        let list_items: Vec<ListItem> = self
+            .playlist
+            .tracks()
+            .iter()
+            .enumerate()
+            .map(|(i, track)| {
+                let is_current = self.playlist.current() == Some(i);
+                let num = format!("{}[{}] ", if is_current { "▶" } else { " " }, i + 1);
+                let name = track.file_name().unwrap_or_default().to_string_lossy();
+                let item_style = if i == self.ui.selected_playlist_index {
+                    hl
+                } else {
+                    s
+                };
+                let name = if is_current {
+                    Span::styled(name, item_style.bold())
+                } else {
+                    Span::styled(name, item_style)
+                };
+                ListItem::from(Span::styled(num, item_style) + name)
+            })
+            .collect();
+        let auto_advance = if self.playlist.auto_advance {
+            "on"
+        } else {
+            "off"
+        };
+        let list = List::new(list_items).style(s).block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title("Playlist")
+                .title_bottom(
+                    Line::from(format!(
+                        "Enter: play  x: remove  a: auto-advance [{auto_advance}]  w: save"
+                    ))
+                    .style(bd)
+                    .centered(),
+                )
+                .style(bd),
+        );
+
+        f.render_widget(list, area);
+    }
+
+    /// Shows how many files are selected at the bottom of the explorer.
+    fn render_explorer_selection(&self, f: &mut Frame, area: Rect) {
+        if self.explorer_selection.is_empty() || area.height < 2 {
+            return;
+        }
+        let s = Style::default()
+            .bg(self.ui.theme.explorer.background.unwrap())
+            .fg(self.ui.theme.explorer.highlight_item_foreground.unwrap());
+        let text = format!(
+            " {} selected: Enter to open, a to add to playlist ",
+            self.explorer_selection.len()
+        );
+        let line_area = Rect::new(area.x + 2, area.y + area.height - 1, area.width - 4, 1);
+        f.render_widget(Line::styled(text, s).centered(), line_area);
+    }
+
+    fn render_prompt(&self, f: &mut Frame) {
+        let Some(prompt) = &self.prompt else {
+            return;
+        };
+        let s = Style::default().fg(self.ui.theme.global.foreground).bg(self
+            .ui
+            .theme
+            .global
+            .background);
+        let area = Self::get_popup_area_with_lenght(f.area(), 3, prompt.width());
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Paragraph::new(format!("{}_", prompt.input)).style(s).block(
+                Block::bordered()
+                    .border_type(BorderType::Rounded)
+                    .title(prompt.title())
+                    .style(s),
+            ),
+            area,
+        );
+    }
+
+    fn render_loading(&self, f: &mut Frame) {
+        let Some(loading) = &self.loading else {
+            return;
+        };
+        let s = Style::default().fg(self.ui.theme.global.foreground).bg(self
+            .ui
+            .theme
+            .global
+            .background);
+        let hl = s.fg(self.ui.theme.global.highlight.unwrap());
+        let decoded = format_time(loading.progress.decoded_duration().as_secs_f64());
+        // the length of some streams is unknown until they are decoded
+        let (ratio, label) = match loading.progress.total_duration() {
+            Some(total) => (
+                loading.progress.ratio().unwrap_or(0.),
+                format!("{decoded} / {}", format_time(total.as_secs_f64())),
+            ),
+            None => (0., decoded),
+        };
+        let area = Self::get_popup_area_with_lenght(f.area(), 3, 60);
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Gauge::default()
+                .ratio(ratio)
+                .label(label)
+                .gauge_style(hl)
+                .style(s)
+                .block(
+                    Block::bordered()
+                        .border_type(BorderType::Rounded)
+                        .title(format!("Loading {}", loading.title))
+                        .title_bottom(
+                            Line::from(vec!["Esc".bold().style(hl), " cancel".to_span()])
+                                .right_aligned(),
+                        )
+                        .style(s),
+                ),
+            area,
+        );
+    }
+
+    fn render_fft_info(&self, f: &mut Frame<'_>, x: u16, y: u16) {
+        let rect_width = self.ui.chart_rect.unwrap().width;
+        let rect_height = self.ui.chart_rect.unwrap().height;
+
+        let left_bound = 8;
+        let right_bound = rect_width - 1;
+        let upper_bound = self.ui.chart_rect.unwrap().y + 1;
+        let lower_bound = upper_bound + rect_height - 4;
+
+        let (hz, db) = Self::map_mouse_position_to_chart_point(
+            x - left_bound,
+            right_bound - left_bound,
+            y - upper_bound,
+            lower_bound - upper_bound,
+        );
+        let hz_text = format!("{hz:.2} Hz");
+        let db_text = format!("{db:.2} Db");
+
+        let width = hz_text.len().max(db_text.len()) as u16 + 2;
+        let height = 4;
+
+        let text = db_text + "\n" + &hz_text;
+
+        let x = u16::min(x, right_bound - width) + 1;
+        let y = u16::min(y, lower_bound - height) + 1;
+        let area = Rect::new(x, y, width, height);
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Paragraph::new(text).block(
+                Block::bordered().border_type(BorderType::Rounded).style(
+                    Style::default().fg(self.ui.theme.global.foreground).bg(self
+                        .ui
+                        .theme
+                        .global
+                        .background),
+                ),
+            ),
+            area,
         );
     }
 
     fn receive_audio_file(&mut self, audio_file: AudioFile) {
+        // reset everything
+        self.reset_charts();
+        if let Some(loading) = self.loading.take() {
+            self.is_playing_audio = loading.autoplay;
+            self.last_reload = loading.reload.then(Instant::now);
+            // let the user pick a track if it was not chosen explicitly
+            self.ui.show_tracks_list = loading.track.is_none() && audio_file.tracks().len() > 1;
+        }
+        self.ui.selected_track_index = audio_file.track();
+        // a CUE sheet is reloaded when the album file it references is rewritten
+        self.file_watch = Some(FileWatch::new(audio_file.audio_path()));
         self.audio_file = audio_file;
         self.is_file_selected = true;
-        if self.audio_file.duration().as_secs_f64() < 15. {
-            self.ui.waveform_window = self.audio_file.duration().as_secs_f64();
-        }
-        self.waveform.audio_file_chart = Analyzer::get_waveform(
-            self.audio_file.samples(),
-            self.audio_file.duration().as_secs_f64(),
-        );
+        self.ui.selected_marker_index = 0;
+        self.markers = match Markers::load_for(self.audio_file.path()) {
+            Ok(markers) => markers,
+            Err(err) => {
+                self.handle_error(format!("Error loading markers: {err}"));
+                Markers::default()
+            }
+        };
         // TODO: channels
         if let Err(err) = self.file_analyzer.create_loudness_meter(
             // self.audio_file.channels() as u32,
@@ -1225,23 +2209,30 @@ impl App {
             ));
         }
 
+        self.ui.decoding = !self.audio_file.is_decoded();
+        self.apply_file_length_and_loudness();
+
+        self.ui.needs_render = true;
+    }
+
+    /// Fits the waveform window to a short file and normalizes the spectrum to its loudness,
+    /// when the file is received and again once it is fully decoded.
+    fn apply_file_length_and_loudness(&mut self) {
+        if self.audio_file.duration().as_secs_f64() < 15. {
+            self.ui.waveform_window = self.audio_file.duration().as_secs_f64();
+        }
+
         // Calculate gain compensation to normalize track to target LUFS
-        if let Some(integrated_lufs) = self.file_analyzer.calculate_integrated_lufs(
-            // self.audio_file.channels(),
-            2,
-            self.audio_file.samples(),
-        ) {
+        if let Some(integrated_lufs) = self.audio_file.integrated_lufs() {
             let gain_db = FFT_TARGET_LUFS - integrated_lufs as f32;
             self.ui.fft_gain_compensation_db = gain_db;
         } else {
             self.ui.fft_gain_compensation_db = 0.0;
         }
-
-        self.ui.needs_render = true;
     }
 
     /// The main loop
-    fn run(mut self, mut terminal: DefaultTerminal, startup_file: Option<PathBuf>) -> Result<()> {
+    fn run(mut self, mut terminal: DefaultTerminal, args: Args) -> Result<()> {
         // apply theme
         // check if config directory exists
         if let Some(path) = config_dir() {
@@ -1253,6 +2244,29 @@ impl App {
             self.set_theme(theme);
         }
 
+        self.config = match Config::load() {
+            Ok(config) => config,
+            Err(err) => {
+                self.handle_error(format!("Error reading config.toml: {err}. Using defaults."));
+                Config::default()
+            }
+        };
+        if let Some(name) = &self.config.output_device {
+            self.ui.output_device_name.clone_from(name);
+        }
+        if let Some(rule) = self
+            .config
+            .alarms
+            .iter()
+            .find(|rule| rule.above.is_none() && rule.below.is_none())
+        {
+            self.handle_error(format!(
+                "Alarm \"{}\" never triggers, it needs `above` or `below`",
+                rule.name
+            ));
+        }
+        self.alarms = Alarms::new(self.config.alarms.clone());
+
         self.current_directory = self.explorer.cwd().clone();
         self.explorer.set_filter_map(|file| {
             let keep = match file.path.extension() {
@@ -1267,11 +2281,29 @@ impl App {
         })?;
         terminal.draw(|f| self.draw(f))?;
 
-        // blocking audio file receiver
-        // blocking to ensure that audio file is loaded
-        // before we render TUI, so that waveform is rendered correctly
-        if let Some(f) = startup_file {
-            self.select_audio_file(f);
+        if let Some(track) = args.track {
+            for file in &args.files {
+                self.cli_tracks.insert(file.clone(), track);
+            }
+        }
+        if let Some(raw) = args.raw {
+            for file in &args.files {
+                self.raw_formats.insert(file.clone(), raw);
+            }
+        }
+        self.settings.watch_file = args.watch;
+        if args.stdin {
+            self.stdin = Some(LiveInput::new(
+                stdin_input::spawn(args.raw),
+                String::from("stdin"),
+            ));
+            self.set_mode(Mode::Stdin);
+        }
+        if let Some(url) = args.stream {
+            self.open_stream(url);
+        }
+        if !args.files.is_empty() {
+            self.open_files(args.files);
             terminal.draw(|f| self.draw(f))?;
         }
 
@@ -1279,6 +2311,43 @@ impl App {
             std::thread::sleep(Duration::from_millis(8));
             self.ui.needs_render = false;
 
+            // receive a file loaded in the background
+            if let Ok(audio_file) = self.audio_file_rx.try_recv() {
+                self.receive_audio_file(audio_file);
+            }
+            self.receive_output_device();
+            if let Some(loading) = &self.loading {
+                // the error itself comes through the error channel
+                if loading.progress.has_failed() {
+                    self.loading = None;
+                }
+                // keep the progress bar moving
+                self.ui.needs_render = true;
+            }
+            if self.ui.decoding {
+                if self.audio_file.is_decoded() {
+                    self.ui.decoding = false;
+                    self.apply_file_length_and_loudness();
+                }
+                // keep the decoded part of the waveform growing
+                self.ui.needs_render = true;
+            }
+
+            // reload the file once it has been rewritten
+            if self.settings.watch_file
+                && self.loading.is_none()
+                && self.is_file_selected
+                && self.file_watch.as_mut().is_some_and(|watch| watch.poll())
+            {
+                self.reload_audio_file();
+            }
+            // keep the time since the last reload up to date
+            let reload_age = self.reload_age();
+            if reload_age != self.ui.reload_age {
+                self.ui.reload_age = reload_age;
+                self.ui.needs_render = true;
+            }
+
             // receive playback position
             let prev_playhead = self.waveform.playhead;
             if let Ok(pos) = self.playback_position_rx.try_recv()
@@ -1288,10 +2357,29 @@ impl App {
                 self.analyze_audio_file_samples(pos);
                 // render only if playhead position changed
                 self.ui.needs_render = prev_playhead != self.waveform.playhead;
+
+                // the player sends the length of the file when the track ends,
+                // which is only the end of the track once it is fully decoded
+                if self.audio_file.is_decoded()
+                    && pos >= self.audio_file.samples_len()
+                    && self.is_playing_audio
+                    && self.playlist.auto_advance
+                {
+                    self.play_next_track(true);
+                    self.ui.needs_render = true;
+                }
             }
 
+            self.receive_input_events(Mode::Stdin);
+            self.receive_input_events(Mode::Stream);
+            self.receive_input_events(Mode::System);
+            self.check_recording();
+
             // use ringbuf to analyze data if the `Mode` is not `Mode::Player`
-            if matches!(self.settings.mode, Mode::Microphone) {
+            if matches!(
+                self.settings.mode,
+                Mode::Microphone | Mode::Stdin | Mode::Stream | Mode::System
+            ) {
                 self.analyze_microphone_input();
                 self.ui.needs_render = true; // Always render in microphone mode
             }
@@ -1385,13 +2473,30 @@ impl App {
                 // if let Event::Key(key) = event {
                 match event {
                     Event::Key(key) => {
+                        // every key goes to the prompt while it is shown
+                        if self.prompt.is_some() {
+                            self.handle_prompt_input(key);
+                            self.ui.needs_render = true;
+                            continue;
+                        }
                         // quit (only if not in any popup)
                         if key.code == KeyCode::Char('q')
                             && !(self.ui.show_themes_list
                                 || self.ui.show_explorer
                                 || self.ui.show_devices_list
-                                || self.ui.show_help_message)
+                                || self.ui.show_help_message
+                                || self.ui.show_markers_list
+                                || self.ui.show_chapters_list
+                                || self.ui.show_playlist
+                                || self.ui.show_output_devices_list
+                                || self.ui.show_tracks_list
+                                || self.ui.show_file_info
+                                || self.ui.show_alarm_log)
                         {
+                            self.stop_recording(false);
+                            self.disarm_trigger();
+                            LoudnessLog::stop_shared(&self.device_log);
+                            LoudnessLog::stop_shared(&self.system_log);
                             self.player_command_tx.send(PlayerCommand::Quit)?;
                             return Ok(());
                         }
@@ -1399,15 +2504,7 @@ impl App {
                         self.ui.needs_render = true;
                     }
                     Event::Mouse(m) => {
-                        if matches!(m.kind, MouseEventKind::Moved) {
-                            if self.in_fft_chart(m) {
-                                self.mouse_position = Some((m.column, m.row));
-                            } else {
-                                self.mouse_position = None;
-                            }
-                        } else {
-                            self.mouse_position = None;
-                        }
+                        self.handle_mouse(m);
                         self.ui.needs_render = true;
                     }
                     Event::Resize(_, _) => {
@@ -1428,7 +2525,8 @@ impl App {
         let samples = self.latest_captured_samples.lock().unwrap().to_vec();
         let (mid_samples, side_samples) = audio_player::get_mid_and_side_samples(&samples);
         let sample_rate = self.device_analyzer.sample_rate() as usize;
-        let left_bound = 15 * sample_rate - 2usize.pow(14);
+        // a too low rate gives a shorter window, which the FFT reports as an error
+        let left_bound = (15 * sample_rate).saturating_sub(2usize.pow(14));
 
         // get fft
         self.fft_data.mid_fft = match self
@@ -1463,7 +2561,7 @@ impl App {
             self.lufs[i] = self.lufs[i + 1];
         }
 
-        let lb = 30 * sample_rate - 2usize.pow(14);
+        let lb = (30 * sample_rate).saturating_sub(2usize.pow(14));
         if let Err(err) = self
             .device_analyzer
             .add_samples(&samples[lb..30 * sample_rate])
@@ -1477,6 +2575,28 @@ impl App {
                 0.0
             }
         };
+
+        // check the alarms against the analyzed window
+        let window = &samples[lb..30 * sample_rate];
+        let sample_peak = window.iter().fold(0f32, |peak, s| peak.max(s.abs()));
+        let measurements = Measurements {
+            momentary: self
+                .device_analyzer
+                .get_momentary_lufs()
+                .unwrap_or(f64::NEG_INFINITY),
+            shortterm: self.lufs[299],
+            true_peak: self
+                .device_analyzer
+                .get_prev_true_peak()
+                .map_or(f64::NEG_INFINITY, |peak| 20. * peak.log10()),
+            sample_peak: 20. * (sample_peak as f64).log10(),
+            correlation: alarms::correlation(window),
+        };
+        if self.alarms.update(&measurements, Instant::now()) {
+            // ring the terminal bell
+            print!("\x07");
+            let _ = std::io::stdout().flush();
+        }
     }
 
     fn analyze_audio_file_samples(&mut self, pos: usize) {
@@ -1487,22 +2607,17 @@ impl App {
         // get fft
         let fft_left_bound = pos.saturating_sub(16384);
         if fft_left_bound != 0 {
-            let mid_samples_len = self.audio_file.mid_samples().len();
-            let side_samples_len = self.audio_file.side_samples().len();
-
             // check bounds to prevent panic when file was changed
-            let mid_samples = if pos <= mid_samples_len && fft_left_bound < mid_samples_len {
-                &self.audio_file.mid_samples()[fft_left_bound..pos]
+            let (mid_samples, side_samples) = if pos <= self.audio_file.frames() {
+                (
+                    self.audio_file.mid_samples(fft_left_bound..pos),
+                    self.audio_file.side_samples(fft_left_bound..pos),
+                )
             } else {
-                &[]
-            };
-            let side_samples = if pos <= side_samples_len && fft_left_bound < side_samples_len {
-                &self.audio_file.side_samples()[fft_left_bound..pos]
-            } else {
-                &[]
+                (Vec::new(), Vec::new())
             };
 
-            self.fft_data.mid_fft = match self.file_analyzer.get_fft(mid_samples) {
+            self.fft_data.mid_fft = match self.file_analyzer.get_fft(&mid_samples) {
                 Ok(fft) => fft,
                 Err(_err) => {
                     // can't log the error because this fn takes a mutable reference
@@ -1512,7 +2627,7 @@ impl App {
                     vec![(0., 0.)]
                 }
             };
-            self.fft_data.side_fft = match self.file_analyzer.get_fft(side_samples) {
+            self.fft_data.side_fft = match self.file_analyzer.get_fft(&side_samples) {
                 Ok(fft) => fft,
                 Err(_err) => {
                     // can't log the error because this fn takes a mutable reference
@@ -1531,12 +2646,12 @@ impl App {
             for i in 0..self.lufs.len() - 1 {
                 self.lufs[i] = self.lufs[i + 1];
             }
-            let samples_len = self.audio_file.samples().len();
+            let samples_len = self.audio_file.samples_len();
             // check bounds to prevent panic when file was changed
             if pos <= samples_len && lufs_left_bound < samples_len {
                 if let Err(err) = self
                     .file_analyzer
-                    .add_samples(&self.audio_file.samples()[lufs_left_bound..pos])
+                    .add_samples(&self.audio_file.samples(lufs_left_bound..pos))
                 {
                     self.handle_error(format!("Could not get samples for LUFS analyzer: {err}"));
                 }
@@ -1564,14 +2679,47 @@ impl App {
             KeyCode::Enter if self.ui.show_explorer => {
                 let file = self.explorer.current();
                 let file_path = self.explorer.current().path.clone();
-                if file.is_file() {
+                if !self.explorer_selection.is_empty() {
+                    let files = std::mem::take(&mut self.explorer_selection);
+                    self.open_files(files);
+                } else if file.is_file() {
                     if file_path.extension().unwrap() == "theme" {
                         self.apply_theme_file(&file_path);
                     } else {
-                        self.select_audio_file(file_path);
+                        self.open_files(vec![file_path]);
                     }
                 }
             }
+            // select several files to open them as a playlist
+            KeyCode::Char(' ') if self.ui.show_explorer => {
+                let file = self.explorer.current();
+                if file.is_file() {
+                    let path = file.path.clone();
+                    if let Some(i) = self.explorer_selection.iter().position(|p| *p == path) {
+                        self.explorer_selection.remove(i);
+                    } else {
+                        self.explorer_selection.push(path);
+                    }
+                }
+            }
+            // add selected files (or the current one) to the end of the playlist
+            KeyCode::Char('a') if self.ui.show_explorer => {
+                let files = if self.explorer_selection.is_empty() {
+                    let file = self.explorer.current();
+                    if !file.is_file() {
+                        return;
+                    }
+                    vec![file.path.clone()]
+                } else {
+                    std::mem::take(&mut self.explorer_selection)
+                };
+                if let Err(err) = self.playlist.append(files) {
+                    self.handle_error(format!("Error adding files to the playlist: {err}"));
+                }
+                if !self.is_file_selected {
+                    self.play_next_track(false);
+                }
+            }
 
             // show side fft
             KeyCode::Char('S') => self.ui.show_side_fft = !self.ui.show_side_fft,
@@ -1599,7 +2747,8 @@ impl App {
                 self.ui.right_arrow_timer = Some(Instant::now());
                 self.lufs = [-100.; 300];
                 self.file_analyzer.reset();
-                if let Err(_err) = self.player_command_tx.send(PlayerCommand::MoveRight) {
+                let step = self.seek_step(key.modifiers);
+                if let Err(_err) = self.player_command_tx.send(PlayerCommand::MoveRight(step)) {
                     //TODO: log sending error
                 }
             }
@@ -1612,17 +2761,140 @@ impl App {
                 self.ui.left_arrow_timer = Some(Instant::now());
                 self.lufs = [-100.; 300];
                 self.file_analyzer.reset();
-                if let Err(_err) = self.player_command_tx.send(PlayerCommand::MoveLeft) {
+                let step = self.seek_step(key.modifiers);
+                if let Err(_err) = self.player_command_tx.send(PlayerCommand::MoveLeft(step)) {
+                    //TODO: log sending error
+                }
+            }
+            // step one analysis frame back and forth
+            KeyCode::Char(',') if matches!(self.settings.mode, Mode::Player) => {
+                self.seek_to(self.current_time() - self.analysis_frame_duration());
+            }
+            KeyCode::Char('.') if matches!(self.settings.mode, Mode::Player) => {
+                self.seek_to(self.current_time() + self.analysis_frame_duration());
+            }
+            KeyCode::Home
+                if matches!(self.settings.mode, Mode::Player) && !self.ui.show_explorer =>
+            {
+                self.seek_to(0.);
+            }
+            KeyCode::End
+                if matches!(self.settings.mode, Mode::Player) && !self.ui.show_explorer =>
+            {
+                self.seek_to(self.audio_file.duration().as_secs_f64());
+            }
+            // volume, mute and monitoring
+            KeyCode::Char('9')
+                if matches!(self.settings.mode, Mode::Player)
+                    && !(self.ui.show_devices_list
+                        || self.ui.show_themes_list
+                        || self.ui.show_output_devices_list
+                        || self.ui.show_tracks_list
+                        || self.ui.show_explorer) =>
+            {
+                self.set_volume(self.settings.volume - VOLUME_STEP);
+            }
+            KeyCode::Char('0')
+                if matches!(self.settings.mode, Mode::Player)
+                    && !(self.ui.show_devices_list
+                        || self.ui.show_themes_list
+                        || self.ui.show_output_devices_list
+                        || self.ui.show_tracks_list
+                        || self.ui.show_explorer) =>
+            {
+                self.set_volume(self.settings.volume + VOLUME_STEP);
+            }
+            KeyCode::Char('u')
+                if matches!(self.settings.mode, Mode::Player) && !self.ui.show_explorer =>
+            {
+                self.settings.muted = !self.settings.muted;
+                if let Err(_err) = self
+                    .player_command_tx
+                    .send(PlayerCommand::SetMuted(self.settings.muted))
+                {
                     //TODO: log sending error
                 }
             }
-            KeyCode::Char('1') if !self.ui.show_devices_list && !self.ui.show_themes_list => {
+            KeyCode::Char('c')
+                if matches!(self.settings.mode, Mode::Player)
+                    && !self.ui.show_explorer
+                    && !key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                self.settings.monitor_mode = self.settings.monitor_mode.next();
+                if let Err(_err) = self
+                    .player_command_tx
+                    .send(PlayerCommand::SetMonitorMode(self.settings.monitor_mode))
+                {
+                    //TODO: log sending error
+                }
+            }
+            // band solo filter
+            KeyCode::Char('f')
+                if matches!(self.settings.mode, Mode::Player) && !self.ui.show_explorer =>
+            {
+                self.settings.band_solo_kind = match self.settings.band_solo_kind {
+                    BandSoloKind::BandPass => BandSoloKind::Bell,
+                    BandSoloKind::Bell => BandSoloKind::BandPass,
+                };
+                if let Some(band) = self.settings.band_solo {
+                    let kind = self.settings.band_solo_kind;
+                    self.set_band_solo(Some(BandSolo { kind, ..band }), false);
+                }
+            }
+            KeyCode::Char('x')
+                if self.settings.band_solo.is_some()
+                    && !(self.ui.show_explorer
+                        || self.ui.show_markers_list
+                        || self.ui.show_playlist) =>
+            {
+                self.set_band_solo(None, false);
+            }
+            // go to time
+            KeyCode::Char('g')
+                if matches!(self.settings.mode, Mode::Player)
+                    && self.is_file_selected
+                    && !(self.ui.show_devices_list
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list
+                        || self.ui.show_help_message) =>
+            {
+                self.prompt = Some(Prompt::new(PromptKind::GoTo));
+            }
+            // open a stream
+            KeyCode::Char('U')
+                if !(self.ui.show_devices_list
+                    || self.ui.show_explorer
+                    || self.ui.show_themes_list
+                    || self.ui.show_help_message) =>
+            {
+                let url = self
+                    .stream
+                    .as_ref()
+                    .map_or("http://", |s| s.source.as_str());
+                self.prompt = Some(Prompt::new(PromptKind::StreamUrl).with_input(url));
+            }
+            KeyCode::Char('1')
+                if !self.ui.show_devices_list
+                    && !self.ui.show_themes_list
+                    && !self.ui.show_output_devices_list
+                    && !self.ui.show_tracks_list =>
+            {
                 self.ui.show_waveform = !self.ui.show_waveform;
             }
-            KeyCode::Char('2') if !self.ui.show_devices_list && !self.ui.show_themes_list => {
+            KeyCode::Char('2')
+                if !self.ui.show_devices_list
+                    && !self.ui.show_themes_list
+                    && !self.ui.show_output_devices_list
+                    && !self.ui.show_tracks_list =>
+            {
                 self.ui.show_fft_chart = !self.ui.show_fft_chart;
             }
-            KeyCode::Char('3') if !self.ui.show_devices_list && !self.ui.show_themes_list => {
+            KeyCode::Char('3')
+                if !self.ui.show_devices_list
+                    && !self.ui.show_themes_list
+                    && !self.ui.show_output_devices_list
+                    && !self.ui.show_tracks_list =>
+            {
                 self.ui.show_lufs = !self.ui.show_lufs;
             }
             // Quick selection with numbers 0-9 when themes list is open
@@ -1639,9 +2911,14 @@ impl App {
                 .unwrap(),
             // show devices
             KeyCode::Char('d')
-                if matches!(self.settings.mode, Mode::Microphone) && !self.ui.show_help_message =>
+                if matches!(self.settings.mode, Mode::Microphone | Mode::System)
+                    && !self.ui.show_help_message =>
             {
                 self.ui.show_devices_list = !self.ui.show_devices_list;
+                if self.ui.show_devices_list && self.settings.mode == Mode::System {
+                    self.ui.system_sources = list_system_sources();
+                    self.ui.selected_device_index = 0;
+                }
             }
             // change mode
             KeyCode::Char('m')
@@ -1649,18 +2926,125 @@ impl App {
                     || self.ui.show_explorer
                     || self.ui.show_themes_list) =>
             {
-                self.settings.mode = if matches!(self.settings.mode, Mode::Microphone) {
-                    self.reset_charts();
-                    if let Some(stream) = self.audio_capture_stream.as_ref() {
-                        let _ = stream.pause();
-                    }
-                    Mode::Player
-                } else {
-                    if let Some(stream) = self.audio_capture_stream.as_ref() {
-                        let _ = stream.play();
+                let mode = match self.settings.mode {
+                    Mode::Player => Mode::Microphone,
+                    Mode::Microphone if self.stdin.is_some() => Mode::Stdin,
+                    Mode::Microphone | Mode::Stdin if self.stream.is_some() => Mode::Stream,
+                    // monitor sources and ALSA loopback devices are only listed on Linux
+                    Mode::Microphone | Mode::Stdin | Mode::Stream if cfg!(target_os = "linux") => {
+                        Mode::System
                     }
-                    Mode::Microphone
+                    _ => Mode::Player,
                 };
+                self.set_mode(mode);
+            }
+            // show output devices
+            KeyCode::Char('o')
+                if matches!(self.settings.mode, Mode::Player)
+                    && !(self.ui.show_help_message
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list) =>
+            {
+                self.ui.show_output_devices_list = !self.ui.show_output_devices_list;
+                if self.ui.show_output_devices_list {
+                    self.ui.output_devices = list_output_devs()
+                        .into_iter()
+                        .map(|(name, _)| name)
+                        .collect();
+                }
+            }
+            // Select output device using its index if the output device list is shown
+            KeyCode::Char(c)
+                if self.ui.show_output_devices_list && c.is_ascii_digit() && c != '0' =>
+            {
+                let index = (c as usize) - ('1' as usize);
+                self.select_output_device(index);
+            }
+            // Arrow key navigation for output devices list
+            KeyCode::Up
+                if self.ui.show_output_devices_list && !self.ui.output_devices.is_empty() =>
+            {
+                if self.ui.selected_output_device_index > 0 {
+                    self.ui.selected_output_device_index -= 1;
+                } else {
+                    self.ui.selected_output_device_index = self.ui.output_devices.len() - 1; // Wrap to end
+                }
+            }
+            KeyCode::Down
+                if self.ui.show_output_devices_list && !self.ui.output_devices.is_empty() =>
+            {
+                if self.ui.selected_output_device_index < self.ui.output_devices.len() - 1 {
+                    self.ui.selected_output_device_index += 1;
+                } else {
+                    self.ui.selected_output_device_index = 0; // Wrap to beginning
+                }
+            }
+            KeyCode::Enter if self.ui.show_output_devices_list => {
+                self.select_output_device(self.ui.selected_output_device_index);
+            }
+            // show audio tracks
+            KeyCode::Char('n')
+                if matches!(self.settings.mode, Mode::Player)
+                    && self.is_file_selected
+                    && !(self.ui.show_help_message
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list) =>
+            {
+                self.ui.show_tracks_list = !self.ui.show_tracks_list;
+                self.ui.selected_track_index = self.audio_file.track();
+            }
+            // watch the file and reload it when it changes
+            KeyCode::Char('W')
+                if matches!(self.settings.mode, Mode::Player) && !self.ui.show_explorer =>
+            {
+                self.settings.watch_file = !self.settings.watch_file;
+                // changes made while not watching are ignored
+                if self.is_file_selected {
+                    self.file_watch = Some(FileWatch::new(self.audio_file.audio_path()));
+                }
+            }
+            // show file info
+            KeyCode::Char('i')
+                if matches!(self.settings.mode, Mode::Player)
+                    && self.is_file_selected
+                    && !(self.ui.show_help_message
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list) =>
+            {
+                self.ui.show_file_info = !self.ui.show_file_info;
+                self.ui.file_info_scroll = 0;
+            }
+            KeyCode::Up if self.ui.show_file_info => {
+                self.ui.file_info_scroll = self.ui.file_info_scroll.saturating_sub(1);
+            }
+            KeyCode::Down if self.ui.show_file_info => {
+                let rows = self.audio_file.info().rows().len() as u16;
+                if self.ui.file_info_scroll + 1 < rows {
+                    self.ui.file_info_scroll += 1;
+                }
+            }
+            // Select audio track using its index if the audio tracks list is shown
+            KeyCode::Char(c) if self.ui.show_tracks_list && c.is_ascii_digit() && c != '0' => {
+                let index = (c as usize) - ('1' as usize);
+                self.select_audio_track(index);
+            }
+            // Arrow key navigation for audio tracks list
+            KeyCode::Up if self.ui.show_tracks_list => {
+                if self.ui.selected_track_index > 0 {
+                    self.ui.selected_track_index -= 1;
+                } else {
+                    self.ui.selected_track_index = self.audio_file.tracks().len().saturating_sub(1); // Wrap to end
+                }
+            }
+            KeyCode::Down if self.ui.show_tracks_list => {
+                if self.ui.selected_track_index + 1 < self.audio_file.tracks().len() {
+                    self.ui.selected_track_index += 1;
+                } else {
+                    self.ui.selected_track_index = 0; // Wrap to beginning
+                }
+            }
+            KeyCode::Enter if self.ui.show_tracks_list => {
+                self.select_audio_track(self.ui.selected_track_index);
             }
             // Select device using its index if the device list is shown
             KeyCode::Char(c) if self.ui.show_devices_list && c.is_ascii_digit() && c != '0' => {
@@ -1671,7 +3055,7 @@ impl App {
             }
             // Arrow key navigation for devices list
             KeyCode::Up if self.ui.show_devices_list => {
-                let devs = list_input_devs();
+                let devs = self.device_names();
                 if !devs.is_empty() {
                     if self.ui.selected_device_index > 0 {
                         self.ui.selected_device_index -= 1;
@@ -1682,7 +3066,7 @@ impl App {
                 }
             }
             KeyCode::Down if self.ui.show_devices_list => {
-                let devs = list_input_devs();
+                let devs = self.device_names();
                 if !devs.is_empty() {
                     if self.ui.selected_device_index < devs.len() - 1 {
                         self.ui.selected_device_index += 1;
@@ -1692,11 +3076,277 @@ impl App {
                     self.ui.needs_render = true;
                 }
             }
-            KeyCode::Enter if self.ui.show_devices_list => {
-                if let Err(err) = self.select_device(self.ui.selected_device_index) {
-                    self.handle_error(format!("Failed to select device: {err}"));
+            KeyCode::Enter if self.ui.show_devices_list => {
+                if let Err(err) = self.select_device(self.ui.selected_device_index) {
+                    self.handle_error(format!("Failed to select device: {err}"));
+                }
+            }
+            // drop a marker at the playhead
+            KeyCode::Char('k')
+                if matches!(self.settings.mode, Mode::Player)
+                    && self.is_file_selected
+                    && !(self.ui.show_devices_list
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list
+                        || self.ui.show_help_message) =>
+            {
+                self.prompt = Some(Prompt::new(PromptKind::NewMarker(self.current_time())));
+            }
+            // show markers
+            KeyCode::Char('K')
+                if matches!(self.settings.mode, Mode::Player)
+                    && !(self.ui.show_devices_list
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list
+                        || self.ui.show_help_message) =>
+            {
+                self.ui.show_markers_list = !self.ui.show_markers_list;
+            }
+            // jump to the previous/next marker
+            KeyCode::Char('[')
+                if matches!(self.settings.mode, Mode::Player)
+                    && !(self.ui.show_devices_list
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list
+                        || self.ui.show_help_message) =>
+            {
+                // step back a bit more so that pressing it twice does not get stuck on the same marker
+                if let Some(time) = self
+                    .markers
+                    .prev_before(self.current_time() - 0.5)
+                    .map(|m| m.time)
+                {
+                    self.seek_to(time);
+                }
+            }
+            KeyCode::Char(']')
+                if matches!(self.settings.mode, Mode::Player)
+                    && !(self.ui.show_devices_list
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list
+                        || self.ui.show_help_message) =>
+            {
+                if let Some(time) = self.markers.next_after(self.current_time()).map(|m| m.time) {
+                    self.seek_to(time);
+                }
+            }
+            // show chapters
+            KeyCode::Char('C')
+                if matches!(self.settings.mode, Mode::Player)
+                    && self.is_file_selected
+                    && !(self.ui.show_devices_list
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list
+                        || self.ui.show_help_message) =>
+            {
+                self.ui.show_chapters_list = !self.ui.show_chapters_list;
+                self.ui.selected_chapter_index = self.current_chapter().unwrap_or_default();
+            }
+            // jump to the previous/next chapter
+            KeyCode::Char('{') if matches!(self.settings.mode, Mode::Player) => {
+                // like markers, step back a bit so a chapter that just started can be skipped
+                let time = self.current_time() - 0.5;
+                if let Some(chapter) = self
+                    .audio_file
+                    .chapters()
+                    .iter()
+                    .rev()
+                    .find(|c| c.start < time)
+                {
+                    self.seek_to(chapter.start);
+                }
+            }
+            KeyCode::Char('}') if matches!(self.settings.mode, Mode::Player) => {
+                let time = self.current_time();
+                if let Some(chapter) = self.audio_file.chapters().iter().find(|c| c.start > time) {
+                    self.seek_to(chapter.start);
+                }
+            }
+            // Arrow key navigation for chapters list
+            KeyCode::Up if self.ui.show_chapters_list => {
+                let len = self.audio_file.chapters().len();
+                if self.ui.selected_chapter_index > 0 {
+                    self.ui.selected_chapter_index -= 1;
+                } else {
+                    self.ui.selected_chapter_index = len.saturating_sub(1); // Wrap to end
+                }
+            }
+            KeyCode::Down if self.ui.show_chapters_list => {
+                if self.ui.selected_chapter_index + 1 < self.audio_file.chapters().len() {
+                    self.ui.selected_chapter_index += 1;
+                } else {
+                    self.ui.selected_chapter_index = 0; // Wrap to beginning
+                }
+            }
+            KeyCode::Enter if self.ui.show_chapters_list => {
+                if let Some(start) = self
+                    .audio_file
+                    .chapters()
+                    .get(self.ui.selected_chapter_index)
+                    .map(|c| c.start)
+                {
+                    self.seek_to(start);
+                    self.ui.show_chapters_list = false;
+                }
+            }
+            // Arrow key navigation for markers list
+            KeyCode::Up if self.ui.show_markers_list && !self.markers.is_empty() => {
+                if self.ui.selected_marker_index > 0 {
+                    self.ui.selected_marker_index -= 1;
+                } else {
+                    self.ui.selected_marker_index = self.markers.len() - 1; // Wrap to end
+                }
+            }
+            KeyCode::Down if self.ui.show_markers_list && !self.markers.is_empty() => {
+                if self.ui.selected_marker_index < self.markers.len() - 1 {
+                    self.ui.selected_marker_index += 1;
+                } else {
+                    self.ui.selected_marker_index = 0; // Wrap to beginning
+                }
+            }
+            KeyCode::Enter if self.ui.show_markers_list => {
+                if let Some(time) = self
+                    .markers
+                    .get(self.ui.selected_marker_index)
+                    .map(|m| m.time)
+                {
+                    self.seek_to(time);
+                    self.ui.show_markers_list = false;
+                }
+            }
+            KeyCode::Char('r')
+                if self.ui.show_markers_list
+                    && self.markers.get(self.ui.selected_marker_index).is_some() =>
+            {
+                self.prompt = Some(Prompt::new(PromptKind::RenameMarker(
+                    self.ui.selected_marker_index,
+                )));
+            }
+            // start or stop recording the input device
+            KeyCode::Char('r')
+                if self.settings.mode == Mode::Microphone
+                    && !(self.ui.show_devices_list
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list
+                        || self.ui.show_help_message) =>
+            {
+                self.toggle_recording();
+            }
+            // reset the programme loudness of the microphone
+            KeyCode::Char('R')
+                if self.settings.mode == Mode::Microphone
+                    && !(self.ui.show_help_message
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list) =>
+            {
+                if let Some(meter) = self.program_meter.lock().unwrap().as_mut() {
+                    meter.reset();
+                }
+            }
+            // pause or resume measuring the programme loudness
+            KeyCode::Char('P')
+                if self.settings.mode == Mode::Microphone
+                    && !(self.ui.show_help_message
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list) =>
+            {
+                if let Some(meter) = self.program_meter.lock().unwrap().as_mut() {
+                    meter.toggle_pause();
+                }
+            }
+            // show the alarm log
+            KeyCode::Char('A')
+                if !(self.ui.show_help_message
+                    || self.ui.show_explorer
+                    || self.ui.show_themes_list) =>
+            {
+                self.ui.show_alarm_log = !self.ui.show_alarm_log;
+            }
+            // start or stop the loudness log
+            KeyCode::Char('l')
+                if matches!(self.settings.mode, Mode::Microphone | Mode::System)
+                    && !(self.ui.show_devices_list
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list
+                        || self.ui.show_help_message) =>
+            {
+                self.toggle_log();
+            }
+            // arm or disarm triggered recording
+            KeyCode::Char('T')
+                if self.settings.mode == Mode::Microphone
+                    && !(self.ui.show_devices_list
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list
+                        || self.ui.show_help_message) =>
+            {
+                self.toggle_trigger();
+            }
+            KeyCode::Delete | KeyCode::Char('x') if self.ui.show_markers_list => {
+                self.markers.remove(self.ui.selected_marker_index);
+                self.ui.selected_marker_index = self
+                    .ui
+                    .selected_marker_index
+                    .min(self.markers.len().saturating_sub(1));
+                self.save_markers();
+            }
+            // show playlist
+            KeyCode::Char('p')
+                if matches!(self.settings.mode, Mode::Player)
+                    && !(self.ui.show_devices_list
+                        || self.ui.show_explorer
+                        || self.ui.show_themes_list
+                        || self.ui.show_help_message
+                        || self.ui.show_markers_list) =>
+            {
+                self.ui.show_playlist = !self.ui.show_playlist;
+                self.ui.selected_playlist_index = self.playlist.current().unwrap_or(0);
+            }
+            // previous/next track
+            KeyCode::Char('<') if matches!(self.settings.mode, Mode::Player) => {
+                self.play_prev_track();
+            }
+            KeyCode::Char('>') if matches!(self.settings.mode, Mode::Player) => {
+                self.play_next_track(self.is_playing_audio);
+            }
+            // Arrow key navigation for playlist
+            KeyCode::Up if self.ui.show_playlist && !self.playlist.is_empty() => {
+                if self.ui.selected_playlist_index > 0 {
+                    self.ui.selected_playlist_index -= 1;
+                } else {
+                    self.ui.selected_playlist_index = self.playlist.len() - 1; // Wrap to end
+                }
+            }
+            KeyCode::Down if self.ui.show_playlist && !self.playlist.is_empty() => {
+                if self.ui.selected_playlist_index < self.playlist.len() - 1 {
+                    self.ui.selected_playlist_index += 1;
+                } else {
+                    self.ui.selected_playlist_index = 0; // Wrap to beginning
+                }
+            }
+            KeyCode::Enter if self.ui.show_playlist => {
+                if let Some(path) = self
+                    .playlist
+                    .select(self.ui.selected_playlist_index)
+                    .cloned()
+                {
+                    self.play_track(path, true);
                 }
             }
+            KeyCode::Delete | KeyCode::Char('x') if self.ui.show_playlist => {
+                self.playlist.remove(self.ui.selected_playlist_index);
+                self.ui.selected_playlist_index = self
+                    .ui
+                    .selected_playlist_index
+                    .min(self.playlist.len().saturating_sub(1));
+            }
+            KeyCode::Char('a') if self.ui.show_playlist => {
+                self.playlist.auto_advance = !self.playlist.auto_advance;
+            }
+            KeyCode::Char('w') if self.ui.show_playlist && !self.playlist.is_empty() => {
+                self.prompt =
+                    Some(Prompt::new(PromptKind::SavePlaylist).with_input("playlist.m3u8"));
+            }
             // Arrow key navigation for themes list
             KeyCode::Up if self.ui.show_themes_list => {
                 let themes = builtin_themes::list_themes();
@@ -1732,26 +3382,31 @@ impl App {
                 if self.ui.show_themes_list
                     || self.ui.show_explorer
                     || self.ui.show_devices_list
-                    || self.ui.show_help_message =>
+                    || self.ui.show_help_message
+                    || self.ui.show_markers_list
+                    || self.ui.show_chapters_list
+                    || self.ui.show_playlist
+                    || self.ui.show_output_devices_list
+                    || self.ui.show_tracks_list
+                    || self.ui.show_file_info
+                    || self.ui.show_alarm_log =>
             {
                 self.ui.show_themes_list = false;
                 self.ui.show_explorer = false;
                 self.ui.show_devices_list = false;
                 self.ui.show_help_message = false;
+                self.ui.show_markers_list = false;
+                self.ui.show_chapters_list = false;
+                self.ui.show_playlist = false;
+                self.ui.show_output_devices_list = false;
+                self.ui.show_tracks_list = false;
+                self.ui.show_file_info = false;
+                self.ui.show_alarm_log = false;
+                self.explorer_selection.clear();
             }
-            KeyCode::Char('=' | '+') => {
-                self.ui.plus_sign_timer = Some(Instant::now());
-                self.ui.waveform_window = f64::max(self.ui.waveform_window - 1., 1.);
-            }
-            KeyCode::Char('-' | '_') => {
-                let bound = if self.audio_file.duration().as_secs_f64() < 15. {
-                    self.audio_file.duration().as_secs_f64()
-                } else {
-                    15.
-                };
-                self.ui.minus_sign_timer = Some(Instant::now());
-                self.ui.waveform_window = f64::min(self.ui.waveform_window + 1., bound);
-            }
+            KeyCode::Esc if self.loading.is_some() => self.cancel_loading(),
+            KeyCode::Char('=' | '+') => self.zoom_waveform_in(),
+            KeyCode::Char('-' | '_') => self.zoom_waveform_out(),
             KeyCode::Char('h' | '?') | KeyCode::F(1)
                 if !(self.ui.show_devices_list
                     || self.ui.show_explorer
@@ -1763,12 +3418,517 @@ impl App {
         }
     }
 
+    fn handle_prompt_input(&mut self, key: KeyEvent) {
+        let Some(prompt) = self.prompt.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Char(c) => prompt.input.push(c),
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            }
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Enter => {
+                let prompt = self.prompt.take().unwrap();
+                let input = prompt.input.trim().to_string();
+                match prompt.kind {
+                    PromptKind::NewMarker(time) => {
+                        let name = if input.is_empty() {
+                            format!("Marker {}", self.markers.len() + 1)
+                        } else {
+                            input
+                        };
+                        self.ui.selected_marker_index = self.markers.add(name, time);
+                        self.save_markers();
+                    }
+                    PromptKind::RenameMarker(index) => {
+                        if let Some(marker) = self.markers.get(index).cloned()
+                            && !input.is_empty()
+                        {
+                            self.markers.remove(index);
+                            self.ui.selected_marker_index = self.markers.add(input, marker.time);
+                            self.save_markers();
+                        }
+                    }
+                    PromptKind::GoTo => {
+                        match parse_time_input(
+                            &input,
+                            self.audio_file.sample_rate(),
+                            self.audio_file.duration().as_secs_f64(),
+                        ) {
+                            Ok(time) => self.seek_to(time),
+                            Err(err) => self.handle_error(err.to_string()),
+                        }
+                    }
+                    PromptKind::SavePlaylist => {
+                        let path = self.current_directory.join(input);
+                        if let Err(err) = self.playlist.write_m3u(&path) {
+                            self.handle_error(format!("Error saving playlist: {err}"));
+                        }
+                    }
+                    PromptKind::RawFormat(path, autoplay) => match input.parse::<RawFormat>() {
+                        Ok(format) => {
+                            self.raw_formats.insert(path.clone(), format);
+                            self.load_audio_file(path, None, autoplay);
+                        }
+                        Err(err) => self.handle_error(format!("Invalid raw format: {err}")),
+                    },
+                    PromptKind::StreamUrl => self.open_stream(input),
+                    PromptKind::OpenRecording(path) => {
+                        self.set_mode(Mode::Player);
+                        self.select_audio_file(path, false);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Index of the chapter at the playhead.
+    fn current_chapter(&self) -> Option<usize> {
+        let time = self.current_time();
+        self.audio_file
+            .chapters()
+            .iter()
+            .rposition(|c| c.start <= time)
+    }
+
+    /// Current playhead position in seconds.
+    fn current_time(&self) -> f64 {
+        self.waveform.playhead as f64 / self.audio_file.sample_rate() as f64
+    }
+
+    /// Returns the seek step of the arrow keys depending on the pressed modifiers.
+    fn seek_step(&self, modifiers: KeyModifiers) -> Duration {
+        let step = if modifiers.contains(KeyModifiers::SHIFT) {
+            self.config.seek.large_step
+        } else if modifiers.contains(KeyModifiers::CONTROL) {
+            self.config.seek.small_step
+        } else {
+            self.config.seek.step
+        };
+        Duration::from_secs_f64(step.max(0.))
+    }
+
+    /// Duration of one analysis frame in seconds.
+    fn analysis_frame_duration(&self) -> f64 {
+        let channels = (self.audio_file.channels() as usize).max(1);
+        (POSITION_UPDATE_INTERVAL / channels) as f64 / self.audio_file.sample_rate() as f64
+    }
+
+    /// Moves the playhead to `time` (in seconds).
+    fn seek_to(&mut self, time: f64) {
+        self.lufs = [-100.; 300];
+        self.file_analyzer.reset();
+        let pos = Duration::from_secs_f64(time.max(0.));
+        if let Err(_err) = self.player_command_tx.send(PlayerCommand::SeekTo(pos)) {
+            //TODO: log sending error
+        }
+    }
+
+    fn save_markers(&mut self) {
+        if let Err(err) = self.markers.save_for(self.audio_file.path()) {
+            self.handle_error(format!("Error saving markers: {err}"));
+        }
+    }
+
+    /// Sets the playback volume clamped to `0..=MAX_VOLUME`.
+    fn set_volume(&mut self, volume: f32) {
+        // rounding keeps the displayed percentage stable after many steps
+        self.settings.volume = (volume.clamp(0., MAX_VOLUME) * 100.).round() / 100.;
+        if let Err(_err) = self
+            .player_command_tx
+            .send(PlayerCommand::SetVolume(self.settings.volume))
+        {
+            //TODO: log sending error
+        }
+    }
+
+    /// Switches playback to the output device with the given index in the output devices list
+    /// and remembers the choice in the config.
+    fn select_output_device(&mut self, index: usize) {
+        let Some(name) = self.ui.output_devices.get(index).cloned() else {
+            self.handle_error(format!("Invalid device index: {}", index + 1));
+            return;
+        };
+        let (reply_tx, reply_rx) = crossbeam::channel::bounded(1);
+        if let Err(_err) = self
+            .player_command_tx
+            .send(PlayerCommand::SelectOutputDevice(name.clone(), reply_tx))
+        {
+            //TODO: log sending error
+        }
+        self.ui.show_output_devices_list = false;
+        self.output_device_switch = Some((name, reply_rx));
+    }
+
+    /// Shows and saves the output device once the player has switched to it,
+    /// a failed switch is reported through the error channel.
+    fn receive_output_device(&mut self) {
+        let Some((name, reply_rx)) = &self.output_device_switch else {
+            return;
+        };
+        let switched = match reply_rx.try_recv() {
+            Ok(switched) => switched,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => false,
+        };
+        let name = name.clone();
+        self.output_device_switch = None;
+        if !switched {
+            return;
+        }
+        self.ui.output_device_name.clone_from(&name);
+        self.ui.needs_render = true;
+        self.config.output_device = Some(name.clone());
+        // only the device is written, the rest of the file stays as the user wrote it
+        if let Err(err) = Config::save_output_device(&name) {
+            self.handle_error(format!("Error saving config: {err}"));
+        }
+    }
+
+    /// Switches the mode, pausing the microphone when it is not used.
+    fn set_mode(&mut self, mode: Mode) {
+        if mode != Mode::Microphone {
+            self.stop_recording(true);
+            self.disarm_trigger();
+        }
+        self.alarms.reset();
+        if !matches!(self.settings.mode, Mode::Player) {
+            self.reset_charts();
+        }
+        self.swap_live_input(self.settings.mode);
+        if let Some(stream) = self.audio_capture_stream.as_ref() {
+            if matches!(mode, Mode::Microphone) {
+                let _ = stream.play();
+            } else {
+                let _ = stream.pause();
+            }
+        }
+        self.swap_live_input(mode);
+        self.settings.mode = mode;
+        if mode == Mode::System && self.system.is_none() {
+            self.ui.system_sources = list_system_sources();
+            if self.ui.system_sources.is_empty() {
+                self.handle_error(String::from(
+                    "No system output source found, it needs PulseAudio/PipeWire (pactl and parec) \
+                     or an ALSA loopback device (arecord)",
+                ));
+            } else if let Err(err) = self.select_system_source(0) {
+                self.handle_error(format!("Failed to capture the system output: {err}"));
+            }
+        }
+    }
+
+    /// Names shown in the devices list, the input devices or the system output sources.
+    fn device_names(&self) -> Vec<String> {
+        if self.settings.mode == Mode::System {
+            return self
+                .ui
+                .system_sources
+                .iter()
+                .map(|s| s.name().to_string())
+                .collect();
+        }
+        list_input_devs()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// Captures one of the listed system output sources, replacing the current capture.
+    fn select_system_source(&mut self, index: usize) -> Result<()> {
+        let source = self
+            .ui
+            .system_sources
+            .get(index)
+            .cloned()
+            .ok_or_else(|| eyre!("Invalid source index: {}", index + 1))?;
+        let thread = audio_capture::capture_system(&source, self.system_log.clone())?;
+        let is_active = self.settings.mode == Mode::System;
+        if is_active {
+            self.swap_live_input(Mode::System);
+            self.reset_charts();
+        }
+        self.system = Some(LiveInput::new(thread, source.name().to_string()));
+        if is_active {
+            self.swap_live_input(Mode::System);
+        }
+        self.ui.show_devices_list = false;
+        Ok(())
+    }
+
+    /// Input of a live mode other than the microphone.
+    fn live_input(&self, mode: Mode) -> Option<&LiveInput> {
+        match mode {
+            Mode::Stdin => self.stdin.as_ref(),
+            Mode::Stream => self.stream.as_ref(),
+            Mode::System => self.system.as_ref(),
+            _ => None,
+        }
+    }
+
+    fn live_input_mut(&mut self, mode: Mode) -> Option<&mut LiveInput> {
+        match mode {
+            Mode::Stdin => self.stdin.as_mut(),
+            Mode::Stream => self.stream.as_mut(),
+            Mode::System => self.system.as_mut(),
+            _ => None,
+        }
+    }
+
+    /// Puts the buffer and analyzer of the input of `mode` in place of the microphone ones, or back.
+    fn swap_live_input(&mut self, mode: Mode) {
+        let input = match mode {
+            Mode::Stdin => &mut self.stdin,
+            Mode::Stream => &mut self.stream,
+            Mode::System => &mut self.system,
+            _ => return,
+        };
+        if let Some(input) = input {
+            std::mem::swap(&mut self.latest_captured_samples, &mut input.buffer);
+            std::mem::swap(&mut self.device_analyzer, &mut input.analyzer);
+        }
+    }
+
+    /// Connects to a stream and switches to [`Mode::Stream`], replacing the previous stream.
+    fn open_stream(&mut self, url: String) {
+        let is_active = matches!(self.settings.mode, Mode::Stream);
+        if is_active {
+            self.swap_live_input(Mode::Stream);
+            self.reset_charts();
+        }
+        self.stream = Some(LiveInput::new(stream_input::spawn(url.clone()), url));
+        if is_active {
+            self.swap_live_input(Mode::Stream);
+        } else {
+            self.set_mode(Mode::Stream);
+        }
+    }
+
+    /// Starts recording the input device to the recording directory, or stops the recording.
+    fn toggle_recording(&mut self) {
+        if self.recording_elapsed().is_some() {
+            self.stop_recording(true);
+            return;
+        }
+        let Some((sample_rate, channels)) = self.capture_format else {
+            self.handle_error(String::from("Select an input device with d to record it"));
+            return;
+        };
+        match Recorder::create(&self.config.recording.directory(), sample_rate, channels) {
+            Ok(recorder) => *self.recorder.lock().unwrap() = Some(recorder),
+            Err(err) => self.handle_error(format!("Could not start recording: {err}")),
+        }
+    }
+
+    /// Finishes the recording, if any, and offers to open it when `ask_to_open` is set.
+    fn stop_recording(&mut self, ask_to_open: bool) {
+        let Some(recorder) = self.recorder.lock().unwrap().take() else {
+            return;
+        };
+        match recorder.finish() {
+            Ok(path) if ask_to_open => {
+                self.prompt = Some(Prompt::new(PromptKind::OpenRecording(path)));
+            }
+            Ok(_) => (),
+            Err(err) => self.handle_error(format!("Error saving the recording: {err}")),
+        }
+    }
+
+    /// Length of the recording in progress.
+    fn recording_elapsed(&self) -> Option<Duration> {
+        self.recorder
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(Recorder::elapsed)
+    }
+
+    /// Arms triggered recording, which saves a file every time the input exceeds the threshold
+    /// of the config, or disarms it.
+    fn toggle_trigger(&mut self) {
+        if self.trigger.lock().unwrap().is_some() {
+            self.disarm_trigger();
+            return;
+        }
+        let Some((sample_rate, channels)) = self.capture_format else {
+            self.handle_error(String::from("Select an input device with d to record it"));
+            return;
+        };
+        let recording = &self.config.recording;
+        match Trigger::new(
+            recording.trigger,
+            recording.directory(),
+            sample_rate,
+            channels,
+        ) {
+            Ok(trigger) => *self.trigger.lock().unwrap() = Some(trigger),
+            Err(err) => self.handle_error(format!("Could not arm the trigger: {err}")),
+        }
+    }
+
+    /// Finishes the recording of the current event, if any, and disarms the trigger.
+    fn disarm_trigger(&mut self) {
+        // the lock is released first, the capture callback must not wait for the disk
+        let trigger = self.trigger.lock().unwrap().take();
+        if let Some(trigger) = trigger {
+            trigger.disarm();
+        }
+    }
+
+    /// `armed`, or the length of the event being recorded, with the number of recorded events.
+    fn trigger_status(&self) -> Option<String> {
+        let trigger = self.trigger.lock().unwrap();
+        let trigger = trigger.as_ref()?;
+        let state = match trigger.elapsed() {
+            Some(elapsed) => format!("● trig {}", format_mm_ss(elapsed)),
+            None => String::from("armed"),
+        };
+        Some(format!("{state} ({})", trigger.recordings()))
+    }
+
+    /// Starts or stops logging the loudness of the input of the current mode to daily files.
+    fn toggle_log(&mut self) {
+        let (log, format) = match self.settings.mode {
+            Mode::System => (
+                self.system_log.clone(),
+                self.system
+                    .as_ref()
+                    .map(|_| (SYSTEM_FORMAT.sample_rate, SYSTEM_FORMAT.channels as u16)),
+            ),
+            _ => (self.device_log.clone(), self.capture_format),
+        };
+        let mut log = log.lock().unwrap();
+        if let Some(old) = log.take() {
+            // the lock is released first, the capture must not wait for the disk
+            drop(log);
+            old.stop();
+            return;
+        }
+        let Some((sample_rate, channels)) = format else {
+            drop(log);
+            self.handle_error(String::from("Select an input with d to log it"));
+            return;
+        };
+        match LoudnessLog::new(&self.config.log, sample_rate, channels as u32) {
+            Ok(new_log) => *log = Some(new_log),
+            Err(err) => {
+                drop(log);
+                self.handle_error(format!("Could not start the loudness log: {err}"));
+            }
+        }
+    }
+
+    /// `log`, with the number of lines written.
+    fn log_status(&self, log: &SharedLog) -> Option<String> {
+        let log = log.lock().unwrap();
+        log.as_ref().map(|log| format!("log ({})", log.lines()))
+    }
+
+    /// Stops a recording the capture stream could not write, e.g. because the disk is full.
+    fn check_recording(&mut self) {
+        let error = self
+            .recorder
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|r| r.error().map(String::from));
+        if let Some(error) = error {
+            self.stop_recording(false);
+            self.handle_error(format!("Recording stopped: {error}"));
+        }
+        let error = self
+            .trigger
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|t| t.error().map(String::from));
+        if let Some(error) = error {
+            self.disarm_trigger();
+            self.handle_error(format!("Triggered recording stopped: {error}"));
+        }
+        for log in [self.device_log.clone(), self.system_log.clone()] {
+            let error = log
+                .lock()
+                .unwrap()
+                .as_ref()
+                .and_then(|l| l.error().map(String::from));
+            if let Some(error) = error {
+                LoudnessLog::stop_shared(&log);
+                self.handle_error(format!("Loudness log stopped: {error}"));
+            }
+        }
+    }
+
+    fn receive_input_events(&mut self, mode: Mode) {
+        let Some(input) = self.live_input(mode) else {
+            return;
+        };
+        let events: Vec<InputEvent> = input.thread.events.try_iter().collect();
+        for event in events {
+            match event {
+                InputEvent::Started {
+                    buffer,
+                    sample_rate,
+                    description,
+                } => {
+                    let mut analyzer = Analyzer::default();
+                    if let Err(err) = analyzer.create_loudness_meter(2, sample_rate) {
+                        self.handle_error(format!(
+                            "Could not create an analyzer for {}: {err}",
+                            mode.to_string().to_lowercase()
+                        ));
+                    }
+                    // replace the input in place if it is in use
+                    let is_active = self.settings.mode == mode;
+                    if is_active {
+                        self.swap_live_input(mode);
+                    }
+                    if let Some(input) = self.live_input_mut(mode) {
+                        input.buffer = buffer;
+                        input.analyzer = analyzer;
+                        input.description = description;
+                    }
+                    if is_active {
+                        self.swap_live_input(mode);
+                    }
+                }
+                InputEvent::Title(title) => {
+                    if let Some(input) = self.live_input_mut(mode) {
+                        input.title = Some(title);
+                    }
+                }
+                InputEvent::Ended => {
+                    if let Some(input) = self.live_input_mut(mode) {
+                        input.ended = true;
+                    }
+                }
+                InputEvent::Error(err) => {
+                    if let Some(input) = self.live_input_mut(mode) {
+                        input.ended = true;
+                    }
+                    self.handle_error(err);
+                }
+            }
+            self.ui.needs_render = true;
+        }
+    }
+
     fn select_device(&mut self, index: usize) -> Result<()> {
+        if self.settings.mode == Mode::System {
+            return self.select_system_source(index);
+        }
         let devices = list_input_devs();
         if index > devices.len() - 1 {
             return Err(eyre!("Invalid device index: {}", index + 1));
         }
 
+        // the new device may have another format
+        self.stop_recording(true);
+        self.disarm_trigger();
+        LoudnessLog::stop_shared(&self.device_log);
+        self.alarms.reset();
         if let Some(stream) = &self.audio_capture_stream {
             stream.pause().unwrap();
             self.audio_capture_stream = None;
@@ -1785,8 +3945,19 @@ impl App {
         let latest_captured_samples = Arc::new(Mutex::new(buf));
         self.latest_captured_samples = latest_captured_samples;
 
+        match ProgramMeter::new(channels as u32, sr) {
+            Ok(meter) => *self.program_meter.lock().unwrap() = Some(meter),
+            Err(err) => {
+                *self.program_meter.lock().unwrap() = None;
+                self.handle_error(format!("Could not create a loudness meter: {err}"));
+            }
+        }
         let stream = match audio_capture::build_input_stream(
             self.latest_captured_samples.clone(),
+            self.program_meter.clone(),
+            self.recorder.clone(),
+            self.trigger.clone(),
+            self.device_log.clone(),
             &audio_device,
         ) {
             Ok(stream) => stream,
@@ -1795,6 +3966,7 @@ impl App {
             }
         };
         self.audio_capture_stream = Some(stream);
+        self.capture_format = Some((sr, channels));
         self.audio_capture_stream.as_ref().unwrap().play()?;
         self.ui.show_devices_list = false;
         if let Err(err) = self
@@ -1876,21 +4048,157 @@ impl App {
         self.ui.error_timer = Some(Instant::now());
     }
 
-    fn select_audio_file(&mut self, file_path: PathBuf) {
-        // reset everything
-        self.reset_charts();
-        self.ui.show_explorer = false;
+    /// Replaces the playlist with `files` and loads the first track.
+    fn open_files(&mut self, files: Vec<PathBuf>) {
+        if let Err(err) = self.playlist.set_tracks(files) {
+            self.handle_error(format!("Error reading playlist: {err}"));
+            return;
+        }
+        self.play_next_track(false);
+    }
+
+    fn play_next_track(&mut self, autoplay: bool) {
+        if let Some(path) = self.playlist.next().cloned() {
+            self.play_track(path, autoplay);
+        }
+    }
+
+    fn play_prev_track(&mut self) {
+        if let Some(path) = self.playlist.prev().cloned() {
+            self.play_track(path, self.is_playing_audio);
+        }
+    }
+
+    /// Loads a track of the playlist, starts playing it if `autoplay` is set.
+    fn play_track(&mut self, path: PathBuf, autoplay: bool) {
+        self.select_audio_file(path, autoplay);
+        self.ui.selected_playlist_index = self.playlist.current().unwrap_or(0);
+    }
+
+    /// Reloads the current file with another audio track.
+    fn select_audio_track(&mut self, index: usize) {
+        if index >= self.audio_file.tracks().len() {
+            self.handle_error(format!("Invalid track index: {}", index + 1));
+            return;
+        }
+        self.ui.show_tracks_list = false;
+        if index != self.audio_file.track() {
+            // the tracks of a container are in sync, keep the playhead
+            let start = Duration::from_secs_f64(self.current_time());
+            let path = self.audio_file.path().clone();
+            self.load_audio_file_from(path, Some(index), self.is_playing_audio, start);
+        }
+    }
 
+    /// Starts loading a file, with the track chosen on the command line if it was given there.
+    fn select_audio_file(&mut self, file_path: PathBuf, autoplay: bool) {
+        let track = self.cli_tracks.get(&file_path).copied();
+        self.load_audio_file(file_path, track, autoplay);
+    }
+
+    /// Starts loading a file in the background, see [`Loading`].
+    fn load_audio_file(&mut self, file_path: PathBuf, track: Option<usize>, autoplay: bool) {
+        self.load_audio_file_from(file_path, track, autoplay, Duration::ZERO);
+    }
+
+    /// Like [`App::load_audio_file`] but playback starts at `start`.
+    fn load_audio_file_from(
+        &mut self,
+        file_path: PathBuf,
+        track: Option<usize>,
+        autoplay: bool,
+        start: Duration,
+    ) {
+        self.ui.show_explorer = false;
+        if self.needs_raw_format(&file_path) {
+            // suggest the format of the open file, dumps often come in batches
+            let format = self
+                .raw_formats
+                .get(self.audio_file.path())
+                .copied()
+                .unwrap_or_default();
+            self.prompt = Some(
+                Prompt::new(PromptKind::RawFormat(file_path, autoplay))
+                    .with_input(&format.to_input()),
+            );
+            return;
+        }
+        self.cancel_loading();
+
+        let progress = Arc::new(LoadProgress::default());
+        let title = file_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let request = FileRequest {
+            raw: self.raw_formats.get(&file_path).copied(),
+            path: file_path,
+            track,
+            autoplay,
+            start,
+            progress: progress.clone(),
+        };
         if let Err(_err) = self
             .player_command_tx
-            .send(PlayerCommand::SelectFile(file_path))
+            .send(PlayerCommand::SelectFile(request))
         {
             //TODO: log sending error
         }
+        self.loading = Some(Loading {
+            title,
+            track,
+            autoplay,
+            reload: false,
+            progress,
+        });
+    }
+
+    /// Returns `true` if `path` is headerless PCM whose format has not been entered yet:
+    /// a `.raw`/`.pcm` file, or a file with an unknown extension and unrecognized content.
+    fn needs_raw_format(&self, path: &Path) -> bool {
+        if self.raw_formats.contains_key(path) {
+            return false;
+        }
+        let extension = path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        match extension.as_str() {
+            "raw" | "pcm" => true,
+            extension if SUPPORTED_FORMATS.contains(&extension) => false,
+            _ => matches!(probe::sniff_file(path), Ok(None)),
+        }
+    }
+
+    /// Loads the current file again keeping the track, the playhead and the play state.
+    fn reload_audio_file(&mut self) {
+        let start = Duration::from_secs_f64(self.current_time());
+        self.load_audio_file_from(
+            self.audio_file.path().clone(),
+            Some(self.audio_file.track()),
+            self.is_playing_audio,
+            start,
+        );
+        if let Some(loading) = &mut self.loading {
+            loading.reload = true;
+        }
+    }
+
+    /// Text of the watch indicator, e.g. "reloaded 5s ago".
+    fn reload_age(&self) -> Option<String> {
+        let secs = self.last_reload?.elapsed().as_secs();
+        Some(match secs {
+            0..60 => format!("reloaded {secs}s ago"),
+            60..3600 => format!("reloaded {}m ago", secs / 60),
+            _ => format!("reloaded {}h ago", secs / 3600),
+        })
+    }
 
-        // blocking receiver
-        if let Ok(audio_file) = self.audio_file_rx.recv() {
-            self.receive_audio_file(audio_file);
+    fn cancel_loading(&mut self) {
+        if let Some(loading) = self.loading.take() {
+            loading.progress.cancel();
         }
     }
 
@@ -1952,26 +4260,57 @@ impl App {
         let bd = s.fg(self.ui.theme.help.borders.unwrap());
         let hl = s.fg(self.ui.theme.help.highlight.unwrap());
 
-        let area = Self::get_popup_area_with_lenght(f.area(), 22, 42);
-        f.render_widget(Clear, area);
         let rows = vec![
             help_message_row!["1", "Toggle waveform", hl],
             help_message_row!["2", "Toggle spectrum", hl],
             help_message_row!["3", "Toggle LUFS", hl],
             help_message_row!["e", "Toggle explorer", hl],
             help_message_row!["m", "Change mode", hl],
+            help_message_row!["U", "Open an http:// (not https://) stream", hl],
             help_message_row!["d", "Toggle device list", hl],
+            help_message_row!["r", "Record the input device", hl],
+            help_message_row!["T", "Record when the input gets loud", hl],
+            help_message_row!["l", "Log the loudness of the input", hl],
+            help_message_row!["A", "Show the alarm log", hl],
+            help_message_row!["R", "Reset the integrated loudness (microphone)", hl],
+            help_message_row!["P", "Pause/resume the integrated loudness", hl],
+            help_message_row!["o", "Toggle output device list", hl],
+            help_message_row!["n", "Select audio track", hl],
+            help_message_row!["i", "Show file info and tags", hl],
+            help_message_row!["W", "Reload the file when it changes", hl],
             help_message_row!["t", "Select theme", hl],
             help_message_row!["?/h/F1", "Show this window", hl],
             help_message_row!["q/Ctrl+c", "Quit", hl],
             help_message_row!["q/Escape", "Close pop-up window", hl],
+            help_message_row!["Escape", "Cancel loading a file", hl],
             help_message_row!["M", "Toggle mid frequencies", hl],
             help_message_row!["S", "Toggle side frequencies", hl],
-            help_message_row!["Right", "Jump forward 5s", hl],
-            help_message_row!["Left", "Jump back 5s", hl],
+            help_message_row!["k", "Drop a marker", hl],
+            help_message_row!["K", "Show markers", hl],
+            help_message_row!["[/]", "Previous/next marker", hl],
+            help_message_row!["C", "Show chapters and their loudness", hl],
+            help_message_row!["{/}", "Previous/next chapter", hl],
+            help_message_row!["p", "Show playlist", hl],
+            help_message_row!["</>", "Previous/next track", hl],
+            help_message_row!["Space", "Select files in explorer", hl],
+            help_message_row!["Right", "Jump forward", hl],
+            help_message_row!["Left", "Jump back", hl],
+            help_message_row!["Shift+Arrow", "Jump by a large step", hl],
+            help_message_row!["Ctrl+Arrow", "Jump by a small step", hl],
+            help_message_row![",/.", "Step one analysis frame", hl],
+            help_message_row!["Home/End", "Go to start/end", hl],
+            help_message_row!["g", "Go to time", hl],
             help_message_row!["Space", "Play/Pause", hl],
+            help_message_row!["9/0", "Volume down/up", hl],
+            help_message_row!["u", "Mute", hl],
+            help_message_row!["c", "Cycle monitoring (L/R/mono/mid/side)", hl],
+            help_message_row!["Drag spectrum", "Solo a frequency range", hl],
+            help_message_row!["f", "Band-pass/bell boost solo", hl],
+            help_message_row!["x", "Remove the solo", hl],
             help_message_row!["-/_", "Zoom waveform in", hl],
             help_message_row!["=/+", "Zoom waveform out", hl],
+            help_message_row!["Click/drag", "Seek on the waveform", hl],
+            help_message_row!["Scroll", "Zoom waveform", hl],
             help_message_row!["1-9", "Select device/theme", hl],
             Row::new(vec![
                 Cell::new("Up/Down".to_line().style(hl).centered()),
@@ -1982,6 +4321,9 @@ impl App {
             ])
             .height(2),
         ];
+        // every row is one line high except the last one, plus borders
+        let area = Self::get_popup_area_with_lenght(f.area(), rows.len() as u16 + 3, 42);
+        f.render_widget(Clear, area);
         let widths = [Constraint::Percentage(30), Constraint::Percentage(70)];
         let table = Table::new(rows, widths).style(s).block(
             Block::bordered()
@@ -2127,6 +4469,18 @@ impl App {
         false
     }
 
+    /// Maps a column of the fft chart to a frequency in Hz. Columns outside the chart are clamped.
+    fn map_column_to_hz(&self, column: u16) -> f32 {
+        let Some(r) = self.ui.chart_rect else {
+            return 20.;
+        };
+        // same boundaries as in `in_fft_chart`
+        let x_min = r.x + 8;
+        let x_max = r.x + r.width - 1;
+        let x = column.clamp(x_min, x_max) - x_min;
+        Self::map_mouse_position_to_chart_point(x, x_max - x_min, 0, 1).0
+    }
+
     fn map_mouse_position_to_chart_point(x: u16, max_x: u16, y: u16, max_y: u16) -> (f32, f32) {
         // x
         let min_freq_log = 20f32.log10();
@@ -2146,14 +4500,68 @@ impl App {
     }
 }
 
-fn config_dir() -> Option<PathBuf> {
-    if cfg!(target_os = "macos") {
-        // On macOS, use ~/.config instead of ~/Library/Application Support
-        let home = std::env::var("HOME").ok()?;
-        Some(PathBuf::from(home).join(".config"))
+/// Maps a frequency in Hz to the logarithmic x axis of the fft chart (0..100).
+fn map_hz_to_chart_x(hz: f32) -> f64 {
+    let min_freq_log = 20f64.log10();
+    let max_freq_log = 20000f64.log10();
+    (((hz as f64).log10() - min_freq_log) / (max_freq_log - min_freq_log) * 100.).clamp(0., 100.)
+}
+
+/// Formats a frequency as `250Hz` or `1.2kHz`.
+fn format_hz(hz: f32) -> String {
+    if hz < 1000. {
+        format!("{hz:.0}Hz")
+    } else {
+        format!("{:.1}kHz", hz / 1000.)
+    }
+}
+
+/// Parses the input of the "go to" prompt and returns the time in seconds.
+/// Accepts `[hh:]mm:ss[.mmm]`, seconds with a fraction (`12.5`), samples (`44100`)
+/// and percent of the duration (`50%`).
+fn parse_time_input(input: &str, sample_rate: u32, duration: f64) -> Result<f64> {
+    let input = input.trim();
+    let invalid = || eyre!("Invalid time: {input}. Use mm:ss.mmm, samples or percent.");
+
+    let time = if let Some(percent) = input.strip_suffix('%') {
+        let percent: f64 = percent.trim().parse().map_err(|_| invalid())?;
+        duration * percent / 100.
+    } else if input.contains(':') {
+        input.split(':').try_fold(0., |acc, part| {
+            part.parse::<f64>()
+                .ok()
+                .filter(|v| *v >= 0.)
+                .map(|v| acc * 60. + v)
+                .ok_or_else(invalid)
+        })?
+    } else if input.contains('.') {
+        input.parse().map_err(|_| invalid())?
     } else {
-        dirs::config_local_dir()
+        let samples: u64 = input.parse().map_err(|_| invalid())?;
+        samples as f64 / sample_rate as f64
+    };
+
+    if !time.is_finite() || time < 0. {
+        return Err(invalid());
     }
+    Ok(time.min(duration))
+}
+
+/// Formats seconds as `mm:ss.mmm`.
+fn format_time(seconds: f64) -> String {
+    let millis = (seconds.max(0.) * 1000.).round() as u64;
+    format!(
+        "{:0>2}:{:0>2}.{:0>3}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Formats a duration as `mm:ss`, e.g. the length of a recording.
+fn format_mm_ss(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}", secs / 60, secs % 60)
 }
 
 /// pub run function that initializes the terminal and runs the application
@@ -2164,7 +4572,7 @@ pub fn run(
     playback_position_rx: Receiver<usize>,
     error_rx: Receiver<String>,
     latest_captured_samples: RBuffer,
-    startup_file: Option<PathBuf>,
+    args: Args,
 ) -> Result<()> {
     let terminal = ratatui::init();
     ratatui::crossterm::execute!(
@@ -2179,7 +4587,7 @@ pub fn run(
         error_rx,
         latest_captured_samples,
     )?
-    .run(terminal, startup_file);
+    .run(terminal, args);
     ratatui::restore();
     app_result
 }
@@ -2246,6 +4654,44 @@ mod tests {
         assert!(popup_area.y > area.y);
     }
 
+    #[test]
+    fn test_map_column_to_waveform_millis() {
+        let rect = Rect::new(10, 0, 102, 20);
+
+        // borders are outside of the chart
+        assert!(App::map_column_to_waveform_millis(10, rect, 0., 15000.).is_none());
+        assert!(App::map_column_to_waveform_millis(111, rect, 0., 15000.).is_none());
+
+        // first and last columns map to the bounds
+        assert_eq!(
+            App::map_column_to_waveform_millis(11, rect, 5000., 20000.),
+            Some(5000.)
+        );
+        assert_eq!(
+            App::map_column_to_waveform_millis(110, rect, 5000., 20000.),
+            Some(20000.)
+        );
+    }
+
+    #[test]
+    fn test_parse_time_input() {
+        let duration = 600.;
+        assert_eq!(
+            parse_time_input("01:30.250", 48000, duration).unwrap(),
+            90.25
+        );
+        assert_eq!(parse_time_input("0:05:00", 48000, duration).unwrap(), 300.);
+        assert_eq!(parse_time_input("12.5", 48000, duration).unwrap(), 12.5);
+        assert_eq!(parse_time_input("96000", 48000, duration).unwrap(), 2.);
+        assert_eq!(parse_time_input("50%", 48000, duration).unwrap(), 300.);
+        // clamped to the duration
+        assert_eq!(parse_time_input("20:00", 48000, duration).unwrap(), 600.);
+
+        assert!(parse_time_input("", 48000, duration).is_err());
+        assert!(parse_time_input("1:-5", 48000, duration).is_err());
+        assert!(parse_time_input("abc", 48000, duration).is_err());
+    }
+
     #[test]
     fn test_error_timer_logic() {
         let (mut app, _, _) = create_test_app();
@@ -0,0 +1,1488 @@
+//! WavPack (`.wv`) files, which symphonia has no reader or decoder for.
+//! [`WavPackReader`] splits a file into frames, the blocks holding the same samples of every
+//! channel, and [`WavPackDecoder`] decodes them. Lossless 8, 16 and 24 bit audio is decoded,
+//! hybrid (lossy), float, 32 bit and DSD files are rejected as unsupported when they are opened.
+//! The reader can't seek, the file is decoded as a whole and playback seeks in the decoded
+//! samples. The APE tags at the end of the file are not read.
+use std::io;
+use symphonia::core::{
+    audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec},
+    codecs::{
+        CODEC_TYPE_WAVPACK, CodecDescriptor, CodecParameters, Decoder, DecoderOptions,
+        FinalizeResult,
+    },
+    errors::{Error, Result, SeekErrorKind, decode_error, seek_error, unsupported_error},
+    formats::{Cue, FormatOptions, FormatReader, Packet, SeekMode, SeekTo, SeekedTo, Track},
+    io::{MediaSourceStream, ReadBytes},
+    meta::{Metadata, MetadataLog},
+    probe::{Descriptor, Instantiate, QueryDescriptor},
+    support_codec, support_format,
+    units::TimeBase,
+};
+
+const HEADER_LEN: usize = 32;
+/// Samples per channel a block may hold, the encoder keeps them below 150000 for all channels
+const MAX_BLOCK_SAMPLES: u32 = 1 << 18;
+
+// Flags of the block header
+const BYTES_STORED: u32 = 0x3;
+const MONO_FLAG: u32 = 0x4;
+const HYBRID_FLAG: u32 = 0x8;
+const JOINT_STEREO: u32 = 0x10;
+const FLOAT_DATA: u32 = 0x80;
+const INITIAL_BLOCK: u32 = 0x800;
+const FINAL_BLOCK: u32 = 0x1000;
+const SHIFT_LSB: u32 = 13;
+const SRATE_LSB: u32 = 23;
+const FALSE_STEREO: u32 = 0x4000_0000;
+const DSD_FLAG: u32 = 0x8000_0000;
+/// The block holds one channel, of a mono file or of a stereo pair with identical channels
+const MONO_DATA: u32 = MONO_FLAG | FALSE_STEREO;
+
+// Metadata sub-blocks, the low 6 bits of their id
+const ID_DECORR_TERMS: u8 = 0x2;
+const ID_DECORR_WEIGHTS: u8 = 0x3;
+const ID_DECORR_SAMPLES: u8 = 0x4;
+const ID_ENTROPY_VARS: u8 = 0x5;
+const ID_INT32_INFO: u8 = 0x9;
+const ID_WV_BITSTREAM: u8 = 0xa;
+const ID_CHANNEL_INFO: u8 = 0xd;
+const ID_SAMPLE_RATE: u8 = 0x27;
+const ID_ODD_SIZE: u8 = 0x40;
+const ID_LARGE: u8 = 0x80;
+
+/// Sample rates of the 4 bit index in the flags, 15 means the rate is in a sub-block
+const SAMPLE_RATES: [u32; 15] = [
+    6000, 8000, 9600, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200, 96000,
+    192000,
+];
+
+/// The fixed 32 bytes at the start of every block.
+#[derive(Clone, Copy, Debug)]
+struct BlockHeader {
+    /// Length of the whole block, header included
+    len: usize,
+    /// Samples per channel of the file, `None` if unknown
+    total_samples: Option<u64>,
+    block_index: u64,
+    block_samples: u32,
+    flags: u32,
+    crc: u32,
+}
+
+impl BlockHeader {
+    /// Checks the id, length and version at the start of a header, the first 10 bytes.
+    fn is_valid_start(header: &[u8]) -> bool {
+        header.len() >= 10
+            && &header[..4] == b"wvpk"
+            && u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize + 8 >= HEADER_LEN
+            && (0x402..=0x410).contains(&u16::from_le_bytes([header[8], header[9]]))
+    }
+
+    fn parse(header: &[u8]) -> Result<Self> {
+        if header.len() < HEADER_LEN || &header[..4] != b"wvpk" {
+            return decode_error("wavpack: missing block header");
+        }
+        if !Self::is_valid_start(header) {
+            return decode_error("wavpack: invalid block header");
+        }
+        let u32_at = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+        let size = u32_at(4) as usize + 8;
+        // the upper 8 bits of 40 bit counts are stored separately
+        let total_samples = match u32_at(12) {
+            u32::MAX => None,
+            low => Some(low as u64 + ((header[11] as u64) << 32) - header[11] as u64),
+        };
+        Ok(Self {
+            len: size,
+            total_samples,
+            block_index: u32_at(16) as u64 + ((header[10] as u64) << 32),
+            block_samples: u32_at(20),
+            flags: u32_at(24),
+            crc: u32_at(28),
+        })
+    }
+
+    fn is_mono_data(&self) -> bool {
+        self.flags & MONO_DATA != 0
+    }
+
+    /// Channels of the file decoded from this block
+    fn channels(&self) -> usize {
+        if self.flags & MONO_FLAG != 0 { 1 } else { 2 }
+    }
+
+    fn bytes_per_sample(&self) -> u32 {
+        (self.flags & BYTES_STORED) + 1
+    }
+
+    /// Rejects the modes the decoder doesn't handle.
+    fn check_supported(&self) -> Result<()> {
+        if self.flags & DSD_FLAG != 0 {
+            unsupported_error("wavpack: DSD audio is not supported")
+        } else if self.flags & HYBRID_FLAG != 0 {
+            unsupported_error("wavpack: hybrid (lossy) audio is not supported")
+        } else if self.flags & FLOAT_DATA != 0 {
+            unsupported_error("wavpack: float audio is not supported")
+        } else if self.bytes_per_sample() == 4 {
+            unsupported_error("wavpack: 32 bit audio is not supported")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Splits a block into its metadata sub-blocks, the id and the data of each.
+fn sub_blocks(mut data: &[u8]) -> Result<Vec<(u8, &[u8])>> {
+    let mut blocks = Vec::new();
+    while data.len() >= 2 {
+        let id = data[0];
+        let (words, start) = if id & ID_LARGE != 0 {
+            if data.len() < 4 {
+                return decode_error("wavpack: truncated metadata");
+            }
+            (
+                u32::from_le_bytes([data[1], data[2], data[3], 0]) as usize,
+                4,
+            )
+        } else {
+            (data[1] as usize, 2)
+        };
+        // the length is counted in 16 bit words, an odd length is padded
+        let end = start + words * 2;
+        if end > data.len() {
+            return decode_error("wavpack: truncated metadata");
+        }
+        let len = if id & ID_ODD_SIZE != 0 && words > 0 {
+            words * 2 - 1
+        } else {
+            words * 2
+        };
+        blocks.push((id & !(ID_LARGE | ID_ODD_SIZE), &data[start..start + len]));
+        data = &data[end..];
+    }
+    Ok(blocks)
+}
+
+/// Reads the blocks of a `.wv` file, a packet is a frame with the blocks of all channels.
+pub struct WavPackReader {
+    reader: MediaSourceStream,
+    tracks: Vec<Track>,
+    metadata: MetadataLog,
+    /// The first frame, read to know the format of the file
+    first: Option<Packet>,
+}
+
+impl WavPackReader {
+    /// Reads the blocks of the next frame, the end of the stream is an I/O error like for the
+    /// other readers. Trailing APE or ID3 tags end the stream as well.
+    fn read_frame(&mut self) -> Result<(BlockHeader, Vec<u8>)> {
+        let mut frame = Vec::new();
+        let mut first = None;
+        loop {
+            let mut header = [0u8; HEADER_LEN];
+            self.reader.read_buf_exact(&mut header)?;
+            if &header[..4] != b"wvpk" {
+                return Err(Error::IoError(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "end of the WavPack blocks",
+                )));
+            }
+            let block = BlockHeader::parse(&header)?;
+            let mut data = vec![0u8; block.len - HEADER_LEN];
+            self.reader.read_buf_exact(&mut data)?;
+            // blocks without samples only hold e.g. the trailer of the original file
+            if block.block_samples == 0 {
+                continue;
+            }
+            if block.flags & INITIAL_BLOCK != 0 {
+                frame.clear();
+                first = Some(block);
+            }
+            frame.extend_from_slice(&header);
+            frame.extend_from_slice(&data);
+            if block.flags & FINAL_BLOCK != 0
+                && let Some(first) = first
+            {
+                return Ok((first, frame));
+            }
+        }
+    }
+}
+
+impl QueryDescriptor for WavPackReader {
+    fn query() -> &'static [Descriptor] {
+        &[support_format!(
+            "wavpack",
+            "WavPack",
+            &["wv"],
+            &["audio/x-wavpack"],
+            &[b"wvpk"]
+        )]
+    }
+
+    fn score(context: &[u8]) -> u8 {
+        if BlockHeader::is_valid_start(context) {
+            255
+        } else {
+            0
+        }
+    }
+}
+
+impl FormatReader for WavPackReader {
+    fn try_new(source: MediaSourceStream, _options: &FormatOptions) -> Result<Self> {
+        let mut reader = Self {
+            reader: source,
+            tracks: Vec::new(),
+            metadata: MetadataLog::default(),
+            first: None,
+        };
+        let (header, frame) = reader.read_frame()?;
+
+        // the format of the file is described by the first block, the channels by all of them
+        let mut custom_rate = None;
+        let mut channel_info = None;
+        let mut channels = 0;
+        let mut data = frame.as_slice();
+        while !data.is_empty() {
+            let block = BlockHeader::parse(data)?;
+            block.check_supported()?;
+            channels += block.channels();
+            if block.flags & INITIAL_BLOCK != 0 {
+                for (id, sub_block) in sub_blocks(&data[HEADER_LEN..block.len])? {
+                    match id {
+                        ID_SAMPLE_RATE if sub_block.len() >= 3 => {
+                            custom_rate = Some(
+                                sub_block
+                                    .iter()
+                                    .rev()
+                                    .fold(0, |rate, &b| rate << 8 | b as u32),
+                            );
+                        }
+                        ID_CHANNEL_INFO if !sub_block.is_empty() && sub_block.len() <= 5 => {
+                            let mask = sub_block[1..]
+                                .iter()
+                                .rev()
+                                .fold(0, |mask, &b| mask << 8 | b as u32);
+                            channel_info = Some((sub_block[0] as usize, mask));
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            data = &data[block.len..];
+        }
+        let index = (header.flags >> SRATE_LSB & 0xf) as usize;
+        let Some(sample_rate) = custom_rate.or(SAMPLE_RATES.get(index).copied()) else {
+            return decode_error("wavpack: unknown sample rate");
+        };
+        let (count, mask) = channel_info.unwrap_or((channels, 0));
+        if count != channels {
+            return decode_error("wavpack: the blocks don't match the channel count");
+        }
+        // the mask has the bits of a WAV channel mask like `Channels`, without it the first
+        // `n` channels are used
+        let channels = Channels::from_bits(mask)
+            .filter(|c| c.count() == count)
+            .or_else(|| {
+                u32::try_from(count)
+                    .ok()
+                    .filter(|&n| n < u32::BITS)
+                    .and_then(|n| Channels::from_bits((1 << n) - 1))
+            });
+        let Some(channels) = channels else {
+            return unsupported_error("wavpack: unsupported channel count");
+        };
+
+        let mut params = CodecParameters::new();
+        params
+            .for_codec(CODEC_TYPE_WAVPACK)
+            .with_sample_rate(sample_rate)
+            .with_time_base(TimeBase::new(1, sample_rate))
+            .with_channels(channels)
+            .with_bits_per_sample(header.bytes_per_sample() * 8)
+            .with_max_frames_per_packet(MAX_BLOCK_SAMPLES as u64);
+        if let Some(frames) = header.total_samples {
+            params.with_n_frames(frames);
+        }
+        reader.tracks.push(Track::new(0, params));
+        reader.first = Some(Packet::new_from_boxed_slice(
+            0,
+            header.block_index,
+            header.block_samples as u64,
+            frame.into_boxed_slice(),
+        ));
+        Ok(reader)
+    }
+
+    fn cues(&self) -> &[Cue] {
+        &[]
+    }
+
+    fn metadata(&mut self) -> Metadata<'_> {
+        self.metadata.metadata()
+    }
+
+    fn seek(&mut self, _mode: SeekMode, _to: SeekTo) -> Result<SeekedTo> {
+        // the whole file is decoded, playback seeks in the decoded samples
+        seek_error(SeekErrorKind::Unseekable)
+    }
+
+    fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    fn next_packet(&mut self) -> Result<Packet> {
+        if let Some(packet) = self.first.take() {
+            return Ok(packet);
+        }
+        let (header, frame) = self.read_frame()?;
+        Ok(Packet::new_from_boxed_slice(
+            0,
+            header.block_index,
+            header.block_samples as u64,
+            frame.into_boxed_slice(),
+        ))
+    }
+
+    fn into_inner(self: Box<Self>) -> MediaSourceStream {
+        self.reader
+    }
+}
+
+/// Decodes the frames read by [`WavPackReader`].
+pub struct WavPackDecoder {
+    params: CodecParameters,
+    buf: AudioBuffer<f32>,
+}
+
+impl Decoder for WavPackDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        let (Some(sample_rate), Some(channels)) = (params.sample_rate, params.channels) else {
+            return unsupported_error("wavpack: the sample rate and channels must be known");
+        };
+        Ok(Self {
+            params: params.clone(),
+            buf: AudioBuffer::new(
+                MAX_BLOCK_SAMPLES as u64,
+                SignalSpec::new(sample_rate, channels),
+            ),
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(CODEC_TYPE_WAVPACK, "wavpack", "WavPack")]
+    }
+
+    fn reset(&mut self) {}
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        self.buf.clear();
+        let mut data = packet.buf();
+        let mut channel = 0;
+        while !data.is_empty() {
+            let header = BlockHeader::parse(data)?;
+            if header.len > data.len() {
+                return decode_error("wavpack: truncated block");
+            }
+            if header.block_samples > MAX_BLOCK_SAMPLES {
+                return decode_error("wavpack: block too long");
+            }
+            if channel + header.channels() > self.buf.spec().channels.count() {
+                return decode_error("wavpack: more channels than announced");
+            }
+            if channel == 0 {
+                self.buf
+                    .render_reserved(Some(header.block_samples as usize));
+            } else if header.block_samples as usize != self.buf.frames() {
+                return decode_error("wavpack: the blocks of a frame differ in length");
+            }
+            let samples = decode_block(&header, &data[HEADER_LEN..header.len])?;
+            for (i, plane) in (channel..channel + header.channels()).enumerate() {
+                let stride = header.channels();
+                for (out, sample) in self
+                    .buf
+                    .chan_mut(plane)
+                    .iter_mut()
+                    .zip(samples[i..].iter().step_by(stride))
+                {
+                    *out = *sample;
+                }
+            }
+            channel += header.channels();
+            data = &data[header.len..];
+        }
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buf.as_audio_buffer_ref()
+    }
+}
+
+/// Reads the bitstreams of a block, least significant bit of every byte first.
+struct Bits<'a> {
+    data: &'a [u8],
+    /// Bits not read yet, the next one is the lowest
+    cache: u64,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            cache: 0,
+            count: 0,
+        }
+    }
+
+    fn refill(&mut self) -> Result<()> {
+        while self.count <= 56 {
+            let Some((&byte, rest)) = self.data.split_first() else {
+                break;
+            };
+            self.cache |= (byte as u64) << self.count;
+            self.count += 8;
+            self.data = rest;
+        }
+        if self.count == 0 {
+            return decode_error("wavpack: truncated bitstream");
+        }
+        Ok(())
+    }
+
+    fn bit(&mut self) -> Result<bool> {
+        if self.count == 0 {
+            self.refill()?;
+        }
+        let bit = self.cache & 1 != 0;
+        self.cache >>= 1;
+        self.count -= 1;
+        Ok(bit)
+    }
+
+    /// Reads `n` bits, the first one read is the lowest.
+    fn bits(&mut self, n: u32) -> Result<u32> {
+        if n == 0 {
+            return Ok(0);
+        }
+        if self.count < n {
+            self.refill()?;
+            if self.count < n {
+                return decode_error("wavpack: truncated bitstream");
+            }
+        }
+        let value = (self.cache & ((1u64 << n) - 1)) as u32;
+        self.cache >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Counts ones up to `limit`, the zero ending them is read as well.
+    fn ones(&mut self, limit: u32) -> Result<u32> {
+        let mut ones = 0;
+        while ones < limit && self.bit()? {
+            ones += 1;
+        }
+        Ok(ones)
+    }
+
+    /// Reads a count coded as the number of its bits in unary, then the bits below the top one.
+    fn count(&mut self) -> Result<u32> {
+        let bits = self.ones(33)?;
+        match bits {
+            0 | 1 => Ok(bits),
+            33 => decode_error("wavpack: invalid count"),
+            _ => Ok(self.bits(bits - 1)? | 1 << (bits - 1)),
+        }
+    }
+
+    /// Reads a value from 0 to `max`, shorter codes are used for the lower values.
+    fn code(&mut self, max: u32) -> Result<u32> {
+        if max == 0 {
+            return Ok(0);
+        }
+        let bits = u32::BITS - max.leading_zeros();
+        let extras = ((1u64 << bits) - max as u64 - 1) as u32;
+        let mut code = self.bits(bits - 1)?;
+        if code >= extras {
+            code = (code << 1) - extras + self.bit()? as u32;
+        }
+        Ok(code)
+    }
+}
+
+/// `2^(log / 256 - 1)` of a logarithm stored in 16 bits, which has 8 fractional bits.
+fn exp2(log: i32) -> i32 {
+    if log < 0 {
+        return -exp2(-log);
+    }
+    let mantissa = 256. * (2f64.powf((log & 0xff) as f64 / 256.) - 1.);
+    let value = (mantissa.round() as i32) | 0x100;
+    match log >> 8 {
+        exp @ 0..=9 => value >> (9 - exp),
+        exp @ 10..=31 => value.wrapping_shl(exp as u32 - 9),
+        _ => i32::MIN,
+    }
+}
+
+/// Adaptive Golomb-like coding of the residuals, one set of medians per channel.
+#[derive(Default)]
+struct Words {
+    channels: [Entropy; 2],
+    holding_one: bool,
+    holding_zero: bool,
+    /// Zeros left in a run
+    zeros: u32,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Entropy {
+    median: [u32; 3],
+}
+
+impl Entropy {
+    fn median(&self, i: usize) -> u32 {
+        (self.median[i] >> 4) + 1
+    }
+
+    fn increase(&mut self, i: usize) {
+        let div = 128 >> i;
+        self.median[i] = self.median[i].wrapping_add((self.median[i] + div) / div * 5);
+    }
+
+    fn decrease(&mut self, i: usize) {
+        let div = 128 >> i;
+        self.median[i] = self.median[i].wrapping_sub((self.median[i] + div - 2) / div * 2);
+    }
+}
+
+impl Words {
+    /// Reads the residual of a sample of channel `chan` (0 or 1).
+    fn read(&mut self, bits: &mut Bits, chan: usize) -> Result<i32> {
+        // silence is coded as runs of zeros
+        if self.channels[0].median[0] < 2
+            && self.channels[1].median[0] < 2
+            && !self.holding_zero
+            && !self.holding_one
+        {
+            if self.zeros > 0 {
+                self.zeros -= 1;
+                if self.zeros > 0 {
+                    return Ok(0);
+                }
+            } else {
+                self.zeros = bits.count()?;
+                if self.zeros > 0 {
+                    self.channels[0].median = [0; 3];
+                    self.channels[1].median = [0; 3];
+                    return Ok(0);
+                }
+            }
+        }
+
+        let ones = if self.holding_zero {
+            self.holding_zero = false;
+            0
+        } else {
+            let mut ones = bits.ones(17)?;
+            if ones == 17 {
+                return decode_error("wavpack: invalid residual");
+            }
+            if ones == 16 {
+                ones += bits.count()?;
+            }
+            // an odd count carries over to the next residual
+            let carry = self.holding_one;
+            self.holding_one = ones & 1 != 0;
+            self.holding_zero = !self.holding_one;
+            if carry { (ones >> 1) + 1 } else { ones >> 1 }
+        };
+
+        let c = &mut self.channels[chan];
+        let (mut low, mut high);
+        if ones == 0 {
+            low = 0;
+            high = c.median(0) - 1;
+            c.decrease(0);
+        } else {
+            low = c.median(0);
+            c.increase(0);
+            if ones == 1 {
+                high = low + c.median(1) - 1;
+                c.decrease(1);
+            } else {
+                low = low.wrapping_add(c.median(1));
+                c.increase(1);
+                if ones == 2 {
+                    high = low.wrapping_add(c.median(2) - 1);
+                    c.decrease(2);
+                } else {
+                    low = low.wrapping_add((ones - 2).wrapping_mul(c.median(2)));
+                    high = low.wrapping_add(c.median(2) - 1);
+                    c.increase(2);
+                }
+            }
+        }
+        low &= 0x7fff_ffff;
+        high = (high & 0x7fff_ffff).max(low);
+
+        let value = low + bits.code(high - low)?;
+        let negative = bits.bit()?;
+        Ok(if negative {
+            !(value as i32)
+        } else {
+            value as i32
+        })
+    }
+}
+
+/// One pass of the decorrelation, predicting samples from earlier ones or from the other channel.
+#[derive(Clone, Copy, Default)]
+struct DecorrPass {
+    /// 1 to 8: the sample `term` samples before, 17 and 18: extrapolation of the last two,
+    /// -1 to -3: the other channel
+    term: i32,
+    delta: i32,
+    weight_a: i32,
+    weight_b: i32,
+    samples_a: [i32; 8],
+    samples_b: [i32; 8],
+}
+
+fn apply_weight(weight: i32, sample: i32) -> i32 {
+    ((weight as i64 * sample as i64 + 512) >> 10) as i32
+}
+
+fn update_weight(weight: &mut i32, delta: i32, source: i32, result: i32) {
+    if source != 0 && result != 0 {
+        *weight += if (source ^ result) < 0 { -delta } else { delta };
+    }
+}
+
+fn update_weight_clip(weight: &mut i32, delta: i32, source: i32, result: i32) {
+    if source != 0 && result != 0 {
+        *weight = if (source ^ result) < 0 {
+            (*weight - delta).max(-1024)
+        } else {
+            (*weight + delta).min(1024)
+        };
+    }
+}
+
+impl DecorrPass {
+    /// Prediction of a term from 1 to 18 for `samples` at position `pos`, the index the result
+    /// is stored at is returned with it.
+    fn predict(term: i32, samples: &mut [i32; 8], pos: usize) -> (i32, usize) {
+        match term {
+            17 | 18 => {
+                let predicted = if term == 17 {
+                    samples[0].wrapping_mul(2).wrapping_sub(samples[1])
+                } else {
+                    samples[0].wrapping_mul(3).wrapping_sub(samples[1]) >> 1
+                };
+                samples[1] = samples[0];
+                (predicted, 0)
+            }
+            _ => (samples[pos], (pos + term as usize) & 7),
+        }
+    }
+
+    fn mono(&mut self, value: i32, pos: usize) -> i32 {
+        let (predicted, j) = Self::predict(self.term, &mut self.samples_a, pos);
+        let sample = value.wrapping_add(apply_weight(self.weight_a, predicted));
+        update_weight(&mut self.weight_a, self.delta, predicted, value);
+        self.samples_a[j] = sample;
+        sample
+    }
+
+    fn stereo(&mut self, [left, right]: [i32; 2], pos: usize) -> [i32; 2] {
+        match self.term {
+            1.. => {
+                let (a, j) = Self::predict(self.term, &mut self.samples_a, pos);
+                let (b, _) = Self::predict(self.term, &mut self.samples_b, pos);
+                let l = left.wrapping_add(apply_weight(self.weight_a, a));
+                let r = right.wrapping_add(apply_weight(self.weight_b, b));
+                update_weight(&mut self.weight_a, self.delta, a, left);
+                update_weight(&mut self.weight_b, self.delta, b, right);
+                self.samples_a[j] = l;
+                self.samples_b[j] = r;
+                [l, r]
+            }
+            -1 => {
+                let l = left.wrapping_add(apply_weight(self.weight_a, self.samples_a[0]));
+                update_weight_clip(&mut self.weight_a, self.delta, self.samples_a[0], left);
+                let r = right.wrapping_add(apply_weight(self.weight_b, l));
+                update_weight_clip(&mut self.weight_b, self.delta, l, right);
+                self.samples_a[0] = r;
+                [l, r]
+            }
+            -2 => {
+                let r = right.wrapping_add(apply_weight(self.weight_b, self.samples_b[0]));
+                update_weight_clip(&mut self.weight_b, self.delta, self.samples_b[0], right);
+                let l = left.wrapping_add(apply_weight(self.weight_a, r));
+                update_weight_clip(&mut self.weight_a, self.delta, r, left);
+                self.samples_b[0] = l;
+                [l, r]
+            }
+            _ => {
+                let l = left.wrapping_add(apply_weight(self.weight_a, self.samples_a[0]));
+                update_weight_clip(&mut self.weight_a, self.delta, self.samples_a[0], left);
+                let r = right.wrapping_add(apply_weight(self.weight_b, self.samples_b[0]));
+                update_weight_clip(&mut self.weight_b, self.delta, self.samples_b[0], right);
+                self.samples_b[0] = l;
+                self.samples_a[0] = r;
+                [l, r]
+            }
+        }
+    }
+}
+
+/// How the low bits of samples with a large magnitude were left out before coding.
+#[derive(Clone, Copy, Default)]
+struct Int32Info {
+    shift: u32,
+    /// The shifted out bits are ones, or copies of the lowest remaining bit
+    ones: bool,
+    dups: bool,
+}
+
+/// Decodes the samples of a block, interleaved if it holds a stereo pair.
+fn decode_block(header: &BlockHeader, data: &[u8]) -> Result<Vec<f32>> {
+    header.check_supported()?;
+    let flags = header.flags;
+    let mono = header.is_mono_data();
+    let mut passes: Vec<DecorrPass> = Vec::new();
+    let mut words = Words::default();
+    let mut int32 = Int32Info::default();
+    let mut bitstream = None;
+    for (id, data) in sub_blocks(data)? {
+        match id {
+            // the passes are stored last one first
+            ID_DECORR_TERMS => {
+                passes = data
+                    .iter()
+                    .rev()
+                    .map(|&b| DecorrPass {
+                        term: (b & 0x1f) as i32 - 5,
+                        delta: (b >> 5 & 0x7) as i32,
+                        ..DecorrPass::default()
+                    })
+                    .collect();
+                let valid =
+                    |t: i32| matches!(t, 1..=8 | 17 | 18) || (!mono && (-3..=-1).contains(&t));
+                if passes.len() > 16 || passes.iter().any(|p| !valid(p.term)) {
+                    return decode_error("wavpack: invalid decorrelation terms");
+                }
+            }
+            ID_DECORR_WEIGHTS => {
+                // weights are stored for the last passes, the others stay 0
+                let restore = |b: u8| {
+                    let weight = (b as i8 as i32) << 3;
+                    if weight > 0 {
+                        weight + ((weight + 64) >> 7)
+                    } else {
+                        weight
+                    }
+                };
+                let per_pass = if mono { 1 } else { 2 };
+                if data.len() / per_pass > passes.len() {
+                    return decode_error("wavpack: invalid decorrelation weights");
+                }
+                for (pass, weights) in passes.iter_mut().rev().zip(data.chunks_exact(per_pass)) {
+                    pass.weight_a = restore(weights[0]);
+                    if !mono {
+                        pass.weight_b = restore(weights[1]);
+                    }
+                }
+            }
+            ID_DECORR_SAMPLES => {
+                let mut values = data
+                    .chunks_exact(2)
+                    .map(|b| exp2(i16::from_le_bytes([b[0], b[1]]) as i32));
+                for pass in passes.iter_mut().rev() {
+                    let mut next = || values.next();
+                    let ok = match pass.term {
+                        17 | 18 if mono => next().zip(next()).map(|(a0, a1)| {
+                            pass.samples_a[..2].copy_from_slice(&[a0, a1]);
+                        }),
+                        17 | 18 => next().zip(next()).zip(next().zip(next())).map(
+                            |((a0, a1), (b0, b1))| {
+                                pass.samples_a[..2].copy_from_slice(&[a0, a1]);
+                                pass.samples_b[..2].copy_from_slice(&[b0, b1]);
+                            },
+                        ),
+                        ..0 => next().zip(next()).map(|(a, b)| {
+                            pass.samples_a[0] = a;
+                            pass.samples_b[0] = b;
+                        }),
+                        term => (0..term as usize)
+                            .map(|m| {
+                                pass.samples_a[m] = next()?;
+                                if !mono {
+                                    pass.samples_b[m] = next()?;
+                                }
+                                Some(())
+                            })
+                            .collect(),
+                    };
+                    // passes without stored samples start from silence
+                    if ok.is_none() {
+                        break;
+                    }
+                }
+            }
+            ID_ENTROPY_VARS => {
+                let needed = if mono { 6 } else { 12 };
+                if data.len() < needed {
+                    return decode_error("wavpack: invalid entropy variables");
+                }
+                for (i, b) in data[..needed].chunks_exact(2).enumerate() {
+                    let median = exp2(i16::from_le_bytes([b[0], b[1]]) as i32) as u32;
+                    words.channels[i / 3].median[i % 3] = median;
+                }
+            }
+            ID_INT32_INFO if data.len() >= 4 => {
+                int32 = match data[..4] {
+                    // the low bits are sent in a separate bitstream for 32 bit audio
+                    [1..=u8::MAX, ..] => {
+                        return unsupported_error("wavpack: 32 bit audio is not supported");
+                    }
+                    [0, shift, 0, 0] => Int32Info {
+                        shift: shift as u32,
+                        ..Int32Info::default()
+                    },
+                    [0, 0, shift, 0] => Int32Info {
+                        shift: shift as u32,
+                        ones: true,
+                        ..Int32Info::default()
+                    },
+                    [0, 0, 0, shift] => Int32Info {
+                        shift: shift as u32,
+                        dups: true,
+                        ..Int32Info::default()
+                    },
+                    _ => return decode_error("wavpack: invalid 32 bit info"),
+                };
+                if int32.shift > 31 {
+                    return decode_error("wavpack: invalid 32 bit info");
+                }
+            }
+            ID_WV_BITSTREAM => bitstream = Some(data),
+            _ => (),
+        }
+    }
+    let Some(bitstream) = bitstream else {
+        return decode_error("wavpack: missing bitstream");
+    };
+    let mut bits = Bits::new(bitstream);
+
+    let channels = header.channels();
+    let frames = header.block_samples as usize;
+    let mut samples = Vec::with_capacity(frames * channels);
+    let mut crc = u32::MAX;
+    for pos in 0..frames {
+        let pos = pos & 7;
+        if mono {
+            let mut value = words.read(&mut bits, 0)?;
+            for pass in &mut passes {
+                value = pass.mono(value, pos);
+            }
+            crc = crc.wrapping_mul(3).wrapping_add(value as u32);
+            let sample = int_value(value, header, int32);
+            samples.push(sample);
+            // a false stereo block holds the same samples for both channels
+            if channels == 2 {
+                samples.push(sample);
+            }
+        } else {
+            let mut pair = [words.read(&mut bits, 0)?, words.read(&mut bits, 1)?];
+            for pass in &mut passes {
+                pair = pass.stereo(pair, pos);
+            }
+            let [mut left, mut right] = pair;
+            if flags & JOINT_STEREO != 0 {
+                right = right.wrapping_sub(left >> 1);
+                left = left.wrapping_add(right);
+            }
+            crc = crc
+                .wrapping_mul(3)
+                .wrapping_add(left as u32)
+                .wrapping_mul(3)
+                .wrapping_add(right as u32);
+            samples.push(int_value(left, header, int32));
+            samples.push(int_value(right, header, int32));
+        }
+    }
+    if crc != header.crc {
+        return decode_error("wavpack: checksum mismatch");
+    }
+    Ok(samples)
+}
+
+/// Restores an integer sample and scales it to -1..1.
+fn int_value(value: i32, header: &BlockHeader, int32: Int32Info) -> f32 {
+    let value = value as i64;
+    let low = if int32.ones {
+        1
+    } else if int32.dups {
+        value & 1
+    } else {
+        0
+    };
+    let value = ((value + low) << int32.shift) - low;
+    let value = value << (header.flags >> SHIFT_LSB & 0x1f);
+    let bits = header.bytes_per_sample() * 8;
+    (value as f64 / (1u64 << (bits - 1)) as f64) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe;
+    use std::io::Cursor;
+    use symphonia::core::{audio::SampleBuffer, meta::MetadataOptions, probe::Hint};
+
+    /// Writes bits least significant first, the inverse of [`Bits`].
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        len: usize,
+    }
+
+    impl BitWriter {
+        fn bit(&mut self, bit: bool) {
+            if self.len.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            *self.bytes.last_mut().unwrap() |= (bit as u8) << (self.len % 8);
+            self.len += 1;
+        }
+
+        fn bits(&mut self, value: u32, n: u32) {
+            for i in 0..n {
+                self.bit(value >> i & 1 != 0);
+            }
+        }
+
+        fn ones(&mut self, n: u32) {
+            for _ in 0..n {
+                self.bit(true);
+            }
+            self.bit(false);
+        }
+
+        fn count(&mut self, n: u32) {
+            if n < 2 {
+                self.ones(n);
+            } else {
+                let bits = u32::BITS - n.leading_zeros();
+                self.ones(bits);
+                self.bits(n, bits - 1);
+            }
+        }
+
+        fn code(&mut self, code: u32, max: u32) {
+            if max == 0 {
+                return;
+            }
+            let bits = u32::BITS - max.leading_zeros();
+            let extras = ((1u64 << bits) - max as u64 - 1) as u32;
+            if code < extras {
+                self.bits(code, bits - 1);
+            } else {
+                self.bits((code + extras) >> 1, bits - 1);
+                self.bit((code + extras) & 1 != 0);
+            }
+        }
+    }
+
+    /// How [`Words::read`] reads a residual.
+    #[derive(Clone, Copy, Default)]
+    struct Word {
+        /// Length of a run of zeros starting here, coded before the residual
+        run: Option<u32>,
+        /// The residual is a zero of a run and not coded
+        in_run: bool,
+        /// The unary count is coded, it is left out when the previous one said this is 0
+        count: bool,
+        ones: u32,
+        code: u32,
+        max: u32,
+        negative: bool,
+    }
+
+    /// Codes residuals the way [`Words::read`] reads them, interleaved if `stereo`.
+    fn encode_words(residuals: &[i32], stereo: bool, median: u32) -> Vec<u8> {
+        let mut channels = [Entropy::default(); 2];
+        for c in channels.iter_mut().take(if stereo { 2 } else { 1 }) {
+            c.median = [median; 3];
+        }
+        // the unary count of a word tells whether the next one is 0, so the counts come first
+        let mut words: Vec<Word> = Vec::new();
+        // the last count set `holding_one` or `holding_zero`, which rules out a run
+        let mut holding = false;
+        let mut zeros = 0;
+        for (i, &residual) in residuals.iter().enumerate() {
+            let mut word = Word::default();
+            if !holding && channels.iter().all(|c| c.median[0] < 2) {
+                if zeros > 0 {
+                    zeros -= 1;
+                } else {
+                    zeros = residuals[i..].iter().take_while(|&&r| r == 0).count() as u32;
+                    word.run = Some(zeros);
+                    if zeros > 0 {
+                        channels = [Entropy::default(); 2];
+                    }
+                }
+                if zeros > 0 {
+                    word.in_run = true;
+                    words.push(word);
+                    continue;
+                }
+            }
+            let c = &mut channels[if stereo { i % 2 } else { 0 }];
+            let value = if residual < 0 { !residual } else { residual } as u32;
+            let (ones, low, high) = if value < c.median(0) {
+                let high = c.median(0) - 1;
+                c.decrease(0);
+                (0, 0, high)
+            } else {
+                let low = c.median(0);
+                c.increase(0);
+                if value - low < c.median(1) {
+                    let high = low + c.median(1) - 1;
+                    c.decrease(1);
+                    (1, low, high)
+                } else {
+                    let low = low + c.median(1);
+                    c.increase(1);
+                    let steps = (value - low) / c.median(2);
+                    let low = low + steps * c.median(2);
+                    let high = low + c.median(2) - 1;
+                    if steps == 0 {
+                        c.decrease(2);
+                    } else {
+                        c.increase(2);
+                    }
+                    (2 + steps, low, high)
+                }
+            };
+            word.count = !(holding && ones == 0);
+            holding = word.count;
+            words.push(Word {
+                ones,
+                code: value - low,
+                max: high - low,
+                negative: residual < 0,
+                ..word
+            });
+        }
+
+        let mut bits = BitWriter::default();
+        let mut holding_one = false;
+        for (i, word) in words.iter().enumerate() {
+            if let Some(zeros) = word.run {
+                bits.count(zeros);
+            }
+            if word.in_run {
+                continue;
+            }
+            if word.count {
+                let next = words.get(i + 1).is_some_and(|w| w.ones > 0);
+                let count = (word.ones - holding_one as u32) * 2 + next as u32;
+                if count >= 16 {
+                    bits.ones(16);
+                    bits.count(count - 16);
+                } else {
+                    bits.ones(count);
+                }
+                holding_one = next;
+            }
+            bits.code(word.code, word.max);
+            bits.bit(word.negative);
+        }
+        bits.bytes
+    }
+
+    fn sub_block(id: u8, data: &[u8]) -> Vec<u8> {
+        let words = data.len().div_ceil(2);
+        let odd = if data.len() % 2 == 1 { ID_ODD_SIZE } else { 0 };
+        let mut block = if words > 0xff {
+            let size = (words as u32).to_le_bytes();
+            vec![id | odd | ID_LARGE, size[0], size[1], size[2]]
+        } else {
+            vec![id | odd, words as u8]
+        };
+        block.extend_from_slice(data);
+        block.resize(block.len() + data.len() % 2, 0);
+        block
+    }
+
+    /// A lossless block to encode.
+    #[derive(Default)]
+    struct TestBlock<'a> {
+        /// Samples of one channel, or of a stereo pair interleaved
+        samples: &'a [i32],
+        /// Sample size, channels, stereo mode, shift, position in the frame and sample rate
+        flags: u32,
+        block_index: u32,
+        /// Low zero bits left out with an int32 info sub-block
+        int32_shift: u32,
+        /// Channel count and mask of the file
+        channel_info: Option<&'a [u8]>,
+    }
+
+    impl TestBlock<'_> {
+        /// Encodes the block with three decorrelation passes, two for one channel.
+        fn encode(&self) -> Vec<u8> {
+            let stereo = self.flags & MONO_DATA == 0;
+            let terms: &[_] = if stereo {
+                &[(17, 2, 127), (2, 2, 64), (-1, 3, 0)]
+            } else {
+                &[(17, 2, 127), (2, 2, 64)]
+            };
+            let mut passes: Vec<_> = terms
+                .iter()
+                .map(|&(term, delta, weight)| {
+                    let restore = |w: i32| {
+                        if w > 0 {
+                            (w << 3) + (((w << 3) + 64) >> 7)
+                        } else {
+                            w << 3
+                        }
+                    };
+                    (
+                        DecorrPass {
+                            term,
+                            delta,
+                            weight_a: restore(weight),
+                            weight_b: restore(weight),
+                            ..DecorrPass::default()
+                        },
+                        ((term + 5) as u8 | (delta as u8) << 5, weight as i8 as u8),
+                    )
+                })
+                .collect();
+
+            let shift = (self.flags >> SHIFT_LSB & 0x1f) + self.int32_shift;
+            let values: Vec<i32> = self
+                .samples
+                .iter()
+                .map(|&sample| {
+                    assert_eq!(sample >> shift << shift, sample);
+                    sample >> shift
+                })
+                .collect();
+            let mut residuals = Vec::new();
+            let mut crc = u32::MAX;
+            if stereo {
+                for (pos, pair) in values.chunks_exact(2).enumerate() {
+                    let pos = pos & 7;
+                    let [left, right] = [pair[0], pair[1]];
+                    crc = crc
+                        .wrapping_mul(3)
+                        .wrapping_add(left as u32)
+                        .wrapping_mul(3)
+                        .wrapping_add(right as u32);
+                    let mut pair = if self.flags & JOINT_STEREO != 0 {
+                        [left - right, right + ((left - right) >> 1)]
+                    } else {
+                        [left, right]
+                    };
+                    // undo the passes, the last one first
+                    for (pass, _) in passes.iter_mut().rev() {
+                        let left = pair[0].wrapping_sub(pass.clone().stereo([0, 0], pos)[0]);
+                        let right = pair[1].wrapping_sub(pass.clone().stereo([left, 0], pos)[1]);
+                        assert_eq!(pass.stereo([left, right], pos), pair);
+                        pair = [left, right];
+                    }
+                    residuals.extend(pair);
+                }
+            } else {
+                for (pos, &value) in values.iter().enumerate() {
+                    let pos = pos & 7;
+                    crc = crc.wrapping_mul(3).wrapping_add(value as u32);
+                    let mut value = value;
+                    for (pass, _) in passes.iter_mut().rev() {
+                        let residual = value.wrapping_sub(pass.clone().mono(0, pos));
+                        assert_eq!(pass.mono(residual, pos), value);
+                        value = residual;
+                    }
+                    residuals.push(value);
+                }
+            }
+
+            // the passes are stored last one first, the weights as well
+            let terms: Vec<u8> = passes.iter().rev().map(|(_, (term, _))| *term).collect();
+            let weights: Vec<u8> = passes
+                .iter()
+                .rev()
+                .flat_map(|(_, (_, w))| if stereo { vec![*w, *w] } else { vec![*w] })
+                .collect();
+            // medians of 256, exp2(0x900)
+            let medians: Vec<u8> = [0x900i16; 6][..if stereo { 6 } else { 3 }]
+                .iter()
+                .flat_map(|m| m.to_le_bytes())
+                .collect();
+            let mut data = sub_block(ID_DECORR_TERMS, &terms);
+            data.extend(sub_block(ID_DECORR_WEIGHTS, &weights));
+            data.extend(sub_block(ID_ENTROPY_VARS, &medians));
+            if self.int32_shift > 0 {
+                data.extend(sub_block(ID_INT32_INFO, &[0, self.int32_shift as u8, 0, 0]));
+            }
+            if let Some(channel_info) = self.channel_info {
+                data.extend(sub_block(ID_CHANNEL_INFO, channel_info));
+            }
+            data.extend(sub_block(
+                ID_WV_BITSTREAM,
+                &encode_words(&residuals, stereo, exp2(0x900) as u32),
+            ));
+
+            let frames = self.samples.len() / if stereo { 2 } else { 1 };
+            let mut block = b"wvpk".to_vec();
+            block.extend(((HEADER_LEN + data.len() - 8) as u32).to_le_bytes());
+            block.extend([0x10, 0x04, 0, 0]);
+            for value in [0, self.block_index, frames as u32, self.flags, crc] {
+                block.extend(value.to_le_bytes());
+            }
+            block.extend(data);
+            block
+        }
+    }
+
+    fn open(file: Vec<u8>) -> Result<Box<dyn FormatReader>> {
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(file)), Default::default());
+        let mut hint = Hint::new();
+        hint.with_extension("wv");
+        let probed = probe::formats().format(
+            &hint,
+            mss,
+            &Default::default(),
+            &MetadataOptions::default(),
+        )?;
+        Ok(probed.format)
+    }
+
+    /// Decodes a file, returns its parameters and its interleaved samples.
+    fn decode(file: Vec<u8>) -> (CodecParameters, Vec<f32>) {
+        let mut format = open(file).unwrap();
+        let params = format.default_track().unwrap().codec_params.clone();
+        let mut decoder = probe::make_decoder(&params).unwrap();
+        let mut samples = Vec::new();
+        while let Ok(packet) = format.next_packet() {
+            let decoded = decoder.decode(&packet).unwrap();
+            let mut buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+            buf.copy_interleaved_ref(decoded);
+            samples.extend_from_slice(buf.samples());
+        }
+        (params, samples)
+    }
+
+    /// A tone with some noise, loud enough to keep the medians away from runs of zeros.
+    fn tone(frames: usize, amplitude: f64) -> Vec<[i32; 2]> {
+        let mut seed = 1u32;
+        (0..frames)
+            .map(|i| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let noise = ((seed >> 16) as f64 / 65536. - 0.5) * amplitude / 4.;
+                let tone = amplitude * (i as f64 * 0.05).sin();
+                [(tone + noise) as i32, (tone / 2. - noise) as i32]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decode() {
+        // silence in the middle is coded as runs of zeros
+        let mut frames = tone(3000, 8000.);
+        frames[2200..2700].fill([0, 0]);
+        let flags = 1 | JOINT_STEREO | 9 << SRATE_LSB;
+        let mut file = TestBlock {
+            samples: &frames[..2000].concat(),
+            flags: flags | INITIAL_BLOCK | FINAL_BLOCK,
+            ..TestBlock::default()
+        }
+        .encode();
+        file.extend(
+            TestBlock {
+                samples: &frames[2000..].concat(),
+                flags: flags | INITIAL_BLOCK | FINAL_BLOCK,
+                block_index: 2000,
+                ..TestBlock::default()
+            }
+            .encode(),
+        );
+        // the header of the total length is only read from the first block
+        file[12..16].copy_from_slice(&3000u32.to_le_bytes());
+        file.extend(b"APETAGEX");
+
+        let (params, samples) = decode(file);
+        assert_eq!(params.sample_rate, Some(44100));
+        assert_eq!(
+            params.channels,
+            Some(Channels::FRONT_LEFT | Channels::FRONT_RIGHT)
+        );
+        assert_eq!(params.bits_per_sample, Some(16));
+        assert_eq!(params.n_frames, Some(3000));
+        assert_eq!(probe::codec_name(params.codec), "WAVPACK");
+        let expected: Vec<f32> = frames.concat().iter().map(|&s| s as f32 / 32768.).collect();
+        assert_eq!(samples, expected);
+    }
+
+    #[test]
+    fn test_decode_runs() {
+        // without runs every zero takes at least two bits
+        let silence = TestBlock {
+            samples: &[0; 4000],
+            flags: 1 | JOINT_STEREO | INITIAL_BLOCK | FINAL_BLOCK | 9 << SRATE_LSB,
+            ..TestBlock::default()
+        }
+        .encode();
+        assert!(silence.len() < 200, "{}", silence.len());
+        let (_, samples) = decode(silence);
+        assert_eq!(samples, [0.; 4000]);
+    }
+
+    #[test]
+    fn test_decode_mono() {
+        let samples: Vec<i32> = tone(1000, 8000.).iter().map(|[l, _]| *l).collect();
+        let mono = TestBlock {
+            samples: &samples,
+            flags: 1 | MONO_FLAG | INITIAL_BLOCK | FINAL_BLOCK | 10 << SRATE_LSB,
+            ..TestBlock::default()
+        };
+        let (params, decoded) = decode(mono.encode());
+        assert_eq!(params.channels, Some(Channels::FRONT_LEFT));
+        assert_eq!(params.sample_rate, Some(48000));
+        let expected: Vec<f32> = samples.iter().map(|&s| s as f32 / 32768.).collect();
+        assert_eq!(decoded, expected);
+
+        // 8 bit, the same samples on both channels
+        let samples: Vec<i32> = samples.iter().map(|s| s / 256).collect();
+        let false_stereo = TestBlock {
+            samples: &samples,
+            flags: FALSE_STEREO | INITIAL_BLOCK | FINAL_BLOCK | 10 << SRATE_LSB,
+            ..TestBlock::default()
+        };
+        let (params, decoded) = decode(false_stereo.encode());
+        assert_eq!(
+            params.channels,
+            Some(Channels::FRONT_LEFT | Channels::FRONT_RIGHT)
+        );
+        assert_eq!(params.bits_per_sample, Some(8));
+        let expected: Vec<f32> = samples.iter().flat_map(|&s| [s as f32 / 128.; 2]).collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_decode_channels() {
+        // 24 bit, 3 channels in a stereo and a mono block, with low zero bits left out
+        let frames = tone(1500, 2_000_000.);
+        let pair: Vec<i32> = frames
+            .iter()
+            .flat_map(|[l, r]| [l & !0xf, r & !0xf])
+            .collect();
+        let centre: Vec<i32> = frames.iter().map(|[l, r]| ((l + r) / 2) & !0xff).collect();
+        let flags = 2 | 13 << SRATE_LSB;
+        let mut file = Vec::new();
+        for (start, end) in [(0, 1000), (1000, 1500)] {
+            file.extend(
+                TestBlock {
+                    samples: &pair[start * 2..end * 2],
+                    flags: flags | INITIAL_BLOCK | 4 << SHIFT_LSB,
+                    block_index: start as u32,
+                    channel_info: Some(&[3, 0x7]),
+                    ..TestBlock::default()
+                }
+                .encode(),
+            );
+            file.extend(
+                TestBlock {
+                    samples: &centre[start..end],
+                    flags: flags | MONO_FLAG | FINAL_BLOCK,
+                    block_index: start as u32,
+                    int32_shift: 8,
+                    ..TestBlock::default()
+                }
+                .encode(),
+            );
+        }
+
+        let (params, samples) = decode(file);
+        assert_eq!(params.sample_rate, Some(96000));
+        assert_eq!(
+            params.channels,
+            Some(Channels::FRONT_LEFT | Channels::FRONT_RIGHT | Channels::FRONT_CENTRE)
+        );
+        assert_eq!(params.bits_per_sample, Some(24));
+        let expected: Vec<f32> = (0..1500)
+            .flat_map(|i| [pair[i * 2], pair[i * 2 + 1], centre[i]])
+            .map(|s| s as f32 / 8_388_608.)
+            .collect();
+        assert_eq!(samples, expected);
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let block = TestBlock {
+            samples: &[1, 2, 3, 4],
+            flags: 1 | INITIAL_BLOCK | FINAL_BLOCK | 9 << SRATE_LSB,
+            ..TestBlock::default()
+        }
+        .encode();
+        assert!(open(block.clone()).is_ok());
+        for (flags, message) in [
+            (
+                HYBRID_FLAG,
+                "wavpack: hybrid (lossy) audio is not supported",
+            ),
+            (FLOAT_DATA, "wavpack: float audio is not supported"),
+            (BYTES_STORED, "wavpack: 32 bit audio is not supported"),
+            (DSD_FLAG, "wavpack: DSD audio is not supported"),
+        ] {
+            let mut block = block.clone();
+            block[24] |= flags as u8;
+            block[27] |= (flags >> 24) as u8;
+            assert!(
+                matches!(open(block), Err(Error::Unsupported(m)) if m == message),
+                "{message}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_score() {
+        let block = TestBlock {
+            samples: &[1, 2],
+            flags: 1 | INITIAL_BLOCK | FINAL_BLOCK,
+            ..TestBlock::default()
+        }
+        .encode();
+        assert_eq!(WavPackReader::score(&block[..16]), 255);
+        assert_eq!(WavPackReader::score(&block[..4]), 0);
+        // a version from the future
+        let mut context = block[..16].to_vec();
+        context[9] = 0x05;
+        assert_eq!(WavPackReader::score(&context), 0);
+    }
+
+    #[test]
+    fn test_exp2() {
+        assert_eq!(exp2(0x900), 256);
+        assert_eq!(exp2(0x100), 1);
+        assert_eq!(exp2(0x200), 2);
+        assert_eq!(exp2(-0x100), -1);
+        assert_eq!(exp2(0x980), 362);
+    }
+}